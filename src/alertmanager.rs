@@ -0,0 +1,281 @@
+//! Translates Prometheus Alertmanager webhook notifications into Events V2 events.
+//!
+//! Every notification is for one alert group, so the group key is the dedup key:
+//! the group's incident is triggered while any of its alerts fire, and resolved
+//! once they all have.
+
+use crate::types::*;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+pub type KV = BTreeMap<String, String>;
+
+/// An Alertmanager webhook notification (version 4).
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub version: String,
+    pub group_key: String,
+    #[serde(default)]
+    pub truncated_alerts: u64,
+    pub status: Status,
+    pub receiver: String,
+    #[serde(default)]
+    pub group_labels: KV,
+    #[serde(default)]
+    pub common_labels: KV,
+    #[serde(default)]
+    pub common_annotations: KV,
+    #[serde(rename = "externalURL")]
+    pub external_url: String,
+    pub alerts: Vec<Alert>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    pub status: Status,
+    #[serde(default)]
+    pub labels: KV,
+    #[serde(default)]
+    pub annotations: KV,
+    pub starts_at: String,
+    #[serde(default)]
+    pub ends_at: String,
+    #[serde(rename = "generatorURL", default)]
+    pub generator_url: String,
+    #[serde(default)]
+    pub fingerprint: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Firing,
+    Resolved,
+}
+
+/// Which labels map onto which parts of an alert.
+pub struct AlertmanagerOptions {
+    /// Used as the alert's source when the group has no common `instance` label.
+    pub source: String,
+
+    pub severity_label: String,
+    pub component_label: String,
+    pub group_label: String,
+    pub class_label: String,
+
+    /// Severity for groups whose severity label is missing or unrecognized.
+    pub default_severity: Severity,
+}
+
+impl AlertmanagerOptions {
+    pub fn new(source: String) -> Self {
+        AlertmanagerOptions {
+            source,
+            severity_label: "severity".to_owned(),
+            component_label: "component".to_owned(),
+            group_label: "group".to_owned(),
+            class_label: "alertname".to_owned(),
+            default_severity: Severity::Error,
+        }
+    }
+}
+
+/// Translate a notification into a trigger (while any alert in the group fires)
+/// or a resolve (once they all have resolved).
+pub fn to_event(webhook: &Webhook, options: &AlertmanagerOptions) -> Event<Value> {
+    if webhook.status == Status::Resolved {
+        return Event::AlertResolve(AlertResolve {
+            dedup_key: hash_dedup_key(&webhook.group_key),
+        });
+    }
+
+    let firing: Vec<&Alert> = webhook
+        .alerts
+        .iter()
+        .filter(|a| a.status == Status::Firing)
+        .collect();
+
+    let label = |name: &str| -> Option<String> {
+        webhook
+            .common_labels
+            .get(name)
+            .or_else(|| webhook.group_labels.get(name))
+            .cloned()
+    };
+
+    let summary = match webhook.common_annotations.get("summary") {
+        Some(s) => s.clone(),
+        None => format!(
+            "[FIRING:{}] {}",
+            firing.len(),
+            webhook
+                .group_labels
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<String>>()
+                .join(" ")
+        ),
+    };
+
+    let mut links: Links = vec![];
+    for alert in firing.iter() {
+        if !alert.generator_url.is_empty() && !links.iter().any(|l| l.href == alert.generator_url) {
+            links.push(Link {
+                href: alert.generator_url.clone(),
+                text: Some("Source".to_owned()),
+            });
+        }
+    }
+    if !webhook.external_url.is_empty() {
+        links.push(Link {
+            href: webhook.external_url.clone(),
+            text: Some("Alertmanager".to_owned()),
+        });
+    }
+
+    Event::AlertTrigger(AlertTrigger {
+        payload: AlertTriggerPayload {
+            severity: label(&options.severity_label)
+                .and_then(|s| Severity::from_label(&s))
+                .unwrap_or(options.default_severity),
+            summary: truncate_summary(&summary),
+            source: label("instance").unwrap_or_else(|| options.source.clone()),
+            timestamp: firing
+                .iter()
+                .filter_map(|a| OffsetDateTime::parse(&a.starts_at, &Rfc3339).ok())
+                .min(),
+            component: label(&options.component_label),
+            group: label(&options.group_label),
+            class: label(&options.class_label),
+            custom_details: Some(serde_json::json!({
+                "num_firing": firing.len(),
+                "num_resolved": webhook.alerts.len() - firing.len(),
+                "truncated_alerts": webhook.truncated_alerts,
+                "group_labels": webhook.group_labels,
+                "common_labels": webhook.common_labels,
+                "common_annotations": webhook.common_annotations,
+                "firing": firing
+                    .iter()
+                    .map(|a| serde_json::json!({
+                        "labels": a.labels,
+                        "annotations": a.annotations,
+                        "starts_at": a.starts_at,
+                    }))
+                    .collect::<Vec<Value>>(),
+            })),
+        },
+        dedup_key: Some(hash_dedup_key(&webhook.group_key)),
+        images: None,
+        links: if links.is_empty() { None } else { Some(links) },
+        client: Some("Alertmanager".to_owned()),
+        client_url: if webhook.external_url.is_empty() {
+            None
+        } else {
+            Some(webhook.external_url.clone())
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const FIRING: &str = r#"{
+        "version": "4",
+        "groupKey": "{}:{alertname=\"HighLatency\"}",
+        "truncatedAlerts": 0,
+        "status": "firing",
+        "receiver": "pagerduty",
+        "groupLabels": {"alertname": "HighLatency"},
+        "commonLabels": {"alertname": "HighLatency", "severity": "critical", "component": "api", "instance": "api-1:9090"},
+        "commonAnnotations": {"summary": "API latency above 2s"},
+        "externalURL": "http://alertmanager:9093",
+        "alerts": [{
+            "status": "firing",
+            "labels": {"alertname": "HighLatency", "severity": "critical"},
+            "annotations": {"summary": "API latency above 2s"},
+            "startsAt": "2021-05-30T00:00:00Z",
+            "endsAt": "0001-01-01T00:00:00Z",
+            "generatorURL": "http://prometheus:9090/graph?g0.expr=latency",
+            "fingerprint": "c6ab5ec3b7a4ef4f"
+        }]
+    }"#;
+
+    #[test]
+    fn firing_group_triggers() {
+        let webhook: Webhook = serde_json::from_str(FIRING).unwrap();
+        let event = to_event(&webhook, &AlertmanagerOptions::new("relay".to_owned()));
+
+        match event {
+            Event::AlertTrigger(at) => {
+                assert_eq!(
+                    at.dedup_key.as_deref(),
+                    Some("{}:{alertname=\"HighLatency\"}")
+                );
+                assert_eq!(at.payload.summary, "API latency above 2s");
+                assert_eq!(at.payload.severity, Severity::Critical);
+                assert_eq!(at.payload.source, "api-1:9090");
+                assert_eq!(at.payload.component.as_deref(), Some("api"));
+                assert_eq!(at.payload.class.as_deref(), Some("HighLatency"));
+                let links = at.links.unwrap();
+                assert_eq!(
+                    links[0].href,
+                    "http://prometheus:9090/graph?g0.expr=latency"
+                );
+                assert_eq!(links[1].href, "http://alertmanager:9093");
+            }
+            _ => panic!("expected a trigger"),
+        }
+    }
+
+    #[test]
+    fn resolved_group_resolves() {
+        let webhook: Webhook =
+            serde_json::from_str(&FIRING.replace("\"firing\"", "\"resolved\"")).unwrap();
+        match to_event(&webhook, &AlertmanagerOptions::new("relay".to_owned())) {
+            Event::AlertResolve(ar) => {
+                assert_eq!(ar.dedup_key, "{}:{alertname=\"HighLatency\"}")
+            }
+            _ => panic!("expected a resolve"),
+        }
+    }
+
+    #[test]
+    fn long_group_keys_are_hashed() {
+        let labels: String = (0..40)
+            .map(|i| format!("label{}=\\\"value\\\",", i))
+            .collect();
+        let firing = FIRING.replace("alertname=\\\"HighLatency\\\"}", &format!("{}}}", labels));
+        let webhook: Webhook = serde_json::from_str(&firing).unwrap();
+        assert!(webhook.group_key.chars().count() > 255);
+
+        let options = AlertmanagerOptions::new("relay".to_owned());
+        let trigger_key = match to_event(&webhook, &options) {
+            Event::AlertTrigger(at) => at.dedup_key.unwrap(),
+            _ => panic!("expected a trigger"),
+        };
+        assert_eq!(trigger_key.chars().count(), 255);
+        assert!(webhook.group_key.starts_with(&trigger_key[..200]));
+
+        // Groups that differ only past the limit still get their own alerts.
+        let other: Webhook = serde_json::from_str(&firing.replace("label39=", "label99=")).unwrap();
+        assert!(other.group_key[..255] == webhook.group_key[..255]);
+        match to_event(&other, &options) {
+            Event::AlertTrigger(at) => assert_ne!(at.dedup_key.unwrap(), trigger_key),
+            _ => panic!("expected a trigger"),
+        }
+
+        let webhook: Webhook =
+            serde_json::from_str(&firing.replace("\"firing\"", "\"resolved\"")).unwrap();
+        match to_event(&webhook, &options) {
+            Event::AlertResolve(ar) => assert_eq!(ar.dedup_key, trigger_key),
+            _ => panic!("expected a resolve"),
+        }
+    }
+}
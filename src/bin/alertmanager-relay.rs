@@ -0,0 +1,170 @@
+//! Receives Prometheus Alertmanager webhooks and forwards them to PagerDuty Events V2.
+//!
+//! Configured through the environment:
+//!
+//! * `PAGERDUTY_ROUTING_KEY`: integration key to send events with (required)
+//! * `RELAY_LISTEN`: address to listen on (default `127.0.0.1:9095`)
+//! * `PAGERDUTY_EVENTS_URL`: Events API base URL (default `https://events.pagerduty.com`)
+//! * `RELAY_SOURCE`: alert source for groups without an `instance` label (default `alertmanager-relay`)
+
+use pagerduty_rs::alertmanager::*;
+use pagerduty_rs::eventsv2sync::*;
+
+use std::env;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Webhooks larger than this are refused with 413.
+const MAX_BODY_LEN: usize = 1024 * 1024;
+
+/// The request line and headers together may be no longer than this.
+const MAX_HEADER_LEN: u64 = 64 * 1024;
+
+/// Connections handled at once; any more are refused with 503.
+const MAX_CONNECTIONS: usize = 16;
+
+/// How long a client may take to send its request or accept the response.
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn main() {
+    let routing_key = match env::var("PAGERDUTY_ROUTING_KEY") {
+        Ok(k) => k,
+        Err(_) => {
+            eprintln!("PAGERDUTY_ROUTING_KEY must be set");
+            process::exit(2);
+        }
+    };
+    let listen = env::var("RELAY_LISTEN").unwrap_or_else(|_| "127.0.0.1:9095".to_owned());
+    let source = env::var("RELAY_SOURCE").unwrap_or_else(|_| "alertmanager-relay".to_owned());
+
+    let mut ev2 = EventsV2::new(
+        routing_key,
+        Some("pagerduty-rs alertmanager-relay".to_owned()),
+    )
+    .unwrap_or_else(|e| {
+        eprintln!("Unable to create Events V2 client: {}", e);
+        process::exit(1);
+    });
+    if let Ok(url) = env::var("PAGERDUTY_EVENTS_URL") {
+        ev2 = ev2.with_base_url(url);
+    }
+    let ev2 = Arc::new(ev2);
+    let options = Arc::new(AlertmanagerOptions::new(source));
+    let active = Arc::new(AtomicUsize::new(0));
+
+    let listener = TcpListener::bind(&listen).unwrap_or_else(|e| {
+        eprintln!("Unable to listen on {}: {}", listen, e);
+        process::exit(1);
+    });
+    eprintln!("Listening for Alertmanager webhooks on {}", listen);
+
+    for mut stream in listener.incoming().flatten() {
+        if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            active.fetch_sub(1, Ordering::SeqCst);
+            let _ = respond(
+                &mut stream,
+                "503 Service Unavailable",
+                "Too many connections",
+            );
+            continue;
+        }
+
+        let (ev2, options, active) = (ev2.clone(), options.clone(), active.clone());
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &ev2, &options) {
+                eprintln!("Error handling webhook: {}", e);
+            }
+            active.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+/// A request as read from a connection.
+enum Request {
+    /// The connection closed without sending a request.
+    Closed,
+
+    /// The body was larger than MAX_BODY_LEN, and wasn't read.
+    TooLarge,
+
+    Body(Vec<u8>),
+}
+
+fn handle(
+    mut stream: TcpStream,
+    ev2: &EventsV2,
+    options: &AlertmanagerOptions,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    let body = match read_request(&mut stream)? {
+        Request::Body(body) => body,
+        Request::TooLarge => {
+            eprintln!("Refused a webhook larger than {} bytes", MAX_BODY_LEN);
+            return respond(&mut stream, "413 Payload Too Large", "Webhook too large");
+        }
+        Request::Closed => return Ok(()),
+    };
+
+    let (status, message) = match serde_json::from_slice::<Webhook>(&body) {
+        Err(e) => ("400 Bad Request", format!("Invalid webhook: {}", e)),
+        Ok(webhook) => match ev2.event(to_event(&webhook, options)) {
+            Ok(()) => ("200 OK", "Forwarded".to_owned()),
+            Err(e) => ("502 Bad Gateway", format!("PagerDuty error: {}", e)),
+        },
+    };
+    if !status.starts_with("200") {
+        eprintln!("{}", message);
+    }
+
+    respond(&mut stream, status, &message)
+}
+
+fn respond(stream: &mut TcpStream, status: &str, message: &str) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        message.len(),
+        message
+    )
+}
+
+/// Read an HTTP/1.1 request and return its body, unless it's too large
+/// or the connection closed without sending one.
+fn read_request(stream: &mut TcpStream) -> std::io::Result<Request> {
+    let mut reader = BufReader::new(Read::by_ref(stream).take(MAX_HEADER_LEN));
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line)? == 0 {
+        return Ok(Request::Closed);
+    }
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_LEN {
+        return Ok(Request::TooLarge);
+    }
+
+    // Lift the header limit for the body, part of which the reader may already hold.
+    reader.get_mut().set_limit(content_length as u64);
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Request::Body(body))
+}
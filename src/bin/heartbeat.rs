@@ -0,0 +1,114 @@
+//! A dead-man's switch for cron jobs and other batch work.
+//!
+//! Wrap a job so each run records its exit status and duration, and each successful run
+//! counts as a ping; the job's own exit code is passed through:
+//!
+//!     heartbeat run nightly-backup -- /usr/local/bin/backup.sh
+//!
+//! Or ping from anywhere:
+//!
+//!     heartbeat ping nightly-backup
+//!
+//! Then watch for heartbeats that stop, with each heartbeat's window in seconds:
+//!
+//!     heartbeat watch nightly-backup=90000 hourly-sync=4200
+//!
+//! Configured through the environment:
+//!
+//! * `HEARTBEAT_DIR`: state directory (default `/var/lib/pagerduty-rs/heartbeat`)
+//! * `PAGERDUTY_ROUTING_KEY`: integration key to send events with (required to watch)
+//! * `PAGERDUTY_EVENTS_URL`: Events API base URL (default `https://events.pagerduty.com`)
+//! * `HEARTBEAT_SOURCE`: alert source (default `heartbeat`)
+//! * `HEARTBEAT_INTERVAL`: seconds between checks (default 60)
+
+use pagerduty_rs::eventsv2sync::*;
+use pagerduty_rs::heartbeat::*;
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+const USAGE: &str = "usage: heartbeat run NAME -- COMMAND [ARGS...]
+       heartbeat ping NAME
+       heartbeat watch NAME=WINDOW_SECS...";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let dir =
+        env::var("HEARTBEAT_DIR").unwrap_or_else(|_| "/var/lib/pagerduty-rs/heartbeat".to_owned());
+    let store = HeartbeatStore::new(PathBuf::from(&dir)).unwrap_or_else(|e| {
+        eprintln!("Unable to open heartbeat directory {}: {}", dir, e);
+        process::exit(1);
+    });
+
+    match args.first().map(|a| a.as_str()) {
+        Some("run") if args.len() > 3 && args[2] == "--" => match store.run(&args[1], &args[3..]) {
+            Ok(Some(code)) => process::exit(code),
+            Ok(None) => process::exit(1),
+            Err(e) => {
+                eprintln!("Unable to run or record {}: {}", args[1], e);
+                process::exit(1);
+            }
+        },
+        Some("ping") if args.len() == 2 => {
+            if let Err(e) = store.ping(&args[1]) {
+                eprintln!("Unable to record ping of {}: {}", args[1], e);
+                process::exit(1);
+            }
+        }
+        Some("watch") if args.len() > 1 => watch(store, &args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+fn watch(store: HeartbeatStore, heartbeats: &[String]) -> ! {
+    let routing_key = match env::var("PAGERDUTY_ROUTING_KEY") {
+        Ok(k) => k,
+        Err(_) => {
+            eprintln!("PAGERDUTY_ROUTING_KEY must be set");
+            process::exit(2);
+        }
+    };
+    let source = env::var("HEARTBEAT_SOURCE").unwrap_or_else(|_| "heartbeat".to_owned());
+    let interval = env::var("HEARTBEAT_INTERVAL")
+        .ok()
+        .and_then(|i| i.parse().ok())
+        .unwrap_or(60);
+
+    let mut ev2 = EventsV2::new(routing_key, Some("pagerduty-rs heartbeat".to_owned()))
+        .unwrap_or_else(|e| {
+            eprintln!("Unable to create Events V2 client: {}", e);
+            process::exit(1);
+        });
+    if let Ok(url) = env::var("PAGERDUTY_EVENTS_URL") {
+        ev2 = ev2.with_base_url(url);
+    }
+
+    let mut watcher = HeartbeatWatcher::new(ev2, store, HeartbeatOptions::new(source));
+    for heartbeat in heartbeats {
+        let (name, window) = match heartbeat
+            .split_once('=')
+            .and_then(|(name, window)| Some((name, window.parse().ok()?)))
+        {
+            Some(hw) => hw,
+            None => {
+                eprintln!("Expected NAME=WINDOW_SECS, got {}", heartbeat);
+                process::exit(2);
+            }
+        };
+        watcher = watcher
+            .watch(name.to_owned(), Duration::from_secs(window))
+            .unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(2);
+            });
+    }
+
+    watcher.run(Duration::from_secs(interval), |e| {
+        eprintln!("Heartbeat check failed: {}", e)
+    })
+}
@@ -0,0 +1,89 @@
+//! Sends Nagios plugin results to PagerDuty Events V2.
+//!
+//! Run a plugin and report its result, exiting with the plugin's own exit code:
+//!
+//!     nagios-bridge run HOST SERVICE -- /usr/lib/nagios/plugins/check_disk -w 20% -c 10%
+//!
+//! Or submit a result from a Nagios/Icinga notification command:
+//!
+//!     nagios-bridge submit '$HOSTNAME$' '$SERVICEDESC$' '$SERVICESTATEID$' '$SERVICEOUTPUT$|$SERVICEPERFDATA$'
+//!
+//! Pass an empty SERVICE for host checks, whose STATE is `$HOSTSTATEID$` (DOWN and UNREACHABLE
+//! are both CRITICAL):
+//!
+//!     nagios-bridge submit '$HOSTNAME$' '' '$HOSTSTATEID$' '$HOSTOUTPUT$|$HOSTPERFDATA$'
+//!
+//! A result that can't be sent is reported on stderr; in run mode the plugin's output and exit
+//! code are passed through regardless. Configured through the environment:
+//!
+//! * `PAGERDUTY_ROUTING_KEY`: integration key to send events with (required)
+//! * `PAGERDUTY_EVENTS_URL`: Events API base URL (default `https://events.pagerduty.com`)
+
+use pagerduty_rs::eventsv2sync::*;
+use pagerduty_rs::nagios::*;
+
+use std::env;
+use std::process;
+
+const USAGE: &str = "usage: nagios-bridge run HOST SERVICE -- COMMAND [ARGS...]
+       nagios-bridge submit HOST SERVICE STATE OUTPUT";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let service = |s: &String| if s.is_empty() { None } else { Some(s.clone()) };
+
+    let (state, output) = match args.first().map(|a| a.as_str()) {
+        Some("run") if args.len() > 4 && args[3] == "--" => {
+            // Plugins that can't be run are UNKNOWN, as Nagios itself would report them.
+            run_plugin(&args[4..]).unwrap_or_else(|e| {
+                (
+                    CheckState::Unknown,
+                    format!("Unable to run {}: {}\n", args[4], e),
+                )
+            })
+        }
+        Some("submit") if args.len() == 5 => (
+            if args[2].is_empty() {
+                CheckState::from_host_state(args[3].parse().ok())
+            } else {
+                CheckState::from_exit_code(args[3].parse().ok())
+            },
+            // Nagios macros escape newlines in long output.
+            args[4].replace("\\n", "\n"),
+        ),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(3);
+        }
+    };
+
+    let result = CheckResult::new(args[1].clone(), service(&args[2]), state, &output);
+
+    let sent = send(&result);
+    if let Err(e) = &sent {
+        eprintln!("{}", e);
+    }
+
+    // In run mode, stand in for the plugin so the bridge can itself be a Nagios check.
+    if args[0] == "run" {
+        // The plugin's whole output, so Nagios still sees its long output and perfdata.
+        print!("{}", output);
+        process::exit(result.state.exit_code());
+    }
+    if sent.is_err() {
+        process::exit(3);
+    }
+}
+
+fn send(result: &CheckResult) -> Result<(), String> {
+    let routing_key = env::var("PAGERDUTY_ROUTING_KEY")
+        .map_err(|_| "PAGERDUTY_ROUTING_KEY must be set".to_owned())?;
+    let mut ev2 = EventsV2::new(routing_key, Some("pagerduty-rs nagios-bridge".to_owned()))
+        .map_err(|e| format!("Unable to create Events V2 client: {}", e))?;
+    if let Ok(url) = env::var("PAGERDUTY_EVENTS_URL") {
+        ev2 = ev2.with_base_url(url);
+    }
+
+    ev2.event(result.to_event())
+        .map_err(|e| format!("PagerDuty error: {}", e))
+}
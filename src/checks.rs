@@ -0,0 +1,697 @@
+//! Runs health checks on an interval, triggering an alert when a check keeps failing and
+//! resolving it once the check recovers.
+//!
+//! Checks implement `Check`. The common ones (TCP connect, HTTP status, command exit code,
+//! file age, disk usage) are built in, closures become checks through `FnCheck`, and all but
+//! closures can be declared in configuration as `CheckConfig`s.
+
+use crate::eventsv2sync::*;
+use crate::types::*;
+
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs;
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+pub type CheckResult = Result<(), String>;
+
+pub trait Check: Send {
+    /// Identifies the check in its alert's summary and dedup key.
+    fn name(&self) -> &str;
+
+    /// Ok if healthy, otherwise an explanation of what's wrong.
+    fn check(&self) -> CheckResult;
+}
+
+/// Healthy if a TCP connection can be made.
+pub struct TcpCheck {
+    pub name: String,
+    pub addr: String,
+    pub timeout: Duration,
+}
+
+impl Check for TcpCheck {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self) -> CheckResult {
+        let addrs = self
+            .addr
+            .to_socket_addrs()
+            .map_err(|e| format!("Unable to resolve {}: {}", self.addr, e))?;
+        let mut last_error = format!("{} resolved to no addresses", self.addr);
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(_) => return Ok(()),
+                Err(e) => last_error = format!("Unable to connect to {}: {}", addr, e),
+            }
+        }
+        Err(last_error)
+    }
+}
+
+/// Healthy if a GET returns the expected status, or any 2xx when none is given.
+pub struct HttpCheck {
+    pub name: String,
+    pub url: String,
+    pub expected_status: Option<u16>,
+    client: Client,
+}
+
+impl HttpCheck {
+    pub fn new(
+        name: String,
+        url: String,
+        expected_status: Option<u16>,
+        timeout: Duration,
+    ) -> Result<Self, reqwest::Error> {
+        Ok(HttpCheck {
+            name,
+            url,
+            expected_status,
+            client: Client::builder().timeout(timeout).build()?,
+        })
+    }
+}
+
+impl Check for HttpCheck {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self) -> CheckResult {
+        let status = self
+            .client
+            .get(&self.url)
+            .send()
+            .map_err(|e| format!("GET {} failed: {}", self.url, e))?
+            .status();
+        let healthy = match self.expected_status {
+            Some(expected) => status.as_u16() == expected,
+            None => status.is_success(),
+        };
+        if healthy {
+            Ok(())
+        } else {
+            Err(format!("GET {} returned {}", self.url, status))
+        }
+    }
+}
+
+/// Healthy if a command exits with status 0 within `timeout`.
+pub struct CommandCheck {
+    pub name: String,
+    pub command: Vec<String>,
+    pub timeout: Duration,
+}
+
+impl Check for CommandCheck {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self) -> CheckResult {
+        let (program, args) = self
+            .command
+            .split_first()
+            .ok_or_else(|| "No command to run".to_owned())?;
+        let output = output_within(Command::new(program).args(args), self.timeout)
+            .map_err(|e| format!("Unable to run {}: {}", program, e))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} exited with {}: {}",
+                program,
+                output.status,
+                String::from_utf8_lossy(&output.stdout).trim()
+            ))
+        }
+    }
+}
+
+/// Healthy if a file was modified within `max_age`, such as a backup or a job's output.
+pub struct FileAgeCheck {
+    pub name: String,
+    pub path: PathBuf,
+    pub max_age: Duration,
+}
+
+impl Check for FileAgeCheck {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self) -> CheckResult {
+        let modified = fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .map_err(|e| format!("Unable to stat {}: {}", self.path.display(), e))?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if age <= self.max_age {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} was last modified {}s ago",
+                self.path.display(),
+                age.as_secs()
+            ))
+        }
+    }
+}
+
+/// Healthy while the filesystem holding `path` is at most `max_used_percent` full.
+///
+/// Usage comes from POSIX `df -Pk`, so this works wherever df does.
+pub struct DiskUsageCheck {
+    pub name: String,
+    pub path: PathBuf,
+    pub max_used_percent: u8,
+
+    /// How long df may take, e.g. on a hung network filesystem.
+    pub timeout: Duration,
+}
+
+impl Check for DiskUsageCheck {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self) -> CheckResult {
+        let output = output_within(Command::new("df").arg("-Pk").arg(&self.path), self.timeout)
+            .map_err(|e| format!("Unable to run df: {}", e))?;
+        let used =
+            parse_df_capacity(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+                format!(
+                    "Unable to read disk usage of {}: {}",
+                    self.path.display(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )
+            })?;
+        if used <= self.max_used_percent {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} is {}% full (limit {}%)",
+                self.path.display(),
+                used,
+                self.max_used_percent
+            ))
+        }
+    }
+}
+
+/// Run a command to completion, collecting its output like Command::output, but kill it
+/// and fail if it runs longer than timeout.
+fn output_within(command: &mut Command, timeout: Duration) -> IoResult<Output> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Read both pipes while waiting, so a chatty command can't block on a full pipe.
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut buf = vec![];
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    };
+    let stdout = read(child.stdout.take().map(|p| Box::new(p) as _));
+    let stderr = read(child.stderr.take().map(|p| Box::new(p) as _));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            // The readers are left behind: anything the command started may still hold its pipes.
+            return Err(IoError::new(
+                ErrorKind::TimedOut,
+                format!("timed out after {:?}", timeout),
+            ));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// The Capacity column of `df -P`'s only filesystem line.
+fn parse_df_capacity(output: &str) -> Option<u8> {
+    output
+        .lines()
+        .nth(1)?
+        .split_whitespace()
+        .nth(4)?
+        .trim_end_matches('%')
+        .parse()
+        .ok()
+}
+
+/// A check made from a closure.
+pub struct FnCheck<F: Fn() -> CheckResult + Send> {
+    name: String,
+    f: F,
+}
+
+impl<F: Fn() -> CheckResult + Send> FnCheck<F> {
+    pub fn new(name: String, f: F) -> Self {
+        FnCheck { name, f }
+    }
+}
+
+impl<F: Fn() -> CheckResult + Send> Check for FnCheck<F> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self) -> CheckResult {
+        (self.f)()
+    }
+}
+
+/// A check declared in configuration, for example as JSON:
+///
+/// `{"name": "api", "type": "http", "url": "http://localhost:8080/health", "failure_threshold": 3}`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CheckConfig {
+    pub name: String,
+
+    #[serde(flatten)]
+    pub kind: CheckKind,
+
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+
+    /// A severity label such as "critical" or "warning"; "error" if not given.
+    #[serde(default)]
+    pub severity: Option<String>,
+}
+
+fn default_failure_threshold() -> u32 {
+    CheckPolicy::default().failure_threshold
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CheckKind {
+    Tcp {
+        addr: String,
+        #[serde(default = "default_timeout_secs")]
+        timeout_secs: u64,
+    },
+    Http {
+        url: String,
+        #[serde(default)]
+        expected_status: Option<u16>,
+        #[serde(default = "default_timeout_secs")]
+        timeout_secs: u64,
+    },
+    Command {
+        command: Vec<String>,
+        #[serde(default = "default_timeout_secs")]
+        timeout_secs: u64,
+    },
+    FileAge {
+        path: PathBuf,
+        max_age_secs: u64,
+    },
+    DiskUsage {
+        path: PathBuf,
+        max_used_percent: u8,
+        #[serde(default = "default_timeout_secs")]
+        timeout_secs: u64,
+    },
+}
+
+#[derive(Debug)]
+pub enum CheckConfigError {
+    ReqwestError(reqwest::Error),
+
+    /// A command check with nothing to run. Contains the check's name.
+    EmptyCommand(String),
+}
+
+impl Error for CheckConfigError {}
+impl Display for CheckConfigError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::ReqwestError(e) => write!(f, "RequestError: {}", e),
+            Self::EmptyCommand(name) => write!(f, "EmptyCommand: {}", name),
+        }
+    }
+}
+impl From<reqwest::Error> for CheckConfigError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::ReqwestError(err)
+    }
+}
+
+impl CheckConfig {
+    pub fn into_check(self) -> Result<(Box<dyn Check>, CheckPolicy), CheckConfigError> {
+        let check: Box<dyn Check> = match self.kind {
+            CheckKind::Tcp { addr, timeout_secs } => Box::new(TcpCheck {
+                name: self.name,
+                addr,
+                timeout: Duration::from_secs(timeout_secs),
+            }),
+            CheckKind::Http {
+                url,
+                expected_status,
+                timeout_secs,
+            } => Box::new(HttpCheck::new(
+                self.name,
+                url,
+                expected_status,
+                Duration::from_secs(timeout_secs),
+            )?),
+            CheckKind::Command { command, .. } if command.is_empty() => {
+                return Err(CheckConfigError::EmptyCommand(self.name))
+            }
+            CheckKind::Command {
+                command,
+                timeout_secs,
+            } => Box::new(CommandCheck {
+                name: self.name,
+                command,
+                timeout: Duration::from_secs(timeout_secs),
+            }),
+            CheckKind::FileAge { path, max_age_secs } => Box::new(FileAgeCheck {
+                name: self.name,
+                path,
+                max_age: Duration::from_secs(max_age_secs),
+            }),
+            CheckKind::DiskUsage {
+                path,
+                max_used_percent,
+                timeout_secs,
+            } => Box::new(DiskUsageCheck {
+                name: self.name,
+                path,
+                max_used_percent,
+                timeout: Duration::from_secs(timeout_secs),
+            }),
+        };
+
+        let policy = CheckPolicy {
+            failure_threshold: self.failure_threshold,
+            severity: self
+                .severity
+                .as_deref()
+                .and_then(Severity::from_label)
+                .unwrap_or(CheckPolicy::default().severity),
+        };
+        Ok((check, policy))
+    }
+}
+
+/// When a check's alert is triggered, and how severe it is.
+#[derive(Debug, Clone, Copy)]
+pub struct CheckPolicy {
+    /// Consecutive failures before triggering.
+    pub failure_threshold: u32,
+
+    pub severity: Severity,
+}
+
+impl Default for CheckPolicy {
+    fn default() -> Self {
+        CheckPolicy {
+            failure_threshold: 3,
+            severity: Severity::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Trigger,
+    Resolve,
+}
+
+#[derive(Debug, Default)]
+struct CheckStatus {
+    consecutive_failures: u32,
+    alerting: bool,
+}
+
+impl CheckStatus {
+    /// Count a result, returning what (if anything) should be sent.
+    fn record(&mut self, healthy: bool, policy: &CheckPolicy) -> Option<Action> {
+        if healthy {
+            self.consecutive_failures = 0;
+            if self.alerting {
+                return Some(Action::Resolve);
+            }
+        } else {
+            self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+            if !self.alerting && self.consecutive_failures >= policy.failure_threshold {
+                return Some(Action::Trigger);
+            }
+        }
+        None
+    }
+}
+
+struct Entry {
+    check: Box<dyn Check>,
+    policy: CheckPolicy,
+    status: CheckStatus,
+}
+
+/// The custom_details sent with a failing check.
+#[derive(Serialize)]
+pub struct CheckFailure {
+    pub check: String,
+    pub error: String,
+    pub consecutive_failures: u32,
+}
+
+pub struct CheckRunner {
+    events_v2: EventsV2,
+
+    /// The unique location of the affected system, preferably a hostname or FQDN.
+    source: String,
+
+    entries: Vec<Entry>,
+}
+
+impl CheckRunner {
+    pub fn new(events_v2: EventsV2, source: String) -> Self {
+        CheckRunner {
+            events_v2,
+            source,
+            entries: vec![],
+        }
+    }
+
+    pub fn with_check(mut self, check: Box<dyn Check>, policy: CheckPolicy) -> Self {
+        self.entries.push(Entry {
+            check,
+            policy,
+            status: CheckStatus::default(),
+        });
+        self
+    }
+
+    /// Run every check once, sending triggers and resolves as needed.
+    ///
+    /// An alert that fails to send is retried on the next run.
+    pub fn run_once(&mut self) -> Vec<EventsV2Error> {
+        let mut errors = vec![];
+        for entry in self.entries.iter_mut() {
+            let result = entry.check.check();
+            let dedup_key =
+                truncate_dedup_key(&format!("check:{}:{}", self.source, entry.check.name()));
+
+            let sent = match (entry.status.record(result.is_ok(), &entry.policy), result) {
+                (Some(Action::Trigger), Err(error)) => {
+                    let summary = format!(
+                        "Check '{}' failing on {}: {}",
+                        entry.check.name(),
+                        self.source,
+                        error
+                    );
+                    self.events_v2
+                        .event(Event::AlertTrigger(AlertTrigger {
+                            payload: AlertTriggerPayload {
+                                severity: entry.policy.severity,
+                                summary: truncate_summary(&summary),
+                                source: self.source.clone(),
+                                timestamp: Some(time::OffsetDateTime::now_utc()),
+                                component: Some(entry.check.name().to_owned()),
+                                group: None,
+                                class: Some("health check".to_owned()),
+                                custom_details: Some(CheckFailure {
+                                    check: entry.check.name().to_owned(),
+                                    error,
+                                    consecutive_failures: entry.status.consecutive_failures,
+                                }),
+                            },
+                            dedup_key: Some(dedup_key),
+                            images: None,
+                            links: None,
+                            client: Some("pagerduty-rs".to_owned()),
+                            client_url: None,
+                        }))
+                        .map(|()| true)
+                }
+                (Some(Action::Resolve), _) => self
+                    .events_v2
+                    .event(Event::<()>::AlertResolve(AlertResolve { dedup_key }))
+                    .map(|()| false),
+                _ => continue,
+            };
+
+            match sent {
+                Ok(alerting) => entry.status.alerting = alerting,
+                Err(e) => errors.push(e),
+            }
+        }
+        errors
+    }
+
+    /// Run every `interval`, forever, handing each alert that fails to send to `on_error`.
+    pub fn run(&mut self, interval: Duration, mut on_error: impl FnMut(EventsV2Error)) -> ! {
+        loop {
+            self.run_once().into_iter().for_each(&mut on_error);
+            thread::sleep(interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::TcpListener;
+
+    #[test]
+    fn triggers_after_threshold_and_resolves() {
+        let policy = CheckPolicy {
+            failure_threshold: 2,
+            severity: Severity::Error,
+        };
+        let mut status = CheckStatus::default();
+
+        assert_eq!(status.record(false, &policy), None);
+        assert_eq!(status.record(false, &policy), Some(Action::Trigger));
+        status.alerting = true;
+        assert_eq!(status.record(false, &policy), None);
+        assert_eq!(status.record(true, &policy), Some(Action::Resolve));
+        status.alerting = false;
+        assert_eq!(status.record(true, &policy), None);
+
+        // A failure to send leaves alerting unset, so the trigger is retried.
+        assert_eq!(status.record(false, &policy), None);
+        assert_eq!(status.record(false, &policy), Some(Action::Trigger));
+        assert_eq!(status.record(false, &policy), Some(Action::Trigger));
+    }
+
+    #[test]
+    fn builtin_checks() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp = TcpCheck {
+            name: "tcp".to_owned(),
+            addr: listener.local_addr().unwrap().to_string(),
+            timeout: Duration::from_secs(1),
+        };
+        assert_eq!(tcp.check(), Ok(()));
+        drop(listener);
+        assert!(tcp.check().is_err());
+
+        let command = |c: &str| CommandCheck {
+            name: "command".to_owned(),
+            command: vec!["sh".to_owned(), "-c".to_owned(), c.to_owned()],
+            timeout: Duration::from_secs(5),
+        };
+        assert_eq!(command("exit 0").check(), Ok(()));
+        assert!(command("echo broken; exit 2")
+            .check()
+            .unwrap_err()
+            .ends_with("broken"));
+
+        let file_age = FileAgeCheck {
+            name: "file age".to_owned(),
+            path: PathBuf::from("Cargo.toml"),
+            max_age: Duration::from_secs(0),
+        };
+        assert!(file_age.check().is_err());
+
+        let closure = FnCheck::new("closure".to_owned(), || Err("nope".to_owned()));
+        assert_eq!(closure.check(), Err("nope".to_owned()));
+    }
+
+    #[test]
+    fn parses_df_output() {
+        let df = "Filesystem     1024-blocks      Used Available Capacity Mounted on\n/dev/sda1        102400000  87040000  15360000      85% /\n";
+        assert_eq!(parse_df_capacity(df), Some(85));
+        assert_eq!(parse_df_capacity(""), None);
+    }
+
+    #[test]
+    fn declared_checks() {
+        let config: CheckConfig = serde_json::from_str(
+            r#"{"name": "backup", "type": "file_age", "path": "/var/backups/db.tar", "max_age_secs": 90000, "failure_threshold": 1, "severity": "critical"}"#,
+        )
+        .unwrap();
+        let (check, policy) = config.into_check().unwrap();
+        assert_eq!(check.name(), "backup");
+        assert_eq!(policy.failure_threshold, 1);
+        assert_eq!(policy.severity, Severity::Critical);
+    }
+
+    #[test]
+    fn rejects_empty_commands() {
+        let config: CheckConfig =
+            serde_json::from_str(r#"{"name": "noop", "type": "command", "command": []}"#).unwrap();
+        assert!(matches!(
+            config.into_check(),
+            Err(CheckConfigError::EmptyCommand(name)) if name == "noop"
+        ));
+
+        let check = CommandCheck {
+            name: "noop".to_owned(),
+            command: vec![],
+            timeout: Duration::from_secs(5),
+        };
+        assert!(check.check().is_err());
+    }
+
+    #[test]
+    fn kills_commands_that_time_out() {
+        let check = CommandCheck {
+            name: "hung".to_owned(),
+            command: vec!["sleep".to_owned(), "10".to_owned()],
+            timeout: Duration::from_millis(200),
+        };
+        let started = Instant::now();
+        let err = check.check().unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(err, "Unable to run sleep: timed out after 200ms");
+    }
+}
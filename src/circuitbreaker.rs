@@ -0,0 +1,306 @@
+use serde_json::{json, Value};
+use std::fs::OpenOptions;
+use std::io::{Result as IoResult, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The state of a circuit breaker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Events are posted to PagerDuty as usual.
+    Closed,
+
+    /// PagerDuty is considered unavailable. Events go straight to the fallback
+    /// without waiting on the network.
+    Open,
+
+    /// The reset timeout has elapsed. A single trial event is posted to PagerDuty
+    /// at a time while everything else goes to the fallback.
+    HalfOpen,
+}
+
+/// Thresholds controlling when a circuit breaker opens and closes.
+#[derive(Clone, Debug)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures in the Closed state before the circuit opens.
+    pub failure_threshold: u32,
+
+    /// How long the circuit stays Open before trial events are let through.
+    pub reset_timeout: Duration,
+
+    /// Consecutive successful trial events in the HalfOpen state before the circuit closes.
+    pub success_threshold: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            failure_threshold: 5,
+            reset_timeout: Duration::from_secs(30),
+            success_threshold: 1,
+        }
+    }
+}
+
+/// Receives the URL an event was meant for, and the event itself.
+pub type FallbackCallback = Box<dyn Fn(&str, &Value) + Send + Sync>;
+
+/// Where events go while the circuit is open.
+pub enum Fallback {
+    /// Append each event as a line of JSON to a local spool file, so it can be replayed later.
+    Spool(PathBuf),
+
+    /// Post the event with this routing key instead of the client's integration key.
+    SecondaryRoutingKey(String),
+
+    /// Write each event as a line of JSON to the given sink (stderr, a log file, etc.)
+    LogSink(Mutex<Box<dyn Write + Send>>),
+
+    /// Hand the event to a callback, along with the URL it was meant to be posted to.
+    Callback(FallbackCallback),
+}
+
+impl Fallback {
+    /// Handles every fallback that doesn't need to reach PagerDuty.
+    /// SecondaryRoutingKey is left to the client, and is a no-op here.
+    pub(crate) fn handle_locally(&self, url: &str, content: &Value) -> IoResult<()> {
+        match self {
+            Self::Spool(path) => {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                writeln!(file, "{}", json!({"url": url, "event": content}))
+            }
+            Self::LogSink(sink) => {
+                let mut sink = match sink.lock() {
+                    Ok(s) => s,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                writeln!(sink, "{}", json!({"url": url, "event": content}))
+            }
+            Self::Callback(cb) => {
+                cb(url, content);
+                Ok(())
+            }
+            Self::SecondaryRoutingKey(_) => Ok(()),
+        }
+    }
+}
+
+struct BreakerState {
+    state: CircuitState,
+    consecutive_failures: u32,
+    consecutive_successes: u32,
+    opened_at: Option<Instant>,
+    // When the current HalfOpen trial started. A trial that never reports back
+    // (e.g. its future was dropped) is abandoned after reset_timeout.
+    trial_started_at: Option<Instant>,
+}
+
+/// A circuit breaker that sits in front of an EventsV2 client.
+///
+/// When too many posts in a row fail (network errors, timeouts, 429 or 5xx responses),
+/// the circuit opens and events are handed to the fallback instead of waiting on a
+/// degraded events.pagerduty.com.
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    fallback: Fallback,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig, fallback: Fallback) -> Self {
+        CircuitBreaker {
+            config,
+            fallback,
+            state: Mutex::new(BreakerState {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                consecutive_successes: 0,
+                opened_at: None,
+                trial_started_at: None,
+            }),
+        }
+    }
+
+    /// The current state of the circuit.
+    pub fn state(&self) -> CircuitState {
+        let s = self.lock();
+        match s.state {
+            CircuitState::Open if self.reset_elapsed(&s) => CircuitState::HalfOpen,
+            state => state,
+        }
+    }
+
+    pub fn fallback(&self) -> &Fallback {
+        &self.fallback
+    }
+
+    /// Whether an event may be posted to PagerDuty right now. When this returns true,
+    /// the caller must report the outcome with record_success or record_failure.
+    pub fn allow_request(&self) -> bool {
+        let mut s = self.lock();
+        if s.state == CircuitState::Open && self.reset_elapsed(&s) {
+            s.state = CircuitState::HalfOpen;
+            s.consecutive_successes = 0;
+            s.trial_started_at = None;
+        }
+
+        match s.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => match s.trial_started_at {
+                Some(at) if at.elapsed() < self.config.reset_timeout => false,
+                _ => {
+                    s.trial_started_at = Some(Instant::now());
+                    true
+                }
+            },
+        }
+    }
+
+    /// PagerDuty responded (even if it rejected the event).
+    pub fn record_success(&self) {
+        let mut s = self.lock();
+        s.consecutive_failures = 0;
+        if s.state == CircuitState::HalfOpen {
+            s.trial_started_at = None;
+            s.consecutive_successes += 1;
+            if s.consecutive_successes >= self.config.success_threshold {
+                s.state = CircuitState::Closed;
+                s.consecutive_successes = 0;
+                s.opened_at = None;
+            }
+        }
+    }
+
+    /// PagerDuty could not be reached or is degraded.
+    pub fn record_failure(&self) {
+        let mut s = self.lock();
+        s.consecutive_successes = 0;
+        s.consecutive_failures += 1;
+        let trip = match s.state {
+            CircuitState::HalfOpen => true,
+            CircuitState::Closed => s.consecutive_failures >= self.config.failure_threshold,
+            CircuitState::Open => false,
+        };
+        if trip {
+            s.state = CircuitState::Open;
+            s.opened_at = Some(Instant::now());
+            s.trial_started_at = None;
+        }
+    }
+
+    fn reset_elapsed(&self, s: &BreakerState) -> bool {
+        match s.opened_at {
+            Some(at) => at.elapsed() >= self.config.reset_timeout,
+            None => true,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        // The state is always left consistent, so a poisoned lock is still usable.
+        match self.state.lock() {
+            Ok(s) => s,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread::sleep;
+
+    fn breaker(reset_timeout: Duration) -> CircuitBreaker {
+        CircuitBreaker::new(
+            CircuitBreakerConfig {
+                failure_threshold: 2,
+                reset_timeout,
+                success_threshold: 1,
+            },
+            Fallback::SecondaryRoutingKey("secondary".to_owned()),
+        )
+    }
+
+    #[test]
+    fn opens_after_threshold_and_recovers() {
+        let cb = breaker(Duration::from_millis(20));
+        assert_eq!(cb.state(), CircuitState::Closed);
+
+        assert!(cb.allow_request());
+        cb.record_failure();
+        assert_eq!(cb.state(), CircuitState::Closed);
+        assert!(cb.allow_request());
+        cb.record_failure();
+        assert_eq!(cb.state(), CircuitState::Open);
+        assert!(!cb.allow_request());
+
+        sleep(Duration::from_millis(30));
+        assert_eq!(cb.state(), CircuitState::HalfOpen);
+
+        // Only one trial at a time
+        assert!(cb.allow_request());
+        assert!(!cb.allow_request());
+        cb.record_success();
+        assert_eq!(cb.state(), CircuitState::Closed);
+        assert!(cb.allow_request());
+    }
+
+    #[test]
+    fn failed_trial_reopens() {
+        let cb = breaker(Duration::from_millis(20));
+        cb.record_failure();
+        cb.record_failure();
+        sleep(Duration::from_millis(30));
+
+        assert!(cb.allow_request());
+        cb.record_failure();
+        assert_eq!(cb.state(), CircuitState::Open);
+        assert!(!cb.allow_request());
+    }
+
+    #[test]
+    fn success_resets_failure_count() {
+        let cb = breaker(Duration::from_secs(60));
+        cb.record_failure();
+        cb.record_success();
+        cb.record_failure();
+        assert_eq!(cb.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn spool_and_callback_fallbacks() {
+        let path = std::env::temp_dir().join(format!("pagerduty-rs-spool-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let spool = Fallback::Spool(path.clone());
+        spool
+            .handle_locally(
+                "https://events.pagerduty.com/v2/enqueue",
+                &json!({"dedup_key": "a"}),
+            )
+            .unwrap();
+        spool
+            .handle_locally(
+                "https://events.pagerduty.com/v2/enqueue",
+                &json!({"dedup_key": "b"}),
+            )
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\"event\":{\"dedup_key\":\"a\"},\"url\":\"https://events.pagerduty.com/v2/enqueue\"}\n{\"event\":{\"dedup_key\":\"b\"},\"url\":\"https://events.pagerduty.com/v2/enqueue\"}\n"
+        );
+        fs::remove_file(&path).unwrap();
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let callback = Fallback::Callback(Box::new(move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        callback.handle_locally("url", &json!({})).unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,534 @@
+//! Translates CloudWatch alarm state changes, delivered as SNS notifications, into Events V2 events.
+//!
+//! The alarm ARN is the dedup key, so an alarm's incident is triggered when it enters ALARM
+//! and resolved when it returns to OK.
+
+use crate::types::*;
+
+use base64::Engine;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use std::convert::From;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use time::format_description::well_known::Rfc3339;
+use time::{macros::format_description, OffsetDateTime};
+use x509_cert::der::{DecodePem, Encode};
+use x509_cert::Certificate;
+
+#[derive(Debug)]
+pub enum CloudWatchError {
+    SerdeJsonError(serde_json::Error),
+    Base64Error(base64::DecodeError),
+    CertificateError(x509_cert::der::Error),
+    PublicKeyError(rsa::pkcs8::spki::Error),
+    SignatureError(rsa::Error),
+    UnsupportedSignatureVersion(String),
+}
+
+impl Error for CloudWatchError {}
+impl Display for CloudWatchError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::SerdeJsonError(e) => write!(f, "SerdeJsonError: {}", e),
+            Self::Base64Error(e) => write!(f, "Base64Error: {}", e),
+            Self::CertificateError(e) => write!(f, "CertificateError: {}", e),
+            Self::PublicKeyError(e) => write!(f, "PublicKeyError: {}", e),
+            Self::SignatureError(e) => write!(f, "SignatureError: {}", e),
+            Self::UnsupportedSignatureVersion(v) => write!(f, "UnsupportedSignatureVersion: {}", v),
+        }
+    }
+}
+impl From<serde_json::Error> for CloudWatchError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::SerdeJsonError(err)
+    }
+}
+impl From<base64::DecodeError> for CloudWatchError {
+    fn from(err: base64::DecodeError) -> Self {
+        Self::Base64Error(err)
+    }
+}
+impl From<x509_cert::der::Error> for CloudWatchError {
+    fn from(err: x509_cert::der::Error) -> Self {
+        Self::CertificateError(err)
+    }
+}
+impl From<rsa::pkcs8::spki::Error> for CloudWatchError {
+    fn from(err: rsa::pkcs8::spki::Error) -> Self {
+        Self::PublicKeyError(err)
+    }
+}
+impl From<rsa::Error> for CloudWatchError {
+    fn from(err: rsa::Error) -> Self {
+        Self::SignatureError(err)
+    }
+}
+
+/// An SNS HTTP(S) delivery: a Notification, SubscriptionConfirmation or UnsubscribeConfirmation.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnsMessage {
+    #[serde(rename = "Type")]
+    pub message_type: String,
+    pub message_id: String,
+    pub topic_arn: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    pub message: String,
+    pub timestamp: String,
+    pub signature_version: String,
+    pub signature: String,
+    #[serde(rename = "SigningCertURL")]
+    pub signing_cert_url: String,
+    #[serde(
+        rename = "UnsubscribeURL",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub unsubscribe_url: Option<String>,
+    #[serde(
+        rename = "SubscribeURL",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub subscribe_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl SnsMessage {
+    /// Parse the CloudWatch alarm carried in a Notification's message.
+    pub fn alarm(&self) -> Result<Alarm, CloudWatchError> {
+        Ok(serde_json::from_str(&self.message)?)
+    }
+
+    /// Verify the message's signature against the PEM encoded certificate downloaded from
+    /// its SigningCertURL. Check that URL with `is_aws_signing_cert_url` before fetching it,
+    /// or anyone can sign messages with a certificate of their own.
+    pub fn verify(&self, certificate_pem: &[u8]) -> Result<(), CloudWatchError> {
+        let certificate = Certificate::from_pem(certificate_pem)?;
+        let key = RsaPublicKey::from_public_key_der(
+            &certificate
+                .tbs_certificate
+                .subject_public_key_info
+                .to_der()?,
+        )?;
+        let signature = base64::engine::general_purpose::STANDARD.decode(&self.signature)?;
+        let string_to_sign = self.string_to_sign();
+
+        match self.signature_version.as_str() {
+            "1" => key.verify(
+                Pkcs1v15Sign::new::<Sha1>(),
+                &Sha1::digest(string_to_sign.as_bytes()),
+                &signature,
+            )?,
+            "2" => key.verify(
+                Pkcs1v15Sign::new::<Sha256>(),
+                &Sha256::digest(string_to_sign.as_bytes()),
+                &signature,
+            )?,
+            v => return Err(CloudWatchError::UnsupportedSignatureVersion(v.to_owned())),
+        }
+        Ok(())
+    }
+
+    /// https://docs.aws.amazon.com/sns/latest/dg/sns-verify-signature-of-message.html
+    fn string_to_sign(&self) -> String {
+        let mut fields: Vec<(&str, Option<&str>)> = vec![
+            ("Message", Some(&self.message)),
+            ("MessageId", Some(&self.message_id)),
+        ];
+        if self.message_type == "Notification" {
+            fields.push(("Subject", self.subject.as_deref()));
+        } else {
+            fields.push(("SubscribeURL", self.subscribe_url.as_deref()));
+        }
+        fields.push(("Timestamp", Some(&self.timestamp)));
+        if self.message_type != "Notification" {
+            fields.push(("Token", self.token.as_deref()));
+        }
+        fields.push(("TopicArn", Some(&self.topic_arn)));
+        fields.push(("Type", Some(&self.message_type)));
+
+        fields
+            .iter()
+            .filter_map(|(name, value)| value.map(|v| format!("{}\n{}\n", name, v)))
+            .collect()
+    }
+}
+
+/// Whether a SigningCertURL points at an SNS endpoint, so it's safe to fetch and trust.
+pub fn is_aws_signing_cert_url(signing_cert_url: &str) -> bool {
+    let url = match url::Url::parse(signing_cert_url) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+    let host = url.host_str().unwrap_or("").strip_prefix("sns.");
+    let region = match host {
+        Some(h) => match h.strip_suffix(".amazonaws.com.cn") {
+            Some(region) => region.starts_with("cn-").then_some(region),
+            None => h.strip_suffix(".amazonaws.com"),
+        },
+        None => None,
+    };
+
+    url.scheme() == "https"
+        && url.port().is_none()
+        && region.map(is_aws_region).unwrap_or(false)
+        && url.path().ends_with(".pem")
+}
+
+/// Whether s looks like an AWS region, e.g. us-east-1 or us-gov-west-1:
+/// `[a-z]{2}(-gov)?-[a-z]+-[0-9]`.
+fn is_aws_region(s: &str) -> bool {
+    let parts: Vec<&str> = s.split('-').collect();
+    let (country, area, number) = match parts.as_slice() {
+        [country, area, number] => (country, area, number),
+        [country, "gov", area, number] => (country, area, number),
+        _ => return false,
+    };
+    let is_lower = |p: &str| !p.is_empty() && p.bytes().all(|b| b.is_ascii_lowercase());
+
+    country.len() == 2
+        && is_lower(country)
+        && is_lower(area)
+        && number.len() == 1
+        && number.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// A CloudWatch alarm state change notification.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct Alarm {
+    pub alarm_name: String,
+    #[serde(default)]
+    pub alarm_description: Option<String>,
+    #[serde(rename = "AWSAccountId")]
+    pub aws_account_id: String,
+    pub new_state_value: AlarmState,
+    pub new_state_reason: String,
+    pub state_change_time: String,
+    /// The region's display name, such as "US East (N. Virginia)".
+    pub region: String,
+    pub alarm_arn: String,
+    pub old_state_value: AlarmState,
+    #[serde(default)]
+    pub trigger: Option<Trigger>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlarmState {
+    Ok,
+    Alarm,
+    InsufficientData,
+}
+
+/// The metric and threshold an alarm watches. Metric math alarms have no metric name or
+/// namespace.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct Trigger {
+    #[serde(default)]
+    pub metric_name: Option<String>,
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub statistic: Option<String>,
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub dimensions: Vec<Dimension>,
+    #[serde(default)]
+    pub period: Option<u64>,
+    #[serde(default)]
+    pub evaluation_periods: Option<u64>,
+    #[serde(default)]
+    pub comparison_operator: Option<String>,
+    #[serde(default)]
+    pub threshold: Option<f64>,
+    #[serde(default)]
+    pub treat_missing_data: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Dimension {
+    pub name: String,
+    pub value: String,
+}
+
+/// What to do when an alarm doesn't have enough data to evaluate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsufficientData {
+    /// Send nothing, leaving any incident as it was.
+    Ignore,
+    Resolve,
+    Trigger(Severity),
+}
+
+pub struct CloudWatchOptions {
+    /// Used as the alert's source when the alarm's metric has no dimensions.
+    pub source: String,
+
+    /// Severity of alarms entering ALARM.
+    pub severity: Severity,
+
+    pub insufficient_data: InsufficientData,
+}
+
+impl CloudWatchOptions {
+    pub fn new(source: String) -> Self {
+        CloudWatchOptions {
+            source,
+            severity: Severity::Error,
+            insufficient_data: InsufficientData::Ignore,
+        }
+    }
+}
+
+/// Translate an alarm into a trigger (on ALARM) or a resolve (on OK). INSUFFICIENT_DATA is
+/// handled as configured, and gives None when ignored.
+pub fn to_event(alarm: &Alarm, options: &CloudWatchOptions) -> Option<Event<Value>> {
+    let severity = match alarm.new_state_value {
+        AlarmState::Alarm => options.severity,
+        AlarmState::Ok => return Some(resolve(alarm)),
+        AlarmState::InsufficientData => match options.insufficient_data {
+            InsufficientData::Ignore => return None,
+            InsufficientData::Resolve => return Some(resolve(alarm)),
+            InsufficientData::Trigger(severity) => severity,
+        },
+    };
+
+    let summary = format!("{}: {}", alarm.alarm_name, alarm.new_state_reason);
+    let region = region_code(&alarm.alarm_arn);
+    let console_url = format!(
+        "https://{}.console.aws.amazon.com/cloudwatch/home?region={}#alarmsV2:alarm/{}",
+        region,
+        region,
+        url::form_urlencoded::byte_serialize(alarm.alarm_name.as_bytes())
+            .collect::<String>()
+            .replace('+', "%20")
+    );
+
+    Some(Event::AlertTrigger(AlertTrigger {
+        payload: AlertTriggerPayload {
+            severity,
+            summary: truncate_summary(&summary),
+            source: alarm
+                .trigger
+                .as_ref()
+                .and_then(|t| t.dimensions.first())
+                .map(|d| d.value.clone())
+                .unwrap_or_else(|| options.source.clone()),
+            timestamp: parse_state_change_time(&alarm.state_change_time),
+            component: alarm.trigger.as_ref().and_then(|t| t.namespace.clone()),
+            group: Some(alarm.aws_account_id.clone()),
+            class: alarm.trigger.as_ref().and_then(|t| t.metric_name.clone()),
+            custom_details: serde_json::to_value(alarm).ok(),
+        },
+        dedup_key: Some(hash_dedup_key(&alarm.alarm_arn)),
+        images: None,
+        links: Some(vec![Link {
+            href: console_url.clone(),
+            text: Some("CloudWatch alarm".to_owned()),
+        }]),
+        client: Some("Amazon CloudWatch".to_owned()),
+        client_url: Some(console_url),
+    }))
+}
+
+fn resolve(alarm: &Alarm) -> Event<Value> {
+    Event::AlertResolve(AlertResolve {
+        dedup_key: hash_dedup_key(&alarm.alarm_arn),
+    })
+}
+
+/// arn:aws:cloudwatch:<region>:<account>:alarm:<name>
+fn region_code(arn: &str) -> &str {
+    arn.split(':').nth(3).unwrap_or("")
+}
+
+/// CloudWatch writes times like 2021-05-30T00:00:00.000+0000, which isn't quite RFC 3339.
+fn parse_state_change_time(s: &str) -> Option<OffsetDateTime> {
+    let format = format_description!(
+        "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond][offset_hour sign:mandatory][offset_minute]"
+    );
+    OffsetDateTime::parse(s, &format)
+        .or_else(|_| OffsetDateTime::parse(s, &Rfc3339))
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// A self-signed stand-in for an SNS signing certificate.
+    const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----\n\
+MIICKjCCAZOgAwIBAgIUEY5x5A7EAcm/EI8WO2QeKtKyra4wDQYJKoZIhvcNAQEL\n\
+BQAwJjEkMCIGA1UEAwwbc25zLnVzLWVhc3QtMS5hbWF6b25hd3MuY29tMCAXDTI2\n\
+MTAxODE2NTMzMloYDzIxMjYwOTI0MTY1MzMyWjAmMSQwIgYDVQQDDBtzbnMudXMt\n\
+ZWFzdC0xLmFtYXpvbmF3cy5jb20wgZ8wDQYJKoZIhvcNAQEBBQADgY0AMIGJAoGB\n\
+ALSG5K6q7jbwGgtMbynu5hxpTup7dJn4mPtCQ3c77foWDHoiqV33tl0Jde4+gjjj\n\
+dDnZnwlMBcwXuYvqlXoimIIU305ngPHL1Fc4+uhstp9jHYSVJE3AbR8GFSj6t368\n\
+9yMIwCU1U4Y6yVXyd6rbIbGDoXB1vs1R85nabO3gDCcbAgMBAAGjUzBRMB0GA1Ud\n\
+DgQWBBRzpxnvODwC7ZqxRcJCLUQHBt2T4jAfBgNVHSMEGDAWgBRzpxnvODwC7Zqx\n\
+RcJCLUQHBt2T4jAPBgNVHRMBAf8EBTADAQH/MA0GCSqGSIb3DQEBCwUAA4GBAElT\n\
+iOsucTcKvB0MS5SZ49ku135XcMMR5ncev2rOTo7d7Kyl4aX00GKJHFjE4chBZM1e\n\
+XC3JMgdEnIkJESr1EqmEWAz6WV2AklE0X7EW42r0foOzTsr6t7i4STD5nsr45TFa\n\
+ctmZZWzvIlZFt5sg11QKoHYjmf36BSJdExf9lHt4\n\
+-----END CERTIFICATE-----";
+
+    /// A notification signed (SignatureVersion 1) with CERTIFICATE's key.
+    const NOTIFICATION: &str = r#"{
+    "Type": "Notification",
+    "MessageId": "22b80b92-fdea-4c2c-8f9d-bdfb0c7bf324",
+    "TopicArn": "arn:aws:sns:us-east-1:123456789012:alarms",
+    "Subject": "ALARM: \"api-5xx\" in US East (N. Virginia)",
+    "Message": "{\"AlarmName\":\"api-5xx\",\"AlarmDescription\":\"API 5xx rate\",\"AWSAccountId\":\"123456789012\",\"NewStateValue\":\"ALARM\",\"NewStateReason\":\"Threshold Crossed: 1 datapoint [12.0] was greater than the threshold (5.0).\",\"StateChangeTime\":\"2021-05-30T00:00:00.000+0000\",\"Region\":\"US East (N. Virginia)\",\"AlarmArn\":\"arn:aws:cloudwatch:us-east-1:123456789012:alarm:api-5xx\",\"OldStateValue\":\"OK\",\"Trigger\":{\"MetricName\":\"HTTPCode_Target_5XX_Count\",\"Namespace\":\"AWS/ApplicationELB\",\"StatisticType\":\"Statistic\",\"Statistic\":\"SUM\",\"Unit\":null,\"Dimensions\":[{\"value\":\"app/api/50dc6c495c0c9188\",\"name\":\"LoadBalancer\"}],\"Period\":60,\"EvaluationPeriods\":1,\"ComparisonOperator\":\"GreaterThanThreshold\",\"Threshold\":5.0,\"TreatMissingData\":\"missing\"}}",
+    "Timestamp": "2021-05-30T00:00:01.000Z",
+    "SignatureVersion": "1",
+    "SigningCertURL": "https://sns.us-east-1.amazonaws.com/SimpleNotificationService-abc.pem",
+    "UnsubscribeURL": "https://sns.us-east-1.amazonaws.com/?Action=Unsubscribe",
+    "Signature": "FmSh9NfTtYRly6/2te5i/gY/dJ4wnGIkAc+s1TXWlb0oZHFXmD1KbqDu5OxPWUwPjW1MOs1PTiFmhVH1+lcZA5WMe/7XtigXDN08Elw8IyWkBGG4dSk7oqm0e7ruq351xLKFm/rSma0m73bstoly8RL41VV3OFuWy5GJbBHeFA8="
+}"#;
+
+    #[test]
+    fn verifies_signature() {
+        let message: SnsMessage = serde_json::from_str(NOTIFICATION).unwrap();
+        message.verify(CERTIFICATE.as_bytes()).unwrap();
+
+        let mut tampered: SnsMessage = serde_json::from_str(NOTIFICATION).unwrap();
+        tampered.message = tampered.message.replace("ALARM", "OK");
+        assert!(matches!(
+            tampered.verify(CERTIFICATE.as_bytes()),
+            Err(CloudWatchError::SignatureError(_))
+        ));
+
+        let mut unsupported: SnsMessage = serde_json::from_str(NOTIFICATION).unwrap();
+        unsupported.signature_version = "3".to_owned();
+        assert!(matches!(
+            unsupported.verify(CERTIFICATE.as_bytes()),
+            Err(CloudWatchError::UnsupportedSignatureVersion(_))
+        ));
+    }
+
+    #[test]
+    fn signing_cert_urls() {
+        assert!(is_aws_signing_cert_url(
+            "https://sns.us-east-1.amazonaws.com/SimpleNotificationService-abc.pem"
+        ));
+        assert!(!is_aws_signing_cert_url(
+            "http://sns.us-east-1.amazonaws.com/SimpleNotificationService-abc.pem"
+        ));
+        assert!(!is_aws_signing_cert_url(
+            "https://sns.us-east-1.amazonaws.com.evil.example/cert.pem"
+        ));
+        assert!(!is_aws_signing_cert_url("https://evil.example/sns.pem"));
+
+        // Other AWS services' hosts aren't SNS endpoints.
+        assert!(!is_aws_signing_cert_url(
+            "https://sns.s3.amazonaws.com/x.pem"
+        ));
+        assert!(!is_aws_signing_cert_url(
+            "https://sns.my-bucket.s3.amazonaws.com/x.pem"
+        ));
+
+        assert!(is_aws_signing_cert_url(
+            "https://sns.us-gov-west-1.amazonaws.com/SimpleNotificationService-abc.pem"
+        ));
+        assert!(is_aws_signing_cert_url(
+            "https://sns.cn-north-1.amazonaws.com.cn/SimpleNotificationService-abc.pem"
+        ));
+        assert!(!is_aws_signing_cert_url(
+            "https://sns.us-east-1.amazonaws.com.cn/SimpleNotificationService-abc.pem"
+        ));
+    }
+
+    #[test]
+    fn alarm_triggers() {
+        let message: SnsMessage = serde_json::from_str(NOTIFICATION).unwrap();
+        let alarm = message.alarm().unwrap();
+        let event = to_event(&alarm, &CloudWatchOptions::new("aws".to_owned()));
+
+        match event {
+            Some(Event::AlertTrigger(at)) => {
+                assert_eq!(
+                    at.dedup_key.as_deref(),
+                    Some("arn:aws:cloudwatch:us-east-1:123456789012:alarm:api-5xx")
+                );
+                assert_eq!(at.payload.severity, Severity::Error);
+                assert_eq!(at.payload.source, "app/api/50dc6c495c0c9188");
+                assert_eq!(at.payload.component.as_deref(), Some("AWS/ApplicationELB"));
+                assert_eq!(
+                    at.payload.timestamp,
+                    Some(time::macros::datetime!(2021-05-30 00:00:00 UTC))
+                );
+                assert_eq!(
+                    at.links.unwrap()[0].href,
+                    "https://us-east-1.console.aws.amazon.com/cloudwatch/home?region=us-east-1#alarmsV2:alarm/api-5xx"
+                );
+            }
+            _ => panic!("expected a trigger"),
+        }
+    }
+
+    #[test]
+    fn state_transitions() {
+        let message: SnsMessage = serde_json::from_str(NOTIFICATION).unwrap();
+        let mut alarm = message.alarm().unwrap();
+        let mut options = CloudWatchOptions::new("aws".to_owned());
+
+        alarm.new_state_value = AlarmState::Ok;
+        assert!(matches!(
+            to_event(&alarm, &options),
+            Some(Event::AlertResolve(_))
+        ));
+
+        alarm.new_state_value = AlarmState::InsufficientData;
+        assert!(to_event(&alarm, &options).is_none());
+
+        options.insufficient_data = InsufficientData::Trigger(Severity::Warning);
+        match to_event(&alarm, &options) {
+            Some(Event::AlertTrigger(at)) => assert_eq!(at.payload.severity, Severity::Warning),
+            _ => panic!("expected a trigger"),
+        }
+    }
+
+    #[test]
+    fn long_alarm_arn_is_truncated() {
+        let message: SnsMessage = serde_json::from_str(NOTIFICATION).unwrap();
+        let mut alarm = message.alarm().unwrap();
+        // Alarm names may be up to 255 characters, so their ARNs can be longer.
+        alarm.alarm_arn = format!(
+            "arn:aws:cloudwatch:us-east-1:123456789012:alarm:{}",
+            "a".repeat(255)
+        );
+        let options = CloudWatchOptions::new("aws".to_owned());
+
+        let trigger_key = match to_event(&alarm, &options) {
+            Some(Event::AlertTrigger(at)) => at.dedup_key.unwrap(),
+            _ => panic!("expected a trigger"),
+        };
+        assert_eq!(trigger_key.chars().count(), 255);
+
+        // Alarms whose names differ only past the limit still get their own alerts.
+        let mut other = message.alarm().unwrap();
+        other.alarm_arn = format!("{}b", alarm.alarm_arn);
+        match to_event(&other, &options) {
+            Some(Event::AlertTrigger(at)) => assert_ne!(at.dedup_key.unwrap(), trigger_key),
+            _ => panic!("expected a trigger"),
+        }
+
+        alarm.new_state_value = AlarmState::Ok;
+        match to_event(&alarm, &options) {
+            Some(Event::AlertResolve(ar)) => assert_eq!(ar.dedup_key, trigger_key),
+            _ => panic!("expected a resolve"),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::io::Result as IoResult;
+
+pub const CONTENT_ENCODING_IDENTITY: &str = "identity";
+#[cfg(feature = "gzip")]
+pub const CONTENT_ENCODING_GZIP: &str = "gzip";
+
+/// Encode a request body, gzipping it when it is larger than the threshold.
+/// Returns the body along with the Content-Encoding it was sent with.
+#[cfg(feature = "gzip")]
+pub fn encode(body: Vec<u8>, gzip_threshold: Option<usize>) -> IoResult<(Vec<u8>, &'static str)> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    match gzip_threshold {
+        Some(threshold) if body.len() > threshold => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&body)?;
+            Ok((encoder.finish()?, CONTENT_ENCODING_GZIP))
+        }
+        _ => Ok((body, CONTENT_ENCODING_IDENTITY)),
+    }
+}
+
+#[cfg(not(feature = "gzip"))]
+pub fn encode(body: Vec<u8>, _gzip_threshold: Option<usize>) -> IoResult<(Vec<u8>, &'static str)> {
+    Ok((body, CONTENT_ENCODING_IDENTITY))
+}
+
+#[cfg(all(test, feature = "gzip"))]
+mod test {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn compresses_above_threshold() {
+        let body = br#"{"payload":{"summary":"small"}}"#.to_vec();
+        let (encoded, encoding) = encode(body.clone(), Some(1024)).unwrap();
+        assert_eq!(encoding, CONTENT_ENCODING_IDENTITY);
+        assert_eq!(encoded, body);
+
+        let body = "stack frame\n".repeat(1000).into_bytes();
+        let (encoded, encoding) = encode(body.clone(), Some(1024)).unwrap();
+        assert_eq!(encoding, CONTENT_ENCODING_GZIP);
+        assert!(encoded.len() < body.len());
+
+        let mut decoded = vec![];
+        GzDecoder::new(encoded.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+    }
+
+    #[test]
+    fn never_compresses_without_threshold() {
+        let body = "stack frame\n".repeat(1000).into_bytes();
+        let (_, encoding) = encode(body, None).unwrap();
+        assert_eq!(encoding, CONTENT_ENCODING_IDENTITY);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::types::*;
+
+use serde::Serialize;
+use std::error::Error;
+
+/// The custom_details of an alert built from an error.
+#[derive(Serialize)]
+pub struct ErrorDetails {
+    /// The error and every source() below it, outermost first.
+    pub chain: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backtrace: Option<String>,
+}
+
+impl AlertTrigger<ErrorDetails> {
+    /// Build an alert from an error and its chain of sources.
+    ///
+    /// The summary is the error's own message; the whole chain goes into custom_details.
+    /// The dedup key is a hash of every message in the chain with numbers and hex stripped,
+    /// so the same failure dedups into one alert even when it names different ports, IDs
+    /// or addresses.
+    pub fn from_error(err: &(dyn Error + 'static), source: String, severity: Severity) -> Self {
+        let mut chain = vec![err];
+        while let Some(next) = chain[chain.len() - 1].source() {
+            chain.push(next);
+        }
+
+        Self::from_chain(chain, None, source, severity)
+    }
+
+    /// Build an alert from an anyhow::Error, including its backtrace when one was captured.
+    #[cfg(feature = "anyhow")]
+    pub fn from_anyhow(err: &anyhow::Error, source: String, severity: Severity) -> Self {
+        let backtrace = err.backtrace();
+        let backtrace = match backtrace.status() {
+            std::backtrace::BacktraceStatus::Captured => Some(backtrace.to_string()),
+            _ => None,
+        };
+
+        Self::from_chain(err.chain().collect(), backtrace, source, severity)
+    }
+
+    /// Build an alert from an eyre::Report. Eyre keeps backtraces in its report handler,
+    /// so none is included.
+    #[cfg(feature = "eyre")]
+    pub fn from_eyre(err: &eyre::Report, source: String, severity: Severity) -> Self {
+        Self::from_chain(err.chain().collect(), None, source, severity)
+    }
+
+    fn from_chain(
+        chain: Vec<&dyn Error>,
+        backtrace: Option<String>,
+        source: String,
+        severity: Severity,
+    ) -> Self {
+        let chain: Vec<String> = chain.iter().map(|e| e.to_string()).collect();
+        let shapes: Vec<String> = chain.iter().map(|m| strip_numbers(m)).collect();
+        let dedup_key = truncate_dedup_key(&format!(
+            "error:{:016x}",
+            fingerprint(shapes.iter().map(|s| s.as_str()))
+        ));
+
+        AlertTrigger {
+            payload: AlertTriggerPayload {
+                severity,
+                summary: truncate_summary(&chain[0]),
+                source,
+                timestamp: Some(time::OffsetDateTime::now_utc()),
+                component: None,
+                group: None,
+                class: Some("error".to_owned()),
+                custom_details: Some(ErrorDetails { chain, backtrace }),
+            },
+            dedup_key: Some(dedup_key),
+            images: None,
+            links: None,
+            client: None,
+            client_url: None,
+        }
+    }
+}
+
+/// A message with its numbers and hex (addresses, IDs, UUIDs) removed, e.g.
+/// "connect to 10.0.0.5:5432 failed" becomes "connect to ...: failed".
+fn strip_numbers(message: &str) -> String {
+    let mut stripped = String::with_capacity(message.len());
+    let mut word = String::new();
+    for c in message.chars().map(Some).chain(std::iter::once(None)) {
+        match c {
+            Some(c) if c.is_ascii_alphanumeric() => word.push(c),
+            _ => {
+                let hex = word.strip_prefix("0x").unwrap_or(&word);
+                let is_number = word.bytes().any(|b| b.is_ascii_digit())
+                    && hex.bytes().all(|b| b.is_ascii_hexdigit());
+                if !is_number {
+                    stripped.extend(word.chars().filter(|c| !c.is_ascii_digit()));
+                }
+                word.clear();
+                stripped.extend(c);
+            }
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fmt::{Display, Formatter, Result as FmtResult};
+
+    #[derive(Debug)]
+    enum ConfigError {
+        Unreadable(std::io::Error),
+    }
+
+    impl Display for ConfigError {
+        fn fmt(&self, f: &mut Formatter) -> FmtResult {
+            write!(f, "config could not be read")
+        }
+    }
+
+    impl Error for ConfigError {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            match self {
+                Self::Unreadable(e) => Some(e),
+            }
+        }
+    }
+
+    fn config_error(path: &str) -> ConfigError {
+        ConfigError::Unreadable(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} not found", path),
+        ))
+    }
+
+    #[test]
+    fn flattens_source_chain() {
+        let at = AlertTrigger::from_error(
+            &config_error("/etc/app.toml"),
+            "hostname".to_owned(),
+            Severity::Error,
+        );
+
+        assert_eq!(at.payload.summary, "config could not be read");
+        assert_eq!(at.payload.class.as_deref(), Some("error"));
+        assert_eq!(
+            at.payload.custom_details.unwrap().chain,
+            vec!["config could not be read", "/etc/app.toml not found"]
+        );
+    }
+
+    #[test]
+    fn dedup_key_ignores_numbers() {
+        let a = AlertTrigger::from_error(
+            &config_error("/var/lib/app/4711.db"),
+            "h".to_owned(),
+            Severity::Error,
+        );
+        let b = AlertTrigger::from_error(
+            &config_error("/var/lib/app/815.db"),
+            "h".to_owned(),
+            Severity::Error,
+        );
+        assert_eq!(a.dedup_key, b.dedup_key);
+        assert!(a.dedup_key.as_deref().unwrap().starts_with("error:"));
+
+        // Every level of the chain counts, not just the outermost.
+        let c = AlertTrigger::from_error(
+            &config_error("/etc/app.toml"),
+            "h".to_owned(),
+            Severity::Error,
+        );
+        assert_ne!(a.dedup_key, c.dedup_key);
+
+        let d = AlertTrigger::from_error(
+            &"oops".parse::<u32>().unwrap_err(),
+            "h".to_owned(),
+            Severity::Error,
+        );
+        assert_ne!(a.dedup_key, d.dedup_key);
+    }
+
+    #[test]
+    fn strips_numbers_and_hex() {
+        assert_eq!(
+            strip_numbers("connect to 10.0.0.5:5432 failed"),
+            "connect to ...: failed"
+        );
+        assert_eq!(
+            strip_numbers("request 550e8400-e29b-41d4-a716-446655440000 at 0x7ffd timed out"),
+            "request ---- at  timed out"
+        );
+        assert_eq!(strip_numbers("worker3 is dead"), "worker is dead");
+        assert_eq!(strip_numbers("cafe is closed"), "cafe is closed");
+    }
+
+    #[test]
+    fn boxed_errors() {
+        let a: Box<dyn Error> = "query took 1500ms".into();
+        let b: Box<dyn Error> = format!("query took {}ms", 20).into();
+        let a = AlertTrigger::from_error(&*a, "h".to_owned(), Severity::Error);
+        let b = AlertTrigger::from_error(&*b, "h".to_owned(), Severity::Error);
+
+        assert_eq!(a.payload.summary, "query took 1500ms");
+        assert_eq!(a.dedup_key, b.dedup_key);
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn from_anyhow_context() {
+        let err = anyhow::Error::new(config_error("/etc/app.toml")).context("startup failed");
+        let at = AlertTrigger::from_anyhow(&err, "hostname".to_owned(), Severity::Critical);
+
+        assert_eq!(at.payload.summary, "startup failed");
+        assert_eq!(
+            at.payload.custom_details.unwrap().chain,
+            vec![
+                "startup failed",
+                "config could not be read",
+                "/etc/app.toml not found"
+            ]
+        );
+    }
+}
@@ -1,4 +1,8 @@
+use crate::circuitbreaker::*;
+use crate::compression;
 use crate::private_types::*;
+use crate::queue::*;
+use crate::telemetry;
 use crate::types::*;
 
 use reqwest::blocking::Client;
@@ -6,12 +10,16 @@ use reqwest::header::{
     HeaderMap, HeaderValue, InvalidHeaderValue, CONTENT_ENCODING, CONTENT_TYPE, USER_AGENT,
 };
 use serde::Serialize;
+use serde_json::Value;
 use std::convert::From;
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-const CONTENT_ENCODING_IDENTITY: &str = "identity";
 const CONTENT_TYPE_JSON: &str = "application/json";
+const DEFAULT_BASE_URL: &str = "https://events.pagerduty.com";
 
 #[derive(Debug)]
 pub enum EventsV2Error {
@@ -21,6 +29,10 @@ pub enum EventsV2Error {
     //https://developer.pagerduty.com/docs/events-api-v2/overview/#api-response-codes--retry-logic
     HttpNotAccepted(u16), // NOT 4xx, 5xx or 200 (we expect 202). Contains HTTP response code.
     HttpError(u16),       // A legit error (4xx or 5xx). Contains HTTP response code.
+
+    // Errors from a circuit breaker fallback
+    SerdeJsonError(serde_json::Error),
+    IoError(std::io::Error),
 }
 
 impl Error for EventsV2Error {}
@@ -31,6 +43,8 @@ impl Display for EventsV2Error {
             Self::InvalidHeaderValue(e) => write!(f, "InvalidHeaderValue: {}", e),
             Self::HttpNotAccepted(e) => write!(f, "HttpNotAccepted: {}", e),
             Self::HttpError(e) => write!(f, "HttpError: {}", e),
+            Self::SerdeJsonError(e) => write!(f, "SerdeJsonError: {}", e),
+            Self::IoError(e) => write!(f, "IoError: {}", e),
         }
     }
 }
@@ -45,6 +59,29 @@ impl From<InvalidHeaderValue> for EventsV2Error {
     }
 }
 
+impl From<serde_json::Error> for EventsV2Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::SerdeJsonError(err)
+    }
+}
+impl From<std::io::Error> for EventsV2Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::IoError(err)
+    }
+}
+
+impl EventsV2Error {
+    /// Whether this error indicates PagerDuty is unreachable or degraded,
+    /// as opposed to it rejecting the event.
+    fn is_degraded(&self) -> bool {
+        match self {
+            Self::ReqwestError(_) => true,
+            Self::HttpError(e) => *e == 429 || *e >= 500,
+            _ => false,
+        }
+    }
+}
+
 pub type EventsV2Result = Result<(), EventsV2Error>;
 
 /// The main PagerDuty Events V2 API
@@ -52,6 +89,15 @@ pub struct EventsV2 {
     /// The integration/routing key for a generated PagerDuty service
     integration_key: String,
     client: Client,
+
+    /// Scheme and host of the Events API, e.g. https://events.pagerduty.com
+    base_url: String,
+
+    /// Optional circuit breaker guarding every post
+    circuit_breaker: Option<CircuitBreaker>,
+
+    /// Request bodies larger than this many bytes are gzipped
+    gzip_threshold: Option<usize>,
 }
 
 impl EventsV2 {
@@ -61,10 +107,6 @@ impl EventsV2 {
     ) -> Result<EventsV2, EventsV2Error> {
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_str(CONTENT_TYPE_JSON)?);
-        headers.insert(
-            CONTENT_ENCODING,
-            HeaderValue::from_str(CONTENT_ENCODING_IDENTITY)?,
-        );
         if let Some(ua) = user_agent {
             headers.insert(USER_AGENT, HeaderValue::from_str(ua.as_str())?);
         }
@@ -74,55 +116,307 @@ impl EventsV2 {
         Ok(EventsV2 {
             integration_key,
             client,
+            base_url: DEFAULT_BASE_URL.to_owned(),
+            circuit_breaker: None,
+            gzip_threshold: None,
         })
     }
 
+    /// Gzip request bodies larger than threshold bytes, setting Content-Encoding to match.
+    /// Useful for alerts with large custom_details (stack traces, query plans, etc.)
+    #[cfg(feature = "gzip")]
+    pub fn with_gzip_threshold(mut self, threshold: usize) -> Self {
+        self.gzip_threshold = Some(threshold);
+        self
+    }
+
+    /// Send events somewhere other than https://events.pagerduty.com,
+    /// e.g. https://events.eu.pagerduty.com or a local relay.
+    pub fn with_base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Guard every post with a circuit breaker. While the circuit is open, events are
+    /// handed to the breaker's fallback instead of being posted to PagerDuty.
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// The state of the circuit breaker, if there is one.
+    pub fn circuit_state(&self) -> Option<CircuitState> {
+        self.circuit_breaker.as_ref().map(|cb| cb.state())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "pagerduty.event",
+            skip_all,
+            fields(
+                routing_key = %telemetry::redact(&self.integration_key),
+                event_action = telemetry::event_action(&event),
+                dedup_key = event.dedup_key(),
+            )
+        )
+    )]
     pub fn event<T: Serialize>(&self, event: Event<T>) -> EventsV2Result {
+        let labels = telemetry::EventLabels::of(&event);
         match event {
-            Event::Change(c) => self.change(c),
-            Event::AlertTrigger(at) => self.alert_trigger(at),
-            Event::AlertAcknowledge(aa) => self.alert_followup(aa.dedup_key, Action::Acknowledge),
-            Event::AlertResolve(ar) => self.alert_followup(ar.dedup_key, Action::Resolve),
+            Event::Change(c) => self.change(c, labels),
+            Event::AlertTrigger(at) => self.alert_trigger(at, labels),
+            Event::AlertAcknowledge(aa) => {
+                self.alert_followup(aa.dedup_key, Action::Acknowledge, labels)
+            }
+            Event::AlertResolve(ar) => self.alert_followup(ar.dedup_key, Action::Resolve, labels),
+        }
+    }
+
+    /// Send every event pending in the queue, in priority order. Events that
+    /// fail to send are not requeued; their errors are returned.
+    pub fn drain<T: Serialize>(&self, queue: &EventQueue<T>) -> Vec<EventsV2Error> {
+        let mut errors = vec![];
+        while let Some(event) = queue.pop() {
+            if let Err(e) = self.event(event) {
+                errors.push(e);
+            }
         }
+        errors
     }
 
-    fn change<T: Serialize>(&self, change: Change<T>) -> EventsV2Result {
+    fn change<T: Serialize>(
+        &self,
+        change: Change<T>,
+        labels: telemetry::EventLabels,
+    ) -> EventsV2Result {
         let sendable_change = SendableChange::from_change(change, self.integration_key.clone());
 
         self.do_post(
-            "https://events.pagerduty.com/v2/change/enqueue",
+            &format!("{}/v2/change/enqueue", self.base_url),
             sendable_change,
+            labels,
         )
     }
 
-    fn alert_trigger<T: Serialize>(&self, alert_trigger: AlertTrigger<T>) -> EventsV2Result {
+    fn alert_trigger<T: Serialize>(
+        &self,
+        alert_trigger: AlertTrigger<T>,
+        labels: telemetry::EventLabels,
+    ) -> EventsV2Result {
         let sendable_alert_trigger =
             SendableAlertTrigger::from_alert_trigger(alert_trigger, self.integration_key.clone());
 
         self.do_post(
-            "https://events.pagerduty.com/v2/enqueue",
+            &format!("{}/v2/enqueue", self.base_url),
             sendable_alert_trigger,
+            labels,
         )
     }
 
-    fn alert_followup(&self, dedup_key: String, action: Action) -> EventsV2Result {
+    fn alert_followup(
+        &self,
+        dedup_key: String,
+        action: Action,
+        labels: telemetry::EventLabels,
+    ) -> EventsV2Result {
         let sendable_alert_followup =
             SendableAlertFollowup::new(dedup_key, action, self.integration_key.clone());
 
         self.do_post(
-            "https://events.pagerduty.com/v2/enqueue",
+            &format!("{}/v2/enqueue", self.base_url),
             sendable_alert_followup,
+            labels,
+        )
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "pagerduty.do_post",
+            skip_all,
+            fields(
+                url = url,
+                http.status = tracing::field::Empty,
+                latency_ms = tracing::field::Empty,
+                fallback = tracing::field::Empty,
+            )
         )
+    )]
+    fn do_post<T: Serialize>(
+        &self,
+        url: &str,
+        content: T,
+        labels: telemetry::EventLabels,
+    ) -> EventsV2Result {
+        let circuit_breaker = match &self.circuit_breaker {
+            Some(cb) => cb,
+            None => return self.post(url, &content, labels),
+        };
+
+        if !circuit_breaker.allow_request() {
+            telemetry::circuit_open(url, labels);
+            return self.fallback(circuit_breaker, url, serde_json::to_value(&content)?);
+        }
+
+        let result = self.post(url, &content, labels);
+        match &result {
+            Err(e) if e.is_degraded() => circuit_breaker.record_failure(),
+            _ => circuit_breaker.record_success(),
+        }
+        result
+    }
+
+    /// Post to PagerDuty, counting the event as sent and then as accepted or rejected.
+    fn post<T: Serialize>(
+        &self,
+        url: &str,
+        content: &T,
+        labels: telemetry::EventLabels,
+    ) -> EventsV2Result {
+        telemetry::event_sent(labels);
+        let result = self.send(url, content);
+        telemetry::event_outcome(labels, result.is_ok());
+        result
+    }
+
+    fn fallback(
+        &self,
+        circuit_breaker: &CircuitBreaker,
+        url: &str,
+        mut content: Value,
+    ) -> EventsV2Result {
+        match circuit_breaker.fallback() {
+            Fallback::SecondaryRoutingKey(key) => {
+                content["routing_key"] = Value::String(key.clone());
+                self.send(url, &content)
+            }
+            fallback => Ok(fallback.handle_locally(url, &content)?),
+        }
+    }
+
+    fn send<T: Serialize>(&self, url: &str, content: &T) -> EventsV2Result {
+        let (body, encoding) =
+            compression::encode(serde_json::to_vec(content)?, self.gzip_threshold)?;
+        let start = Instant::now();
+        let res = self
+            .client
+            .post(url)
+            .header(CONTENT_ENCODING, encoding)
+            .body(body)
+            .send();
+
+        let result = match res {
+            Ok(res) => {
+                let status = res.status().as_u16();
+                telemetry::record_response(status, start.elapsed());
+                match status {
+                    202 => Ok(()),
+                    e if e < 400 => Err(EventsV2Error::HttpNotAccepted(e)),
+                    e => Err(EventsV2Error::HttpError(e)),
+                }
+            }
+            Err(e) => Err(EventsV2Error::from(e)),
+        };
+
+        if let Err(e) = &result {
+            telemetry::post_failed(e, start.elapsed());
+        }
+        result
+    }
+}
+
+/// Sends events from a background thread, so callers never wait on HTTP.
+///
+/// Pending events are held in a bounded EventQueue and sent in priority order,
+/// so critical triggers skip ahead of a backlog of info alerts and change events.
+/// Events that fail to send are dropped (a circuit breaker fallback on the
+/// EventsV2 client can spool them instead).
+pub struct QueuedEventsV2<T: Serialize + Send + 'static> {
+    queue: Arc<EventQueue<T>>,
+    worker: Option<JoinHandle<()>>,
+
+    /// Events queued but not yet sent (or failed), including the one being sent.
+    unsent: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl<T: Serialize + Send + 'static> QueuedEventsV2<T> {
+    pub fn new(events_v2: EventsV2, capacity: usize) -> QueuedEventsV2<T> {
+        let queue = Arc::new(EventQueue::new(capacity));
+        let unsent = Arc::new((Mutex::new(0), Condvar::new()));
+        let worker_queue = queue.clone();
+        let worker_unsent = unsent.clone();
+        let worker = thread::spawn(move || {
+            while let Some(event) = worker_queue.pop_wait() {
+                let _ = events_v2.event(event);
+                Self::add_unsent(&worker_unsent, -1);
+            }
+        });
+
+        QueuedEventsV2 {
+            queue,
+            worker: Some(worker),
+            unsent,
+        }
+    }
+
+    /// Queue an event for sending. If the queue is full, the lowest priority
+    /// event is dropped and returned.
+    pub fn event(&self, event: Event<T>) -> Option<Event<T>> {
+        // Counted before the push, so the worker can never finish sending it first.
+        Self::add_unsent(&self.unsent, 1);
+        let dropped = self.queue.push(event);
+        if dropped.is_some() {
+            Self::add_unsent(&self.unsent, -1);
+        }
+        dropped
+    }
+
+    /// Wait up to timeout for every queued event to be sent, without stopping
+    /// the background thread. Returns whether everything was sent in time.
+    pub fn flush(&self, timeout: Duration) -> bool {
+        let (unsent, sent) = &*self.unsent;
+        let unsent = match unsent.lock() {
+            Ok(u) => u,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let unsent = match sent.wait_timeout_while(unsent, timeout, |u| *u > 0) {
+            Ok((u, _)) => u,
+            Err(poisoned) => poisoned.into_inner().0,
+        };
+        *unsent == 0
     }
 
-    // Make this part Async in the future
-    fn do_post<T: Serialize>(&self, url: &str, content: T) -> EventsV2Result {
-        let res = self.client.post(url).json(&content).send()?;
+    /// The number of events waiting to be sent.
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Send everything still queued, then stop the background thread.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
 
-        match res.status().as_u16() {
-            202 => Ok(()),
-            e if e < 400 => Err(EventsV2Error::HttpNotAccepted(e)),
-            e => Err(EventsV2Error::HttpError(e)),
+    fn stop(&mut self) {
+        self.queue.close();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
         }
     }
+
+    fn add_unsent(unsent: &(Mutex<usize>, Condvar), delta: isize) {
+        let (unsent, sent) = unsent;
+        let mut unsent = match unsent.lock() {
+            Ok(u) => u,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *unsent = (*unsent as isize + delta) as usize;
+        sent.notify_all();
+    }
+}
+
+impl<T: Serialize + Send + 'static> Drop for QueuedEventsV2<T> {
+    fn drop(&mut self) {
+        self.stop();
+    }
 }
@@ -0,0 +1,291 @@
+//! Translates Grafana unified alerting webhook notifications into Events V2 events.
+//!
+//! Every alert in a notification becomes its own event, deduplicated on the alert's
+//! fingerprint, so each Grafana alert instance maps to one PagerDuty alert.
+
+use crate::types::*;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+pub type KV = BTreeMap<String, String>;
+
+/// A Grafana unified alerting webhook notification.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    #[serde(default)]
+    pub receiver: String,
+    pub status: Status,
+    #[serde(default)]
+    pub org_id: u64,
+    pub alerts: Vec<Alert>,
+    #[serde(default)]
+    pub group_labels: KV,
+    #[serde(default)]
+    pub common_labels: KV,
+    #[serde(default)]
+    pub common_annotations: KV,
+    #[serde(rename = "externalURL", default)]
+    pub external_url: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub group_key: String,
+    #[serde(default)]
+    pub truncated_alerts: u64,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub message: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    pub status: Status,
+    #[serde(default)]
+    pub labels: KV,
+    #[serde(default)]
+    pub annotations: KV,
+    #[serde(default)]
+    pub starts_at: String,
+    #[serde(default)]
+    pub ends_at: String,
+    #[serde(rename = "generatorURL", default)]
+    pub generator_url: String,
+    #[serde(default)]
+    pub fingerprint: String,
+    #[serde(rename = "silenceURL", default)]
+    pub silence_url: String,
+    #[serde(rename = "dashboardURL", default)]
+    pub dashboard_url: String,
+    #[serde(rename = "panelURL", default)]
+    pub panel_url: String,
+    #[serde(rename = "imageURL", default)]
+    pub image_url: String,
+    #[serde(default)]
+    pub values: Option<BTreeMap<String, Value>>,
+    #[serde(default)]
+    pub value_string: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Firing,
+    Resolved,
+}
+
+pub struct GrafanaOptions {
+    /// Used as the alert's source when it has no `instance` label.
+    pub source: String,
+
+    /// The label carrying each alert's severity.
+    pub severity_label: String,
+
+    /// Severity for alerts whose severity label is missing or unrecognized.
+    pub default_severity: Severity,
+}
+
+impl GrafanaOptions {
+    pub fn new(source: String) -> Self {
+        GrafanaOptions {
+            source,
+            severity_label: "severity".to_owned(),
+            default_severity: Severity::Error,
+        }
+    }
+}
+
+/// Translate every alert in a notification into a trigger (if it's firing) or a resolve.
+pub fn to_events(webhook: &Webhook, options: &GrafanaOptions) -> Vec<Event<Value>> {
+    webhook
+        .alerts
+        .iter()
+        .map(|alert| to_event(alert, options))
+        .collect()
+}
+
+pub fn to_event(alert: &Alert, options: &GrafanaOptions) -> Event<Value> {
+    let dedup_key = dedup_key(alert);
+    if alert.status == Status::Resolved {
+        return Event::AlertResolve(AlertResolve { dedup_key });
+    }
+
+    let summary = alert
+        .annotations
+        .get("summary")
+        .or_else(|| alert.annotations.get("description"))
+        .or_else(|| alert.labels.get("alertname"))
+        .cloned()
+        .unwrap_or_else(|| "Grafana alert".to_owned());
+
+    let links: Links = [
+        (&alert.panel_url, "Panel"),
+        (&alert.dashboard_url, "Dashboard"),
+        (&alert.generator_url, "Alert rule"),
+        (&alert.silence_url, "Silence"),
+    ]
+    .iter()
+    .filter(|(href, _)| !href.is_empty())
+    .map(|(href, text)| Link {
+        href: (*href).clone(),
+        text: Some((*text).to_owned()),
+    })
+    .collect();
+
+    // PagerDuty only shows images served over HTTPS.
+    let images: Images = if alert.image_url.starts_with("https://") {
+        vec![Image {
+            src: alert.image_url.clone(),
+            href: if alert.panel_url.is_empty() {
+                None
+            } else {
+                Some(alert.panel_url.clone())
+            },
+            alt: Some(summary.clone()),
+        }]
+    } else {
+        vec![]
+    };
+
+    Event::AlertTrigger(AlertTrigger {
+        payload: AlertTriggerPayload {
+            severity: alert
+                .labels
+                .get(&options.severity_label)
+                .and_then(|s| Severity::from_label(s))
+                .unwrap_or(options.default_severity),
+            summary: truncate_summary(&summary),
+            source: alert
+                .labels
+                .get("instance")
+                .cloned()
+                .unwrap_or_else(|| options.source.clone()),
+            timestamp: OffsetDateTime::parse(&alert.starts_at, &Rfc3339).ok(),
+            component: alert.labels.get("component").cloned(),
+            group: alert.labels.get("grafana_folder").cloned(),
+            class: alert.labels.get("alertname").cloned(),
+            custom_details: Some(serde_json::json!({
+                "labels": alert.labels,
+                "annotations": alert.annotations,
+                "values": alert.values,
+                "value_string": alert.value_string,
+            })),
+        },
+        dedup_key: Some(dedup_key),
+        images: if images.is_empty() {
+            None
+        } else {
+            Some(images)
+        },
+        links: if links.is_empty() { None } else { Some(links) },
+        client: Some("Grafana".to_owned()),
+        client_url: None,
+    })
+}
+
+fn dedup_key(alert: &Alert) -> String {
+    if !alert.fingerprint.is_empty() {
+        return format!("grafana:{}", alert.fingerprint);
+    }
+
+    let labels: Vec<String> = alert
+        .labels
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect();
+    truncate_dedup_key(&format!("grafana:{}", labels.join(",")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const NOTIFICATION: &str = r#"{
+        "receiver": "pagerduty",
+        "status": "firing",
+        "orgId": 1,
+        "alerts": [{
+            "status": "firing",
+            "labels": {"alertname": "HighCPU", "severity": "warning", "grafana_folder": "Infra", "instance": "web-1"},
+            "annotations": {"summary": "CPU above 90% on web-1"},
+            "startsAt": "2021-05-30T00:00:00Z",
+            "endsAt": "0001-01-01T00:00:00Z",
+            "generatorURL": "https://grafana.example.com/alerting/grafana/abc/view",
+            "fingerprint": "57c6d9296de2ad39",
+            "silenceURL": "https://grafana.example.com/alerting/silence/new",
+            "dashboardURL": "https://grafana.example.com/d/abc",
+            "panelURL": "https://grafana.example.com/d/abc?viewPanel=1",
+            "imageURL": "https://grafana.example.com/render/abc.png",
+            "values": {"B": 93.5},
+            "valueString": "[ var='B' labels={} value=93.5 ]"
+        }, {
+            "status": "resolved",
+            "labels": {"alertname": "HighCPU", "instance": "web-2"},
+            "annotations": {},
+            "fingerprint": "a1b2c3d4e5f60718"
+        }],
+        "groupLabels": {"alertname": "HighCPU"},
+        "commonLabels": {"alertname": "HighCPU"},
+        "commonAnnotations": {},
+        "externalURL": "https://grafana.example.com/",
+        "version": "1",
+        "groupKey": "{}:{alertname=\"HighCPU\"}",
+        "truncatedAlerts": 0,
+        "title": "[FIRING:1, RESOLVED:1] HighCPU",
+        "state": "alerting",
+        "message": ""
+    }"#;
+
+    #[test]
+    fn converts_each_alert() {
+        let webhook: Webhook = serde_json::from_str(NOTIFICATION).unwrap();
+        let mut events = to_events(&webhook, &GrafanaOptions::new("grafana".to_owned()));
+        assert_eq!(events.len(), 2);
+
+        match events.remove(0) {
+            Event::AlertTrigger(at) => {
+                assert_eq!(at.dedup_key.as_deref(), Some("grafana:57c6d9296de2ad39"));
+                assert_eq!(at.payload.summary, "CPU above 90% on web-1");
+                assert_eq!(at.payload.severity, Severity::Warning);
+                assert_eq!(at.payload.source, "web-1");
+                assert_eq!(at.payload.group.as_deref(), Some("Infra"));
+                let links = at.links.unwrap();
+                assert_eq!(
+                    links[0].href,
+                    "https://grafana.example.com/d/abc?viewPanel=1"
+                );
+                assert_eq!(links[0].text.as_deref(), Some("Panel"));
+                assert_eq!(links.len(), 4);
+                let images = at.images.unwrap();
+                assert_eq!(images[0].src, "https://grafana.example.com/render/abc.png");
+            }
+            _ => panic!("expected a trigger"),
+        }
+
+        match events.remove(0) {
+            Event::AlertResolve(ar) => assert_eq!(ar.dedup_key, "grafana:a1b2c3d4e5f60718"),
+            _ => panic!("expected a resolve"),
+        }
+    }
+
+    #[test]
+    fn configurable_severity_label() {
+        let webhook: Webhook = serde_json::from_str(NOTIFICATION).unwrap();
+        let mut options = GrafanaOptions::new("grafana".to_owned());
+        options.severity_label = "priority".to_owned();
+        options.default_severity = Severity::Critical;
+
+        match to_event(&webhook.alerts[0], &options) {
+            Event::AlertTrigger(at) => assert_eq!(at.payload.severity, Severity::Critical),
+            _ => panic!("expected a trigger"),
+        }
+    }
+}
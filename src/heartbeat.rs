@@ -0,0 +1,428 @@
+//! A dead-man's switch: page when a job *stops* checking in.
+//!
+//! Jobs record pings in a state directory, one file per heartbeat, either directly through
+//! `HeartbeatStore` or by running under the `heartbeat` binary. A `HeartbeatWatcher`, embedded
+//! in a long running process or run as `heartbeat watch`, triggers an alert when a heartbeat
+//! hasn't been seen within its window and resolves it on the next ping. The state is on disk,
+//! so neither pings nor open alerts are lost when the watcher restarts.
+
+use crate::eventsv2sync::*;
+use crate::types::*;
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+/// The outcome of one run of a job.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunReport {
+    /// Unix time the run finished.
+    pub finished_at: i64,
+
+    /// None if the job was killed by a signal.
+    pub exit_status: Option<i32>,
+
+    pub duration_secs: f64,
+}
+
+/// What's recorded in a heartbeat's state file.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PingState {
+    /// Unix time of the last ping or successful run.
+    pub last_seen: Option<i64>,
+
+    pub last_run: Option<RunReport>,
+}
+
+/// A directory holding the state of every heartbeat.
+///
+/// Pings are written to `<name>.json` and only by pingers; whether an alert is open is kept
+/// in `<name>.alerting`, written only by the watcher, so the two never overwrite each other.
+/// Pingers update `<name>.json` while holding an advisory lock on `<name>.lock`, so concurrent
+/// pings and runs, from threads or processes, never lose each other's updates.
+#[derive(Debug, Clone)]
+pub struct HeartbeatStore {
+    dir: PathBuf,
+}
+
+impl HeartbeatStore {
+    pub fn new(dir: PathBuf) -> IoResult<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(HeartbeatStore { dir })
+    }
+
+    pub fn ping(&self, name: &str) -> IoResult<()> {
+        self.update(name, |state| state.last_seen = Some(now()))
+    }
+
+    /// Record a run of a job. Only a successful run counts as a ping, so a job that keeps
+    /// failing is as dead as one that stopped running.
+    pub fn record_run(
+        &self,
+        name: &str,
+        exit_status: Option<i32>,
+        duration: Duration,
+    ) -> IoResult<()> {
+        self.update(name, |state| {
+            let finished_at = now();
+            if exit_status == Some(0) {
+                state.last_seen = Some(finished_at);
+            }
+            state.last_run = Some(RunReport {
+                finished_at,
+                exit_status,
+                duration_secs: duration.as_secs_f64(),
+            });
+        })
+    }
+
+    /// Run a command, recording its exit status and duration.
+    ///
+    /// A command that can't be started is recorded as a failed run, then its error returned.
+    pub fn run(&self, name: &str, command: &[String]) -> IoResult<Option<i32>> {
+        check_name(name)?;
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "empty command"))?;
+        let started = Instant::now();
+        match Command::new(program).args(args).status() {
+            Ok(status) => {
+                self.record_run(name, status.code(), started.elapsed())?;
+                Ok(status.code())
+            }
+            Err(e) => {
+                self.record_run(name, None, started.elapsed())?;
+                Err(e)
+            }
+        }
+    }
+
+    pub fn read(&self, name: &str) -> IoResult<PingState> {
+        match fs::read(self.path(name, "json")?) {
+            Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(PingState::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read, change and write a heartbeat's state under its lock.
+    fn update(&self, name: &str, change: impl FnOnce(&mut PingState)) -> IoResult<()> {
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path(name, "lock")?)?;
+        // Released when the file is closed, even if this process dies.
+        lock.lock()?;
+
+        let mut state = self.read(name)?;
+        change(&mut state);
+        self.write(name, &state)
+    }
+
+    fn write(&self, name: &str, state: &PingState) -> IoResult<()> {
+        // Write then rename, so the watcher never sees half a file. Writers hold the lock,
+        // so they never write into each other's temporary file.
+        let path = self.path(name, "json")?;
+        let tmp = self.path(name, "json.tmp")?;
+        fs::write(&tmp, serde_json::to_vec(state)?)?;
+        fs::rename(tmp, path)
+    }
+
+    fn alerting(&self, name: &str) -> IoResult<bool> {
+        Ok(self.path(name, "alerting")?.exists())
+    }
+
+    fn set_alerting(&self, name: &str, alerting: bool) -> IoResult<()> {
+        let path = self.path(name, "alerting")?;
+        if alerting {
+            fs::write(path, now().to_string())
+        } else {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    fn path(&self, name: &str, extension: &str) -> IoResult<PathBuf> {
+        check_name(name)?;
+        Ok(self.dir.join(format!("{}.{}", name, extension)))
+    }
+}
+
+/// Names become file names, so keep them to a safe set of characters.
+fn check_name(name: &str) -> IoResult<()> {
+    if !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        Ok(())
+    } else {
+        Err(IoError::new(
+            ErrorKind::InvalidInput,
+            format!("invalid heartbeat name: {:?}", name),
+        ))
+    }
+}
+
+fn now() -> i64 {
+    OffsetDateTime::now_utc().unix_timestamp()
+}
+
+pub struct HeartbeatOptions {
+    /// The unique location of the affected system, preferably a hostname or FQDN.
+    pub source: String,
+
+    pub severity: Severity,
+}
+
+impl HeartbeatOptions {
+    pub fn new(source: String) -> Self {
+        HeartbeatOptions {
+            source,
+            severity: Severity::Critical,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Trigger,
+    Resolve,
+}
+
+/// Decide what a heartbeat needs: a trigger once it's overdue, a resolve once it's back.
+fn evaluate(last_seen: i64, window: Duration, alerting: bool, now: i64) -> Option<Action> {
+    let overdue = now - last_seen > window.as_secs() as i64;
+    match (overdue, alerting) {
+        (true, false) => Some(Action::Trigger),
+        (false, true) => Some(Action::Resolve),
+        _ => None,
+    }
+}
+
+pub struct HeartbeatWatcher {
+    events_v2: EventsV2,
+    store: HeartbeatStore,
+    options: HeartbeatOptions,
+    heartbeats: Vec<(String, Duration)>,
+
+    /// Heartbeats that have never pinged are measured from when watching began.
+    started_at: i64,
+}
+
+impl HeartbeatWatcher {
+    pub fn new(events_v2: EventsV2, store: HeartbeatStore, options: HeartbeatOptions) -> Self {
+        HeartbeatWatcher {
+            events_v2,
+            store,
+            options,
+            heartbeats: vec![],
+            started_at: now(),
+        }
+    }
+
+    /// Expect a ping from `name` at least every `window`.
+    pub fn watch(mut self, name: String, window: Duration) -> IoResult<Self> {
+        check_name(&name)?;
+        self.heartbeats.push((name, window));
+        Ok(self)
+    }
+
+    /// Embedded jobs can ping their watcher directly.
+    pub fn ping(&self, name: &str) -> IoResult<()> {
+        self.store.ping(name)
+    }
+
+    /// Check every heartbeat once, sending triggers and resolves as needed.
+    pub fn check(&self) -> Vec<EventsV2Error> {
+        let mut errors = vec![];
+        for (name, window) in self.heartbeats.iter() {
+            if let Err(e) = self.check_one(name, *window) {
+                errors.push(e);
+            }
+        }
+        errors
+    }
+
+    /// Check every `interval`, forever, handing each failure to `on_error`.
+    pub fn run(&self, interval: Duration, mut on_error: impl FnMut(EventsV2Error)) -> ! {
+        loop {
+            self.check().into_iter().for_each(&mut on_error);
+            thread::sleep(interval);
+        }
+    }
+
+    fn check_one(&self, name: &str, window: Duration) -> Result<(), EventsV2Error> {
+        let state = self.store.read(name)?;
+        let alerting = self.store.alerting(name)?;
+        let last_seen = state.last_seen.unwrap_or(self.started_at);
+
+        match evaluate(last_seen, window, alerting, now()) {
+            Some(Action::Trigger) => {
+                self.events_v2.event(self.trigger(name, window, &state))?;
+                self.store.set_alerting(name, true)?;
+            }
+            Some(Action::Resolve) => {
+                self.events_v2
+                    .event(Event::<()>::AlertResolve(AlertResolve {
+                        dedup_key: self.dedup_key(name),
+                    }))?;
+                self.store.set_alerting(name, false)?;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn dedup_key(&self, name: &str) -> String {
+        truncate_dedup_key(&format!("heartbeat:{}:{}", self.options.source, name))
+    }
+
+    fn trigger(&self, name: &str, window: Duration, state: &PingState) -> Event<PingState> {
+        let summary = match state.last_seen {
+            Some(_) => format!(
+                "Heartbeat '{}' missed: no ping in over {}s",
+                name,
+                window.as_secs()
+            ),
+            None => format!(
+                "Heartbeat '{}' missed: never pinged in {}s",
+                name,
+                window.as_secs()
+            ),
+        };
+
+        Event::AlertTrigger(AlertTrigger {
+            payload: AlertTriggerPayload {
+                severity: self.options.severity,
+                summary,
+                source: self.options.source.clone(),
+                timestamp: Some(OffsetDateTime::now_utc()),
+                component: Some(name.to_owned()),
+                group: None,
+                class: Some("heartbeat".to_owned()),
+                custom_details: Some(state.clone()),
+            },
+            dedup_key: Some(self.dedup_key(name)),
+            images: None,
+            links: None,
+            client: Some("pagerduty-rs".to_owned()),
+            client_url: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rand::Rng;
+    use std::ops::Deref;
+
+    /// A store in a fresh temporary directory, removed when dropped.
+    struct TempStore(HeartbeatStore);
+
+    impl Deref for TempStore {
+        type Target = HeartbeatStore;
+
+        fn deref(&self) -> &HeartbeatStore {
+            &self.0
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0.dir);
+        }
+    }
+
+    fn temp_store() -> TempStore {
+        let dir = std::env::temp_dir().join(format!(
+            "pagerduty-rs-heartbeat-{}",
+            rand::thread_rng().gen::<u64>()
+        ));
+        TempStore(HeartbeatStore::new(dir).unwrap())
+    }
+
+    #[test]
+    fn records_pings_and_runs() {
+        let store = temp_store();
+        assert_eq!(store.read("backup").unwrap(), PingState::default());
+
+        store
+            .record_run("backup", Some(1), Duration::from_secs(3))
+            .unwrap();
+        let state = store.read("backup").unwrap();
+        assert_eq!(state.last_seen, None);
+        assert_eq!(state.last_run.as_ref().unwrap().exit_status, Some(1));
+        assert_eq!(state.last_run.unwrap().duration_secs, 3.0);
+
+        store.ping("backup").unwrap();
+        assert!(store.read("backup").unwrap().last_seen.is_some());
+
+        store.set_alerting("backup", true).unwrap();
+        assert!(store.alerting("backup").unwrap());
+        store.set_alerting("backup", false).unwrap();
+        assert!(!store.alerting("backup").unwrap());
+
+        assert!(store.ping("../etc/passwd").is_err());
+        assert!(store.ping("").is_err());
+    }
+
+    #[test]
+    fn runs_commands() {
+        let store = temp_store();
+        assert_eq!(
+            store.run("job", &[]).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+
+        let missing = vec!["/nonexistent/pagerduty-rs-job".to_owned()];
+        assert!(store.run("job", &missing).is_err());
+        let state = store.read("job").unwrap();
+        assert_eq!(state.last_seen, None);
+        assert_eq!(state.last_run.unwrap().exit_status, None);
+    }
+
+    #[test]
+    fn concurrent_pings() {
+        let store = temp_store();
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for _ in 0..20 {
+                        store.ping("busy").unwrap();
+                    }
+                });
+            }
+            // A ping that read the state before this run was recorded would drop it.
+            s.spawn(|| {
+                store
+                    .record_run("busy", Some(1), Duration::from_secs(1))
+                    .unwrap()
+            });
+        });
+        let state = store.read("busy").unwrap();
+        assert!(state.last_seen.is_some());
+        assert_eq!(state.last_run.unwrap().exit_status, Some(1));
+    }
+
+    #[test]
+    fn triggers_once_overdue_and_resolves_on_return() {
+        let window = Duration::from_secs(60);
+        assert_eq!(evaluate(1000, window, false, 1060), None);
+        assert_eq!(evaluate(1000, window, false, 1061), Some(Action::Trigger));
+        assert_eq!(evaluate(1000, window, true, 1100), None);
+        assert_eq!(evaluate(1090, window, true, 1100), Some(Action::Resolve));
+        assert_eq!(evaluate(1090, window, false, 1100), None);
+    }
+}
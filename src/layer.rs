@@ -0,0 +1,287 @@
+use crate::eventsv2sync::*;
+use crate::types::*;
+
+use serde_json::{Map, Value};
+use std::fmt::Debug;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event as TracingEvent, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Targets that are never turned into alerts, since sending an alert emits events on them.
+const IGNORED_TARGETS: [&str; 3] = ["pagerduty_rs::telemetry", "reqwest", "hyper"];
+
+pub struct PagerDutyLayerOptions {
+    /// Events at or above this level trigger alerts.
+    pub level: Level,
+
+    /// Events carrying this field set to true trigger alerts regardless of level.
+    pub marker_field: String,
+
+    /// An event field supplying the dedup key. Without it, the dedup key is
+    /// derived from the event's target and call site.
+    pub dedup_key_field: String,
+
+    /// The unique location of the affected system, preferably a hostname or FQDN.
+    pub source: String,
+
+    /// How many alerts can wait to be sent before the lowest priority ones are dropped.
+    pub queue_capacity: usize,
+}
+
+impl PagerDutyLayerOptions {
+    pub fn new(source: String) -> Self {
+        PagerDutyLayerOptions {
+            level: Level::ERROR,
+            marker_field: "pagerduty".to_owned(),
+            dedup_key_field: "dedup_key".to_owned(),
+            source,
+            queue_capacity: 1000,
+        }
+    }
+}
+
+enum Sink {
+    Queued(QueuedEventsV2<Value>),
+    #[cfg(test)]
+    Captured(std::sync::Arc<std::sync::Mutex<Vec<Event<Value>>>>),
+}
+
+/// A tracing_subscriber Layer that raises PagerDuty alerts from tracing events.
+///
+/// Events at or above the configured level, or carrying the marker field, become
+/// AlertTriggers. Event fields and the fields of every enclosing span become custom_details.
+/// Alerts are sent from a background thread, so the instrumented code never waits on HTTP.
+pub struct PagerDutyLayer {
+    options: PagerDutyLayerOptions,
+    sink: Sink,
+}
+
+impl PagerDutyLayer {
+    pub fn new(events_v2: EventsV2, options: PagerDutyLayerOptions) -> PagerDutyLayer {
+        let sink = Sink::Queued(QueuedEventsV2::new(events_v2, options.queue_capacity));
+        PagerDutyLayer { options, sink }
+    }
+
+    fn alert_for<S>(&self, event: &TracingEvent, ctx: &Context<S>) -> Option<Event<Value>>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let metadata = event.metadata();
+        if IGNORED_TARGETS
+            .iter()
+            .any(|ignored| metadata.target().starts_with(ignored))
+        {
+            return None;
+        }
+
+        let mut fields = JsonVisitor::default();
+        event.record(&mut fields);
+        let mut fields = fields.0;
+
+        let marked = fields.remove(&self.options.marker_field) == Some(Value::Bool(true));
+        if !marked && *metadata.level() > self.options.level {
+            return None;
+        }
+
+        let message = match fields.remove("message") {
+            Some(Value::String(m)) => m,
+            Some(m) => m.to_string(),
+            None => metadata.name().to_owned(),
+        };
+
+        let dedup_key = match fields.remove(&self.options.dedup_key_field) {
+            Some(Value::String(k)) => k,
+            Some(k) => k.to_string(),
+            None => match (metadata.file(), metadata.line()) {
+                (Some(file), Some(line)) => {
+                    format!("tracing:{}:{}:{}", metadata.target(), file, line)
+                }
+                _ => format!("tracing:{}:{}", metadata.target(), message),
+            },
+        };
+
+        let mut spans = vec![];
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                let mut s = Map::new();
+                s.insert("name".to_owned(), Value::from(span.name()));
+                if let Some(span_fields) = span.extensions().get::<SpanFields>() {
+                    s.insert("fields".to_owned(), Value::Object(span_fields.0.clone()));
+                }
+                spans.push(Value::Object(s));
+            }
+        }
+
+        let mut custom_details = Map::new();
+        custom_details.insert("level".to_owned(), Value::from(metadata.level().as_str()));
+        custom_details.insert("target".to_owned(), Value::from(metadata.target()));
+        custom_details.insert("fields".to_owned(), Value::Object(fields));
+        if !spans.is_empty() {
+            custom_details.insert("spans".to_owned(), Value::Array(spans));
+        }
+
+        Some(Event::AlertTrigger(AlertTrigger {
+            payload: AlertTriggerPayload {
+                severity: severity(metadata.level()),
+                summary: truncate_summary(&message),
+                source: self.options.source.clone(),
+                timestamp: Some(time::OffsetDateTime::now_utc()),
+                component: Some(
+                    metadata
+                        .module_path()
+                        .unwrap_or_else(|| metadata.target())
+                        .to_owned(),
+                ),
+                group: None,
+                class: Some("tracing".to_owned()),
+                custom_details: Some(Value::Object(custom_details)),
+            },
+            dedup_key: Some(truncate_dedup_key(&dedup_key)),
+            images: None,
+            links: None,
+            client: Some("pagerduty-rs".to_owned()),
+            client_url: None,
+        }))
+    }
+}
+
+impl<S> Layer<S> for PagerDutyLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut fields = JsonVisitor::default();
+            attrs.record(&mut fields);
+            span.extensions_mut().insert(SpanFields(fields.0));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut fields = JsonVisitor::default();
+            values.record(&mut fields);
+            if let Some(span_fields) = span.extensions_mut().get_mut::<SpanFields>() {
+                span_fields.0.extend(fields.0);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &TracingEvent<'_>, ctx: Context<'_, S>) {
+        if let Some(alert) = self.alert_for(event, &ctx) {
+            match &self.sink {
+                Sink::Queued(sender) => {
+                    sender.event(alert);
+                }
+                #[cfg(test)]
+                Sink::Captured(captured) => captured.lock().unwrap().push(alert),
+            }
+        }
+    }
+}
+
+/// Fields recorded on a span, kept in its extensions.
+struct SpanFields(Map<String, Value>);
+
+/// Collects tracing fields into a JSON object.
+#[derive(Default)]
+struct JsonVisitor(Map<String, Value>);
+
+impl Visit for JsonVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_owned(), Value::from(format!("{:?}", value)));
+    }
+}
+
+fn severity(level: &Level) -> Severity {
+    match *level {
+        Level::ERROR => Severity::Error,
+        Level::WARN => Severity::Warning,
+        _ => Severity::Info,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn capture(f: impl FnOnce()) -> Vec<Event<Value>> {
+        let captured = Arc::new(Mutex::new(vec![]));
+        let layer = PagerDutyLayer {
+            options: PagerDutyLayerOptions::new("hostname".to_owned()),
+            sink: Sink::Captured(captured.clone()),
+        };
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), f);
+
+        let events = std::mem::take(&mut *captured.lock().unwrap());
+        events
+    }
+
+    #[test]
+    fn raises_alerts_from_events() {
+        let events = capture(|| {
+            let span = tracing::info_span!("request", user = "alice");
+            let _guard = span.enter();
+            tracing::info!("nothing to see here");
+            tracing::warn!(pagerduty = true, disk = "/dev/sda1", "disk almost full");
+            tracing::error!(dedup_key = "db-down", attempts = 3, "database unreachable");
+        });
+
+        assert_eq!(events.len(), 2);
+
+        match &events[0] {
+            Event::AlertTrigger(at) => {
+                assert_eq!(at.payload.summary, "disk almost full");
+                assert_eq!(at.payload.severity, Severity::Warning);
+                assert!(at.dedup_key.as_ref().unwrap().starts_with("tracing:"));
+                assert_eq!(
+                    at.payload.custom_details.as_ref().unwrap()["fields"],
+                    json!({"disk": "/dev/sda1"})
+                );
+            }
+            _ => panic!("expected a trigger"),
+        }
+
+        match &events[1] {
+            Event::AlertTrigger(at) => {
+                assert_eq!(at.payload.summary, "database unreachable");
+                assert_eq!(at.payload.severity, Severity::Error);
+                assert_eq!(at.dedup_key.as_deref(), Some("db-down"));
+                let details = at.payload.custom_details.as_ref().unwrap();
+                assert_eq!(details["fields"], json!({"attempts": 3}));
+                assert_eq!(
+                    details["spans"],
+                    json!([{"name": "request", "fields": {"user": "alice"}}])
+                );
+            }
+            _ => panic!("expected a trigger"),
+        }
+    }
+}
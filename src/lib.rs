@@ -1,9 +1,49 @@
+#[cfg(feature = "alertmanager")]
+pub mod alertmanager;
+
+#[cfg(feature = "grafana")]
+pub mod grafana;
+
+#[cfg(feature = "cloudwatch")]
+pub mod cloudwatch;
+
+#[cfg(feature = "nagios")]
+pub mod nagios;
+
+pub mod circuitbreaker;
+mod compression;
+pub mod errorchain;
 mod private_types;
+pub mod queue;
+mod telemetry;
+
+// Only the tests of some features send events over HTTP.
+#[cfg(all(test, feature = "sync"))]
+#[allow(dead_code)]
+mod standin;
 
 pub mod types;
 
+#[cfg(any(feature = "sync", feature = "async"))]
+pub mod rest;
+
 #[cfg(feature = "sync")]
 pub mod eventsv2sync;
 
+#[cfg(feature = "sync")]
+pub mod checks;
+
+#[cfg(feature = "sync")]
+pub mod heartbeat;
+
+#[cfg(feature = "sync")]
+pub mod panic;
+
 #[cfg(feature = "async")]
 pub mod eventsv2async;
+
+#[cfg(feature = "log")]
+pub mod logger;
+
+#[cfg(feature = "tracing-layer")]
+pub mod layer;
@@ -0,0 +1,349 @@
+use crate::eventsv2sync::*;
+use crate::types::*;
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Targets that are never turned into alerts, since sending an alert logs through them.
+const IGNORED_TARGETS: [&str; 3] = ["pagerduty_rs", "reqwest", "hyper"];
+
+/// How a log record's dedup key is derived.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DedupKeySource {
+    /// The file and line of the log statement. Every call site is its own alert.
+    CallSite,
+
+    /// The message's format string when it has no arguments, so the same fixed message
+    /// from anywhere is one alert. Messages with arguments fall back to the call site,
+    /// since their formatted text would give every argument its own alert.
+    MessageTemplate,
+}
+
+pub struct PagerDutyLoggerOptions {
+    /// Records at or above this level trigger alerts.
+    pub level: Level,
+
+    /// The unique location of the affected system, preferably a hostname or FQDN.
+    pub source: String,
+
+    /// How dedup keys are derived from log records.
+    pub dedup_key_source: DedupKeySource,
+
+    /// At most one alert is sent per dedup key within this window.
+    pub rate_limit: Duration,
+
+    /// How many alerts can wait to be sent before the lowest priority ones are dropped.
+    pub queue_capacity: usize,
+
+    /// How long Log::flush waits for queued alerts to be sent.
+    pub flush_timeout: Duration,
+}
+
+impl PagerDutyLoggerOptions {
+    pub fn new(source: String) -> Self {
+        PagerDutyLoggerOptions {
+            level: Level::Error,
+            source,
+            dedup_key_source: DedupKeySource::CallSite,
+            rate_limit: Duration::from_secs(60),
+            queue_capacity: 1000,
+            flush_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// The custom_details sent with every alert.
+#[derive(Serialize)]
+pub struct LogDetails {
+    pub level: String,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+/// A log::Log implementation that turns log records into PagerDuty alerts.
+///
+/// Records at or above the configured level are sent as AlertTriggers from a background
+/// thread, so logging never waits on HTTP. Every record is also passed to an inner logger,
+/// so normal logging keeps working.
+pub struct PagerDutyLogger {
+    options: PagerDutyLoggerOptions,
+    inner: Option<Box<dyn Log>>,
+    sender: QueuedEventsV2<LogDetails>,
+    last_sent: Mutex<HashMap<String, Instant>>,
+}
+
+impl PagerDutyLogger {
+    pub fn new(
+        events_v2: EventsV2,
+        options: PagerDutyLoggerOptions,
+        inner: Option<Box<dyn Log>>,
+    ) -> PagerDutyLogger {
+        let sender = QueuedEventsV2::new(events_v2, options.queue_capacity);
+        PagerDutyLogger {
+            options,
+            inner,
+            sender,
+            last_sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Install this as the global logger. The max level is the more verbose of
+    /// the alerting level and the inner logger's own.
+    pub fn init(self, inner_max_level: LevelFilter) -> Result<(), SetLoggerError> {
+        let max_level = inner_max_level.max(self.options.level.to_level_filter());
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+
+    fn alerts_on(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.options.level
+            && !IGNORED_TARGETS
+                .iter()
+                .any(|ignored| is_crate_target(metadata.target(), ignored))
+    }
+
+    /// Whether an alert for this dedup key is allowed through the rate limit right now.
+    fn rate_limit(&self, dedup_key: &str) -> bool {
+        let mut last_sent = match self.last_sent.lock() {
+            Ok(l) => l,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let now = Instant::now();
+        match last_sent.get(dedup_key) {
+            Some(at) if now.duration_since(*at) < self.options.rate_limit => false,
+            _ => {
+                last_sent.retain(|_, at| now.duration_since(*at) < self.options.rate_limit);
+                last_sent.insert(dedup_key.to_owned(), now);
+                true
+            }
+        }
+    }
+}
+
+impl Log for PagerDutyLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.alerts_on(metadata)
+            || self
+                .inner
+                .as_ref()
+                .map(|inner| inner.enabled(metadata))
+                .unwrap_or(false)
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some(inner) = &self.inner {
+            inner.log(record);
+        }
+
+        if !self.alerts_on(record.metadata()) {
+            return;
+        }
+
+        let dedup_key = dedup_key(record, self.options.dedup_key_source);
+        if !self.rate_limit(&dedup_key) {
+            return;
+        }
+
+        let message = record.args().to_string();
+        self.sender.event(Event::AlertTrigger(AlertTrigger {
+            payload: AlertTriggerPayload {
+                severity: severity(record.level()),
+                summary: truncate_summary(&message),
+                source: self.options.source.clone(),
+                timestamp: Some(time::OffsetDateTime::now_utc()),
+                component: Some(
+                    record
+                        .module_path()
+                        .unwrap_or_else(|| record.target())
+                        .to_owned(),
+                ),
+                group: None,
+                class: Some("log".to_owned()),
+                custom_details: Some(LogDetails {
+                    level: record.level().to_string(),
+                    target: record.target().to_owned(),
+                    module_path: record.module_path().map(|m| m.to_owned()),
+                    file: record.file().map(|f| f.to_owned()),
+                    line: record.line(),
+                    message,
+                }),
+            },
+            dedup_key: Some(dedup_key),
+            images: None,
+            links: None,
+            client: Some("pagerduty-rs".to_owned()),
+            client_url: None,
+        }));
+    }
+
+    fn flush(&self) {
+        self.sender.flush(self.options.flush_timeout);
+        if let Some(inner) = &self.inner {
+            inner.flush();
+        }
+    }
+}
+
+fn severity(level: Level) -> Severity {
+    match level {
+        Level::Error => Severity::Error,
+        Level::Warn => Severity::Warning,
+        Level::Info | Level::Debug | Level::Trace => Severity::Info,
+    }
+}
+
+/// Whether target is the crate itself or one of its modules, e.g. "hyper" or
+/// "hyper::client" but not "hyperlocal".
+fn is_crate_target(target: &str, name: &str) -> bool {
+    target
+        .strip_prefix(name)
+        .map(|rest| rest.is_empty() || rest.starts_with("::"))
+        .unwrap_or(false)
+}
+
+fn dedup_key(record: &Record, source: DedupKeySource) -> String {
+    let template = match source {
+        DedupKeySource::MessageTemplate => record.args().as_str(),
+        DedupKeySource::CallSite => None,
+    };
+    let key = match (template, record.file(), record.line()) {
+        (Some(template), _, _) => template.to_owned(),
+        (None, Some(file), Some(line)) => format!("{}:{}", file, line),
+        // Without a file and line, never fall back to the formatted message.
+        (None, _, _) => record
+            .args()
+            .as_str()
+            .or_else(|| record.module_path())
+            .unwrap_or_default()
+            .to_owned(),
+    };
+
+    truncate_dedup_key(&format!("log:{}:{}", record.target(), key))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::standin;
+    use pretty_assertions::assert_eq;
+    use std::fmt::Arguments;
+
+    fn key(args: Arguments, call_site: Option<(&str, u32)>, source: DedupKeySource) -> String {
+        dedup_key(
+            &Record::builder()
+                .args(args)
+                .level(Level::Error)
+                .target("mydaemon::storage")
+                .file(call_site.map(|(file, _)| file))
+                .line(call_site.map(|(_, line)| line))
+                .build(),
+            source,
+        )
+    }
+
+    #[test]
+    fn dedup_keys() {
+        let device = "/dev/sda1";
+        assert_eq!(
+            key(
+                format_args!("disk {} is full", device),
+                Some(("src/storage.rs", 42)),
+                DedupKeySource::CallSite
+            ),
+            "log:mydaemon::storage:src/storage.rs:42"
+        );
+        assert_eq!(
+            key(
+                format_args!("disk {} is full", device),
+                Some(("src/storage.rs", 42)),
+                DedupKeySource::MessageTemplate
+            ),
+            "log:mydaemon::storage:src/storage.rs:42"
+        );
+        assert_eq!(
+            key(
+                format_args!("disk is full"),
+                Some(("src/storage.rs", 42)),
+                DedupKeySource::MessageTemplate
+            ),
+            "log:mydaemon::storage:disk is full"
+        );
+        assert_eq!(
+            key(
+                format_args!("connection lost"),
+                None,
+                DedupKeySource::CallSite
+            ),
+            "log:mydaemon::storage:connection lost"
+        );
+    }
+
+    #[test]
+    fn ignores_only_whole_crates() {
+        assert!(is_crate_target("hyper", "hyper"));
+        assert!(is_crate_target("hyper::client::pool", "hyper"));
+        assert!(!is_crate_target("hyperlocal", "hyper"));
+        assert!(!is_crate_target("hyperion::render", "hyper"));
+    }
+
+    #[test]
+    fn rate_limits_alerts_per_dedup_key() {
+        let (url, sent) = standin::events_api(202);
+        let events_v2 = EventsV2::new("key".to_owned(), None)
+            .unwrap()
+            .with_base_url(url);
+        let logger = PagerDutyLogger::new(
+            events_v2,
+            PagerDutyLoggerOptions::new("host".to_owned()),
+            None,
+        );
+
+        let log = |device: &str, line: u32| {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("disk {} is full", device))
+                    .level(Level::Error)
+                    .target("mydaemon::storage")
+                    .file(Some("src/storage.rs"))
+                    .line(Some(line))
+                    .build(),
+            )
+        };
+        log("/dev/sda1", 42);
+        log("/dev/sdb1", 42);
+        log("/dev/sda1", 42);
+        log("/dev/sda1", 50);
+        logger.flush();
+
+        let mut keys: Vec<String> = sent
+            .try_iter()
+            .map(|body| body["dedup_key"].as_str().unwrap().to_owned())
+            .collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "log:mydaemon::storage:src/storage.rs:42",
+                "log:mydaemon::storage:src/storage.rs:50"
+            ]
+        );
+    }
+
+    #[test]
+    fn severities() {
+        assert_eq!(severity(Level::Error), Severity::Error);
+        assert_eq!(severity(Level::Warn), Severity::Warning);
+        assert_eq!(severity(Level::Debug), Severity::Info);
+    }
+}
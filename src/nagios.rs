@@ -0,0 +1,391 @@
+//! Turns Nagios plugin results (an exit code, output and perfdata) into Events V2 events.
+//!
+//! Results can come from running a plugin directly, or from a Nagios or Icinga notification
+//! command passing along `$SERVICESTATEID$`, `$SERVICEOUTPUT$` and friends. Host and service
+//! make the dedup key, so a check's incident is triggered while it's failing and resolved once
+//! it's OK again.
+
+use crate::types::*;
+
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::process::Command;
+
+/// https://nagios-plugins.org/doc/guidelines.html#AEN78
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum CheckState {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+impl CheckState {
+    /// Exit codes outside 0-3 (or a plugin killed by a signal) count as UNKNOWN.
+    pub fn from_exit_code(code: Option<i32>) -> Self {
+        match code {
+            Some(0) => Self::Ok,
+            Some(1) => Self::Warning,
+            Some(2) => Self::Critical,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// The state of a host check, from `$HOSTSTATEID$`. DOWN and UNREACHABLE are both CRITICAL.
+    pub fn from_host_state(state: Option<i32>) -> Self {
+        match state {
+            Some(0) => Self::Ok,
+            Some(1) | Some(2) => Self::Critical,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Ok => 0,
+            Self::Warning => 1,
+            Self::Critical => 2,
+            Self::Unknown => 3,
+        }
+    }
+
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            Self::Ok => None,
+            Self::Warning => Some(Severity::Warning),
+            Self::Critical => Some(Severity::Critical),
+            Self::Unknown => Some(Severity::Error),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Ok => "OK",
+            Self::Warning => "WARNING",
+            Self::Critical => "CRITICAL",
+            Self::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// Run a plugin, returning its state and its whole output, perfdata included.
+pub fn run_plugin(command: &[String]) -> IoResult<(CheckState, String)> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "empty plugin command"))?;
+    let output = Command::new(program).args(args).output()?;
+    Ok((
+        CheckState::from_exit_code(output.status.code()),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    ))
+}
+
+/// One performance data item: `'label'=value[UOM];[warn];[crit];[min];[max]`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PerfData {
+    pub label: String,
+    pub value: Option<f64>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub uom: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+/// The custom_details sent with a failing check.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    pub host: String,
+
+    /// None for a host check.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+
+    pub state: CheckState,
+
+    /// The first line of plugin output.
+    pub output: String,
+
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub long_output: String,
+
+    pub perfdata: Vec<PerfData>,
+}
+
+impl CheckResult {
+    /// Parse a plugin's output, splitting text from perfdata. Perfdata may follow a `|` on
+    /// the first line and on the long output's lines.
+    pub fn new(host: String, service: Option<String>, state: CheckState, output: &str) -> Self {
+        let mut lines = output.lines();
+        let (output, mut perfdata) = split_perfdata(lines.next().unwrap_or(""));
+
+        let mut long_output = vec![];
+        let mut in_perfdata = false;
+        for line in lines {
+            if in_perfdata {
+                perfdata.extend(parse_perfdata(line));
+            } else {
+                let (text, data) = split_perfdata(line);
+                long_output.push(text);
+                if line.contains('|') {
+                    perfdata.extend(data);
+                    in_perfdata = true;
+                }
+            }
+        }
+
+        CheckResult {
+            host,
+            service,
+            state,
+            output,
+            long_output: long_output.join("\n").trim_end().to_owned(),
+            perfdata,
+        }
+    }
+
+    /// Run a plugin and parse its result.
+    pub fn run(host: String, service: Option<String>, command: &[String]) -> IoResult<Self> {
+        let (state, output) = run_plugin(command)?;
+        Ok(Self::new(host, service, state, &output))
+    }
+
+    pub fn dedup_key(&self) -> String {
+        let key = match &self.service {
+            Some(service) => format!("nagios:{}/{}", self.host, service),
+            None => format!("nagios:{}", self.host),
+        };
+        truncate_dedup_key(&key)
+    }
+
+    /// A trigger for WARNING, CRITICAL and UNKNOWN; a resolve for OK.
+    pub fn to_event(&self) -> Event<Value> {
+        let severity = match self.state.severity() {
+            Some(severity) => severity,
+            None => {
+                return Event::AlertResolve(AlertResolve {
+                    dedup_key: self.dedup_key(),
+                })
+            }
+        };
+
+        let summary = format!(
+            "{} is {}: {}",
+            match &self.service {
+                Some(service) => format!("{} on {}", service, self.host),
+                None => self.host.clone(),
+            },
+            self.state.name(),
+            self.output
+        );
+
+        Event::AlertTrigger(AlertTrigger {
+            payload: AlertTriggerPayload {
+                severity,
+                summary: truncate_summary(&summary),
+                source: self.host.clone(),
+                timestamp: Some(time::OffsetDateTime::now_utc()),
+                component: self.service.clone(),
+                group: None,
+                class: Some(self.state.name().to_owned()),
+                custom_details: serde_json::to_value(self).ok(),
+            },
+            dedup_key: Some(self.dedup_key()),
+            images: None,
+            links: None,
+            client: Some("pagerduty-rs".to_owned()),
+            client_url: None,
+        })
+    }
+}
+
+fn split_perfdata(line: &str) -> (String, Vec<PerfData>) {
+    match line.split_once('|') {
+        Some((text, perfdata)) => (text.trim().to_owned(), parse_perfdata(perfdata)),
+        None => (line.trim().to_owned(), vec![]),
+    }
+}
+
+/// Parse space separated perfdata items, skipping any that are malformed.
+pub fn parse_perfdata(s: &str) -> Vec<PerfData> {
+    let mut items = vec![];
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        // Labels may be single quoted to contain spaces or '='; '' escapes a quote.
+        let label;
+        if let Some(quoted) = rest.strip_prefix('\'') {
+            let mut end = None;
+            let mut chars = quoted.char_indices().peekable();
+            while let Some((i, c)) = chars.next() {
+                if c == '\'' {
+                    if chars.peek().map(|(_, c)| *c) == Some('\'') {
+                        chars.next();
+                    } else {
+                        end = Some(i);
+                        break;
+                    }
+                }
+            }
+            match end {
+                Some(end) => {
+                    label = quoted[..end].replace("''", "'");
+                    rest = &quoted[end + 1..];
+                }
+                None => break,
+            }
+        } else {
+            match rest.find(|c: char| c == '=' || c.is_whitespace()) {
+                Some(end) => {
+                    label = rest[..end].to_owned();
+                    rest = &rest[end..];
+                }
+                None => break,
+            }
+        }
+
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if let Some(values) = rest[..end].strip_prefix('=') {
+            items.push(perfdata_item(label, values));
+        }
+        rest = rest[end..].trim_start();
+    }
+    items
+}
+
+fn perfdata_item(label: String, values: &str) -> PerfData {
+    let mut fields = values.split(';');
+    let value = fields.next().unwrap_or("");
+    let number_len = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(value.len());
+    let field = |f: Option<&str>| f.filter(|f| !f.is_empty()).map(|f| f.to_owned());
+
+    PerfData {
+        label,
+        value: value[..number_len].parse().ok(),
+        uom: value[number_len..].to_owned(),
+        warn: field(fields.next()),
+        crit: field(fields.next()),
+        min: fields.next().and_then(|f| f.parse().ok()),
+        max: fields.next().and_then(|f| f.parse().ok()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parses_perfdata() {
+        assert_eq!(
+            parse_perfdata("time=0.5s;1;2;0 'free space'=80%;20:;10:;0;100 users=3 bad"),
+            vec![
+                PerfData {
+                    label: "time".to_owned(),
+                    value: Some(0.5),
+                    uom: "s".to_owned(),
+                    warn: Some("1".to_owned()),
+                    crit: Some("2".to_owned()),
+                    min: Some(0.0),
+                    max: None,
+                },
+                PerfData {
+                    label: "free space".to_owned(),
+                    value: Some(80.0),
+                    uom: "%".to_owned(),
+                    warn: Some("20:".to_owned()),
+                    crit: Some("10:".to_owned()),
+                    min: Some(0.0),
+                    max: Some(100.0),
+                },
+                PerfData {
+                    label: "users".to_owned(),
+                    value: Some(3.0),
+                    uom: "".to_owned(),
+                    warn: None,
+                    crit: None,
+                    min: None,
+                    max: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_output_and_perfdata() {
+        let result = CheckResult::new(
+            "db-1".to_owned(),
+            Some("Disk /".to_owned()),
+            CheckState::from_exit_code(Some(2)),
+            "DISK CRITICAL - 4% free | /=96%;80;90\n/dev/sda1 almost full\nmounted rw | inodes=50%\nfiles=12\n",
+        );
+
+        assert_eq!(result.state, CheckState::Critical);
+        assert_eq!(result.output, "DISK CRITICAL - 4% free");
+        assert_eq!(result.long_output, "/dev/sda1 almost full\nmounted rw");
+        let labels: Vec<&str> = result.perfdata.iter().map(|p| p.label.as_str()).collect();
+        assert_eq!(labels, vec!["/", "inodes", "files"]);
+
+        match result.to_event() {
+            Event::AlertTrigger(at) => {
+                assert_eq!(at.payload.severity, Severity::Critical);
+                assert_eq!(
+                    at.payload.summary,
+                    "Disk / on db-1 is CRITICAL: DISK CRITICAL - 4% free"
+                );
+                assert_eq!(at.dedup_key.as_deref(), Some("nagios:db-1/Disk /"));
+            }
+            _ => panic!("expected a trigger"),
+        }
+    }
+
+    #[test]
+    fn ok_resolves() {
+        let result = CheckResult::new(
+            "db-1".to_owned(),
+            None,
+            CheckState::from_exit_code(Some(0)),
+            "PING OK",
+        );
+        match result.to_event() {
+            Event::AlertResolve(ar) => assert_eq!(ar.dedup_key, "nagios:db-1"),
+            _ => panic!("expected a resolve"),
+        }
+        assert_eq!(CheckState::from_exit_code(Some(7)), CheckState::Unknown);
+        assert_eq!(CheckState::from_exit_code(None), CheckState::Unknown);
+    }
+
+    #[test]
+    fn down_host_triggers() {
+        let result = CheckResult::new(
+            "db-1".to_owned(),
+            None,
+            CheckState::from_host_state(Some(1)),
+            "CRITICAL - Host Unreachable (10.0.0.5)",
+        );
+        match result.to_event() {
+            Event::AlertTrigger(at) => {
+                assert_eq!(at.payload.severity, Severity::Critical);
+                assert_eq!(at.dedup_key.as_deref(), Some("nagios:db-1"));
+            }
+            _ => panic!("expected a trigger"),
+        }
+        assert_eq!(CheckState::from_host_state(Some(0)), CheckState::Ok);
+        assert_eq!(CheckState::from_host_state(Some(2)), CheckState::Critical);
+        assert_eq!(CheckState::from_host_state(None), CheckState::Unknown);
+    }
+
+    #[test]
+    fn empty_command_is_an_error() {
+        let err = CheckResult::run("web-1".to_owned(), None, &[]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
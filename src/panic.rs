@@ -0,0 +1,193 @@
+use crate::eventsv2sync::*;
+use crate::types::*;
+
+use serde::Serialize;
+use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::Cell;
+use std::panic::{self, Location};
+use std::thread;
+
+thread_local! {
+    /// Set while this thread is sending an alert, so a panic while sending doesn't send
+    /// another one. Panics on other threads are still alerted.
+    static SENDING: Cell<bool> = const { Cell::new(false) };
+}
+
+pub struct PanicHookOptions {
+    /// The unique location of the affected system, preferably a hostname or FQDN.
+    pub source: String,
+
+    /// Component of the source machine that panicked, for example the agent's name.
+    pub component: Option<String>,
+
+    /// Capture a backtrace even when RUST_BACKTRACE isn't set.
+    pub force_backtrace: bool,
+}
+
+impl PanicHookOptions {
+    pub fn new(source: String) -> Self {
+        PanicHookOptions {
+            source,
+            component: None,
+            force_backtrace: true,
+        }
+    }
+}
+
+/// The custom_details sent with a panic alert.
+#[derive(Serialize)]
+pub struct PanicDetails {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backtrace: Option<String>,
+}
+
+/// Install a panic hook that sends a critical AlertTrigger for every panic, then
+/// calls the previously installed hook.
+///
+/// The alert is sent before the hook returns, so it goes out whether the process
+/// then unwinds or aborts. The dedup key comes from the panic's location, so repeated
+/// panics at the same place add to the same alert.
+pub fn install_panic_hook(events_v2: EventsV2, options: PanicHookOptions) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if !SENDING.with(|sending| sending.replace(true)) {
+            let backtrace = if options.force_backtrace {
+                Backtrace::force_capture()
+            } else {
+                Backtrace::capture()
+            };
+            let event = panic_event(
+                payload_message(info.payload()),
+                info.location(),
+                thread::current().name(),
+                match backtrace.status() {
+                    BacktraceStatus::Captured => Some(backtrace.to_string()),
+                    _ => None,
+                },
+                &options,
+            );
+
+            // Send from a fresh thread: the panicking one may be inside an async runtime,
+            // where the blocking client refuses to run. The sender is marked as sending too,
+            // so a panic inside the client isn't alerted.
+            thread::scope(|s| {
+                if let Ok(sender) = thread::Builder::new().spawn_scoped(s, || {
+                    SENDING.with(|sending| sending.set(true));
+                    events_v2.event(event)
+                }) {
+                    let _ = sender.join();
+                }
+            });
+
+            SENDING.with(|sending| sending.set(false));
+        }
+
+        previous(info);
+    }));
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_owned()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<dyn Any>".to_owned()
+    }
+}
+
+fn panic_event(
+    message: String,
+    location: Option<&Location>,
+    thread: Option<&str>,
+    backtrace: Option<String>,
+    options: &PanicHookOptions,
+) -> Event<PanicDetails> {
+    let location = location.map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+    let summary = format!(
+        "panic in thread '{}' at {}: {}",
+        thread.unwrap_or("<unnamed>"),
+        location.as_deref().unwrap_or("<unknown>"),
+        message
+    );
+    let dedup_key = format!("panic:{}", location.as_deref().unwrap_or(&message));
+
+    Event::AlertTrigger(AlertTrigger {
+        payload: AlertTriggerPayload {
+            severity: Severity::Critical,
+            summary: truncate_summary(&summary),
+            source: options.source.clone(),
+            timestamp: Some(time::OffsetDateTime::now_utc()),
+            component: options.component.clone(),
+            group: None,
+            class: Some("panic".to_owned()),
+            custom_details: Some(PanicDetails {
+                message,
+                location,
+                thread: thread.map(|t| t.to_owned()),
+                backtrace,
+            }),
+        },
+        dedup_key: Some(truncate_dedup_key(&dedup_key)),
+        images: None,
+        links: None,
+        client: Some("pagerduty-rs".to_owned()),
+        client_url: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn builds_critical_alert() {
+        let options = PanicHookOptions::new("hostname".to_owned());
+        let location = Location::caller();
+        let event = panic_event(
+            payload_message(&"index out of bounds"),
+            Some(location),
+            Some("worker-1"),
+            None,
+            &options,
+        );
+
+        let expected_location = format!(
+            "{}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        );
+        match event {
+            Event::AlertTrigger(at) => {
+                assert_eq!(at.payload.severity, Severity::Critical);
+                assert_eq!(
+                    at.payload.summary,
+                    format!(
+                        "panic in thread 'worker-1' at {}: index out of bounds",
+                        expected_location
+                    )
+                );
+                assert_eq!(at.dedup_key, Some(format!("panic:{}", expected_location)));
+                let details = at.payload.custom_details.unwrap();
+                assert_eq!(details.message, "index out of bounds");
+                assert_eq!(details.thread.as_deref(), Some("worker-1"));
+            }
+            _ => panic!("expected a trigger"),
+        }
+    }
+
+    #[test]
+    fn payload_messages() {
+        assert_eq!(payload_message(&"static str"), "static str");
+        assert_eq!(payload_message(&"owned".to_owned()), "owned");
+        assert_eq!(payload_message(&42), "Box<dyn Any>");
+    }
+}
@@ -0,0 +1,316 @@
+use crate::telemetry;
+use crate::types::*;
+
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// The priority an event is sent with when it is queued. Higher goes first.
+///
+/// Critical triggers go first, then acknowledges and resolves (so incidents close promptly),
+/// then error, warning and info triggers, and finally change events.
+pub fn event_priority<T: Serialize>(event: &Event<T>) -> u8 {
+    match event {
+        Event::AlertTrigger(at) => match at.payload.severity {
+            Severity::Critical => 5,
+            Severity::Error => 3,
+            Severity::Warning => 2,
+            Severity::Info => 1,
+        },
+        Event::AlertAcknowledge(_) | Event::AlertResolve(_) => 4,
+        Event::Change(_) => 0,
+    }
+}
+
+// Ordered so the greatest key is the next event to send:
+// highest priority first, and oldest first within a priority.
+type QueueKey = (u8, Reverse<u64>);
+
+struct QueueState<T: Serialize> {
+    entries: BTreeMap<QueueKey, Event<T>>,
+    next_seq: u64,
+
+    // For every dedup key with pending events: the priority of the newest one, and how many there are.
+    dedup_keys: HashMap<String, (u8, usize)>,
+    closed: bool,
+}
+
+impl<T: Serialize> QueueState<T> {
+    fn forget(&mut self, event: &Event<T>) {
+        if let Some(key) = event.dedup_key() {
+            if let Some((_, count)) = self.dedup_keys.get_mut(key) {
+                *count -= 1;
+                if *count == 0 {
+                    self.dedup_keys.remove(key);
+                }
+            }
+        }
+    }
+}
+
+/// A bounded queue of pending events, ordered by severity and event kind.
+///
+/// Events sharing a dedup key are never reordered relative to each other: an event is
+/// never given a higher priority than an earlier pending event with the same dedup key,
+/// so a resolve can't overtake the trigger it resolves.
+pub struct EventQueue<T: Serialize> {
+    state: Mutex<QueueState<T>>,
+    available: Condvar,
+    capacity: usize,
+}
+
+impl<T: Serialize> EventQueue<T> {
+    pub fn new(capacity: usize) -> Self {
+        EventQueue {
+            state: Mutex::new(QueueState {
+                entries: BTreeMap::new(),
+                next_seq: 0,
+                dedup_keys: HashMap::new(),
+                closed: false,
+            }),
+            available: Condvar::new(),
+            capacity,
+        }
+    }
+
+    /// Queue an event. When the queue is full, the lowest priority (and newest) event is
+    /// dropped to make room, which may be the event being pushed. The dropped event is returned.
+    pub fn push(&self, event: Event<T>) -> Option<Event<T>> {
+        let mut s = self.lock();
+
+        let mut priority = event_priority(&event);
+        if let Some(key) = event.dedup_key() {
+            if let Some((pending_priority, _)) = s.dedup_keys.get(key) {
+                priority = priority.min(*pending_priority);
+            }
+        }
+
+        if s.entries.len() >= self.capacity {
+            match s.entries.keys().next() {
+                Some((lowest, _)) if *lowest < priority => {}
+                _ => return Some(Self::dropped(event)),
+            }
+        }
+
+        if let Some(key) = event.dedup_key() {
+            let entry = s.dedup_keys.entry(key.to_owned()).or_insert((priority, 0));
+            entry.0 = priority;
+            entry.1 += 1;
+        }
+        let seq = s.next_seq;
+        s.next_seq += 1;
+        s.entries.insert((priority, Reverse(seq)), event);
+
+        let dropped = if s.entries.len() > self.capacity {
+            s.entries.pop_first().map(|(_, dropped)| {
+                s.forget(&dropped);
+                Self::dropped(dropped)
+            })
+        } else {
+            None
+        };
+
+        self.available.notify_one();
+        dropped
+    }
+
+    /// Take the next event to send, if there is one.
+    pub fn pop(&self) -> Option<Event<T>> {
+        let mut s = self.lock();
+        Self::take(&mut s)
+    }
+
+    /// Take the next event to send, waiting up to timeout for one to be pushed.
+    /// Returns None on timeout, or once the queue is closed and empty.
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<Event<T>> {
+        let deadline = Instant::now() + timeout;
+        let mut s = self.lock();
+        loop {
+            if let Some(event) = Self::take(&mut s) {
+                return Some(event);
+            }
+            let now = Instant::now();
+            if s.closed || now >= deadline {
+                return None;
+            }
+            s = match self.available.wait_timeout(s, deadline - now) {
+                Ok((s, _)) => s,
+                Err(poisoned) => poisoned.into_inner().0,
+            };
+        }
+    }
+
+    /// Take the next event to send, waiting for one to be pushed.
+    /// Returns None once the queue is closed and empty.
+    pub fn pop_wait(&self) -> Option<Event<T>> {
+        let mut s = self.lock();
+        loop {
+            if let Some(event) = Self::take(&mut s) {
+                return Some(event);
+            }
+            if s.closed {
+                return None;
+            }
+            s = match self.available.wait(s) {
+                Ok(s) => s,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+        }
+    }
+
+    /// Wake up everyone waiting on the queue. Pending events can still be popped.
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.available.notify_all();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn dropped(event: Event<T>) -> Event<T> {
+        telemetry::event_dropped(telemetry::EventLabels::of(&event));
+        event
+    }
+
+    fn take(s: &mut QueueState<T>) -> Option<Event<T>> {
+        let (_, event) = s.entries.pop_last()?;
+        s.forget(&event);
+        Some(event)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState<T>> {
+        // The state is always left consistent, so a poisoned lock is still usable.
+        match self.state.lock() {
+            Ok(s) => s,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use time::OffsetDateTime;
+
+    fn trigger(summary: &str, severity: Severity, dedup_key: Option<&str>) -> Event<()> {
+        Event::AlertTrigger(AlertTrigger {
+            payload: AlertTriggerPayload {
+                summary: summary.to_owned(),
+                source: "hostname".to_owned(),
+                timestamp: None,
+                severity,
+                component: None,
+                group: None,
+                class: None,
+                custom_details: None,
+            },
+            dedup_key: dedup_key.map(|k| k.to_owned()),
+            images: None,
+            links: None,
+            client: None,
+            client_url: None,
+        })
+    }
+
+    fn change(summary: &str) -> Event<()> {
+        Event::Change(Change {
+            payload: ChangePayload {
+                summary: summary.to_owned(),
+                timestamp: OffsetDateTime::UNIX_EPOCH,
+                source: None,
+                custom_details: None,
+            },
+            links: None,
+        })
+    }
+
+    fn resolve(dedup_key: &str) -> Event<()> {
+        Event::AlertResolve(AlertResolve {
+            dedup_key: dedup_key.to_owned(),
+        })
+    }
+
+    fn describe(event: Event<()>) -> String {
+        match event {
+            Event::Change(c) => c.payload.summary,
+            Event::AlertTrigger(at) => at.payload.summary,
+            Event::AlertAcknowledge(aa) => format!("ack {}", aa.dedup_key),
+            Event::AlertResolve(ar) => format!("resolve {}", ar.dedup_key),
+        }
+    }
+
+    fn drain(q: &EventQueue<()>) -> Vec<String> {
+        std::iter::from_fn(|| q.pop()).map(describe).collect()
+    }
+
+    #[test]
+    fn orders_by_severity_and_kind() {
+        let q = EventQueue::new(10);
+        q.push(change("change1"));
+        q.push(trigger("info1", Severity::Info, None));
+        q.push(trigger("critical1", Severity::Critical, None));
+        q.push(change("change2"));
+        q.push(trigger("warning1", Severity::Warning, None));
+        q.push(trigger("critical2", Severity::Critical, None));
+
+        assert_eq!(
+            drain(&q),
+            vec![
+                "critical1",
+                "critical2",
+                "warning1",
+                "info1",
+                "change1",
+                "change2"
+            ]
+        );
+    }
+
+    #[test]
+    fn followups_stay_behind_their_trigger() {
+        let q = EventQueue::new(10);
+        q.push(trigger("info-a", Severity::Info, Some("a")));
+        q.push(trigger("error1", Severity::Error, None));
+        q.push(resolve("a"));
+        q.push(resolve("b"));
+        q.push(trigger("info-a-again", Severity::Info, Some("a")));
+
+        assert_eq!(
+            drain(&q),
+            vec!["resolve b", "error1", "info-a", "resolve a", "info-a-again"]
+        );
+    }
+
+    #[test]
+    fn overflow_drops_lowest_priority() {
+        let q = EventQueue::new(2);
+        assert!(q.push(change("change1")).is_none());
+        assert!(q.push(trigger("info1", Severity::Info, None)).is_none());
+
+        let dropped = q.push(trigger("critical1", Severity::Critical, None));
+        assert_eq!(describe(dropped.unwrap()), "change1");
+
+        let dropped = q.push(change("change2"));
+        assert_eq!(describe(dropped.unwrap()), "change2");
+
+        assert_eq!(drain(&q), vec!["critical1", "info1"]);
+    }
+
+    #[test]
+    fn pop_timeout_and_close() {
+        let q = EventQueue::<()>::new(2);
+        assert!(q.pop_timeout(Duration::from_millis(10)).is_none());
+        q.push(change("change1"));
+        q.close();
+        assert_eq!(describe(q.pop_wait().unwrap()), "change1");
+        assert!(q.pop_wait().is_none());
+    }
+}
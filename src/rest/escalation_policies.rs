@@ -0,0 +1,387 @@
+//! Escalation policies: who gets notified about a service's incidents, and in what order.
+
+use crate::rest::types::*;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscalationTargetType {
+    /// Expanded by include[]=targets to "user".
+    #[serde(rename = "user_reference", alias = "user")]
+    User,
+    #[serde(rename = "schedule_reference", alias = "schedule")]
+    Schedule,
+}
+
+/// A user or schedule an escalation rule notifies.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct EscalationTarget {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub target_type: EscalationTargetType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html_url: Option<String>,
+}
+
+impl EscalationTarget {
+    pub fn user(id: String) -> Self {
+        EscalationTarget {
+            id,
+            target_type: EscalationTargetType::User,
+            summary: None,
+            html_url: None,
+        }
+    }
+
+    pub fn schedule(id: String) -> Self {
+        EscalationTarget {
+            id,
+            target_type: EscalationTargetType::Schedule,
+            summary: None,
+            html_url: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct EscalationRule {
+    /// None on rules yet to be created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// Minutes before an unacknowledged incident escalates to the next rule.
+    pub escalation_delay_in_minutes: u32,
+    pub targets: Vec<EscalationTarget>,
+}
+
+impl EscalationRule {
+    pub fn new(escalation_delay_in_minutes: u32, targets: Vec<EscalationTarget>) -> Self {
+        EscalationRule {
+            id: None,
+            escalation_delay_in_minutes,
+            targets,
+        }
+    }
+}
+
+/// Whether to notify users when they go on or off call.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OnCallHandoffNotifications {
+    /// Only when the policy is used by a service.
+    IfHasServices,
+    Always,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct EscalationPolicy {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    #[serde(rename = "self", default)]
+    pub self_url: Option<String>,
+
+    /// Times to loop through the rules when nobody acknowledges.
+    #[serde(default)]
+    pub num_loops: u32,
+    #[serde(default)]
+    pub on_call_handoff_notifications: Option<OnCallHandoffNotifications>,
+    #[serde(default)]
+    pub escalation_rules: Vec<EscalationRule>,
+
+    /// Expanded by include[]=services.
+    #[serde(default)]
+    pub services: Vec<Reference>,
+
+    /// Expanded by include[]=teams.
+    #[serde(default)]
+    pub teams: Vec<Reference>,
+}
+
+/// The fields of an escalation policy to create or update. Fields left None aren't sent, so an
+/// update changes only what's set.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct EscalationPolicySpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_loops: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_call_handoff_notifications: Option<OnCallHandoffNotifications>,
+
+    /// Replaces every rule; rules without an id are created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escalation_rules: Option<Vec<EscalationRule>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub teams: Option<Vec<Reference>>,
+}
+
+impl EscalationPolicySpec {
+    /// The least a new escalation policy needs.
+    pub fn new(name: String, escalation_rules: Vec<EscalationRule>) -> Self {
+        EscalationPolicySpec {
+            name: Some(name),
+            escalation_rules: Some(escalation_rules),
+            ..Default::default()
+        }
+    }
+}
+
+/// Which escalation policies to list.
+#[derive(Debug, Clone, Default)]
+pub struct EscalationPolicyFilter {
+    /// Only policies whose name contains this.
+    pub query: Option<String>,
+
+    /// Only policies that notify any of these users.
+    pub user_ids: Vec<String>,
+    pub team_ids: Vec<String>,
+
+    /// Expand references: "services", "teams" or "targets".
+    pub include: Vec<String>,
+
+    /// "name", "name:asc" or "name:desc".
+    pub sort_by: Option<String>,
+}
+
+impl EscalationPolicyFilter {
+    pub(crate) fn to_query(&self) -> Query {
+        let mut query: Query = self
+            .user_ids
+            .iter()
+            .map(|u| ("user_ids[]".to_owned(), u.clone()))
+            .collect();
+        query.extend(
+            self.team_ids
+                .iter()
+                .map(|t| ("team_ids[]".to_owned(), t.clone())),
+        );
+        query.extend(include_query(&self.include));
+        if let Some(q) = &self.query {
+            query.push(("query".to_owned(), q.clone()));
+        }
+        if let Some(sort_by) = &self.sort_by {
+            query.push(("sort_by".to_owned(), sort_by.clone()));
+        }
+        query
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct EscalationPolicyList {
+    escalation_policies: Vec<EscalationPolicy>,
+    #[serde(flatten)]
+    pagination: Pagination,
+}
+
+impl Page for EscalationPolicyList {
+    type Item = EscalationPolicy;
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn into_items(self) -> Vec<EscalationPolicy> {
+        self.escalation_policies
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct EscalationPolicyWrapper<T> {
+    escalation_policy: T,
+}
+
+#[cfg(feature = "sync")]
+impl crate::rest::restsync::RestClient {
+    /// List every escalation policy matching the filter, fetching all pages.
+    pub fn list_escalation_policies(
+        &self,
+        filter: &EscalationPolicyFilter,
+    ) -> RestResult<Vec<EscalationPolicy>> {
+        self.get_all::<EscalationPolicyList>("/escalation_policies", &filter.to_query())
+    }
+
+    /// Get an escalation policy, expanding the references in `include`.
+    pub fn get_escalation_policy(
+        &self,
+        id: &str,
+        include: &[String],
+    ) -> RestResult<EscalationPolicy> {
+        let wrapper: EscalationPolicyWrapper<EscalationPolicy> = self.get(
+            &format!("/escalation_policies/{}", Segment(id)),
+            &include_query(include),
+        )?;
+        Ok(wrapper.escalation_policy)
+    }
+
+    pub fn create_escalation_policy(
+        &self,
+        escalation_policy: &EscalationPolicySpec,
+    ) -> RestResult<EscalationPolicy> {
+        let wrapper: EscalationPolicyWrapper<EscalationPolicy> = self.post(
+            "/escalation_policies",
+            &EscalationPolicyWrapper { escalation_policy },
+        )?;
+        Ok(wrapper.escalation_policy)
+    }
+
+    pub fn update_escalation_policy(
+        &self,
+        id: &str,
+        escalation_policy: &EscalationPolicySpec,
+    ) -> RestResult<EscalationPolicy> {
+        let wrapper: EscalationPolicyWrapper<EscalationPolicy> = self.put(
+            &format!("/escalation_policies/{}", Segment(id)),
+            &EscalationPolicyWrapper { escalation_policy },
+        )?;
+        Ok(wrapper.escalation_policy)
+    }
+
+    pub fn delete_escalation_policy(&self, id: &str) -> RestResult<()> {
+        self.delete(&format!("/escalation_policies/{}", Segment(id)))
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::rest::restasync::RestClient {
+    /// List every escalation policy matching the filter, fetching all pages.
+    pub async fn list_escalation_policies(
+        &self,
+        filter: &EscalationPolicyFilter,
+    ) -> RestResult<Vec<EscalationPolicy>> {
+        self.get_all::<EscalationPolicyList>("/escalation_policies", &filter.to_query())
+            .await
+    }
+
+    /// Get an escalation policy, expanding the references in `include`.
+    pub async fn get_escalation_policy(
+        &self,
+        id: &str,
+        include: &[String],
+    ) -> RestResult<EscalationPolicy> {
+        let wrapper: EscalationPolicyWrapper<EscalationPolicy> = self
+            .get(
+                &format!("/escalation_policies/{}", Segment(id)),
+                &include_query(include),
+            )
+            .await?;
+        Ok(wrapper.escalation_policy)
+    }
+
+    pub async fn create_escalation_policy(
+        &self,
+        escalation_policy: &EscalationPolicySpec,
+    ) -> RestResult<EscalationPolicy> {
+        let wrapper: EscalationPolicyWrapper<EscalationPolicy> = self
+            .post(
+                "/escalation_policies",
+                &EscalationPolicyWrapper { escalation_policy },
+            )
+            .await?;
+        Ok(wrapper.escalation_policy)
+    }
+
+    pub async fn update_escalation_policy(
+        &self,
+        id: &str,
+        escalation_policy: &EscalationPolicySpec,
+    ) -> RestResult<EscalationPolicy> {
+        let wrapper: EscalationPolicyWrapper<EscalationPolicy> = self
+            .put(
+                &format!("/escalation_policies/{}", Segment(id)),
+                &EscalationPolicyWrapper { escalation_policy },
+            )
+            .await?;
+        Ok(wrapper.escalation_policy)
+    }
+
+    pub async fn delete_escalation_policy(&self, id: &str) -> RestResult<()> {
+        self.delete(&format!("/escalation_policies/{}", Segment(id)))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn deserializes_expanded_policy() {
+        let policy: EscalationPolicy = serde_json::from_value(json!({
+            "id": "PANZZEQ",
+            "type": "escalation_policy",
+            "name": "Engineering Escalation Policy",
+            "num_loops": 2,
+            "on_call_handoff_notifications": "if_has_services",
+            "escalation_rules": [{
+                "id": "PANZZEQ",
+                "escalation_delay_in_minutes": 30,
+                "targets": [
+                    {"id": "PEYSGVF", "type": "user", "summary": "Earline Greenholt"},
+                    {"id": "PI7DH85", "type": "schedule_reference"}
+                ]
+            }],
+            "services": [{"id": "PIJ90N7", "type": "service_reference"}],
+            "teams": []
+        }))
+        .unwrap();
+
+        assert_eq!(policy.num_loops, 2);
+        assert_eq!(
+            policy.on_call_handoff_notifications,
+            Some(OnCallHandoffNotifications::IfHasServices)
+        );
+        let targets = &policy.escalation_rules[0].targets;
+        assert_eq!(targets[0].target_type, EscalationTargetType::User);
+        assert_eq!(targets[0].summary.as_deref(), Some("Earline Greenholt"));
+        assert_eq!(targets[1], EscalationTarget::schedule("PI7DH85".to_owned()));
+    }
+
+    #[test]
+    fn serializes_spec_and_filter() {
+        let mut spec = EscalationPolicySpec::new(
+            "Checkout".to_owned(),
+            vec![EscalationRule::new(
+                15,
+                vec![EscalationTarget::user("PEYSGVF".to_owned())],
+            )],
+        );
+        spec.num_loops = Some(1);
+
+        assert_eq!(
+            serde_json::to_value(&spec).unwrap(),
+            json!({
+                "name": "Checkout",
+                "num_loops": 1,
+                "escalation_rules": [{
+                    "escalation_delay_in_minutes": 15,
+                    "targets": [{"id": "PEYSGVF", "type": "user_reference"}]
+                }]
+            })
+        );
+
+        let filter = EscalationPolicyFilter {
+            user_ids: vec!["PEYSGVF".to_owned()],
+            include: vec!["targets".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.to_query(),
+            vec![
+                ("user_ids[]".to_owned(), "PEYSGVF".to_owned()),
+                ("include[]".to_owned(), "targets".to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,584 @@
+//! Incidents: list, get, create, update, merge and snooze.
+//!
+//! https://developer.pagerduty.com/api-reference/9d0b4b12e36f9-list-incidents
+
+use crate::rest::types::*;
+use crate::types::{
+    datetime_to_iso8601, iso8601_to_datetime, optional_datetime_to_iso8601,
+    optional_iso8601_to_datetime,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IncidentStatus {
+    Triggered,
+    Acknowledged,
+    Resolved,
+}
+
+impl IncidentStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Triggered => "triggered",
+            Self::Acknowledged => "acknowledged",
+            Self::Resolved => "resolved",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Urgency {
+    High,
+    Low,
+}
+
+impl Urgency {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::High => "high",
+            Self::Low => "low",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Assignment {
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub at: OffsetDateTime,
+    pub assignee: Reference,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Acknowledgement {
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub at: OffsetDateTime,
+    pub acknowledger: Reference,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct AlertCounts {
+    pub all: u64,
+    pub triggered: u64,
+    pub resolved: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Incident {
+    pub id: String,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    #[serde(rename = "self", default)]
+    pub self_url: Option<String>,
+    pub incident_number: u64,
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub created_at: OffsetDateTime,
+    #[serde(
+        default,
+        serialize_with = "optional_datetime_to_iso8601",
+        deserialize_with = "optional_iso8601_to_datetime"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+    pub status: IncidentStatus,
+    #[serde(default)]
+    pub incident_key: Option<String>,
+    pub service: Reference,
+    #[serde(default)]
+    pub assignments: Vec<Assignment>,
+    #[serde(default)]
+    pub acknowledgements: Vec<Acknowledgement>,
+    #[serde(
+        default,
+        serialize_with = "optional_datetime_to_iso8601",
+        deserialize_with = "optional_iso8601_to_datetime"
+    )]
+    pub last_status_change_at: Option<OffsetDateTime>,
+    #[serde(default)]
+    pub last_status_change_by: Option<Reference>,
+    #[serde(default)]
+    pub escalation_policy: Option<Reference>,
+    #[serde(default)]
+    pub teams: Vec<Reference>,
+    #[serde(default)]
+    pub priority: Option<Reference>,
+    pub urgency: Urgency,
+    #[serde(default)]
+    pub alert_counts: Option<AlertCounts>,
+    #[serde(default)]
+    pub resolve_reason: Option<Value>,
+}
+
+/// Which incidents to list. The default lists PagerDuty's default: open and recently
+/// resolved incidents from the last 30 days.
+#[derive(Debug, Clone, Default)]
+pub struct IncidentFilter {
+    pub statuses: Vec<IncidentStatus>,
+    pub urgencies: Vec<Urgency>,
+    pub service_ids: Vec<String>,
+    pub team_ids: Vec<String>,
+    pub user_ids: Vec<String>,
+    pub incident_key: Option<String>,
+    pub since: Option<OffsetDateTime>,
+    pub until: Option<OffsetDateTime>,
+
+    /// List incidents from all time, ignoring since and until.
+    pub all_dates: bool,
+
+    /// e.g. "created_at:desc"
+    pub sort_by: Vec<String>,
+}
+
+impl IncidentFilter {
+    pub(crate) fn to_query(&self) -> Query {
+        let mut query = Query::new();
+        let mut each = |name: &str, values: Vec<&str>| {
+            for value in values {
+                query.push((name.to_owned(), value.to_owned()));
+            }
+        };
+        each(
+            "statuses[]",
+            self.statuses.iter().map(|s| s.as_str()).collect(),
+        );
+        each(
+            "urgencies[]",
+            self.urgencies.iter().map(|u| u.as_str()).collect(),
+        );
+        each(
+            "service_ids[]",
+            self.service_ids.iter().map(|s| s.as_str()).collect(),
+        );
+        each(
+            "team_ids[]",
+            self.team_ids.iter().map(|s| s.as_str()).collect(),
+        );
+        each(
+            "user_ids[]",
+            self.user_ids.iter().map(|s| s.as_str()).collect(),
+        );
+        each(
+            "sort_by[]",
+            self.sort_by.iter().map(|s| s.as_str()).collect(),
+        );
+        each(
+            "incident_key",
+            self.incident_key.iter().map(|s| s.as_str()).collect(),
+        );
+
+        query.extend(time_range_query(self.since, self.until));
+        if self.all_dates {
+            query.push(("date_range".to_owned(), "all".to_owned()));
+        }
+        query
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct IncidentBody {
+    #[serde(rename = "type")]
+    object_type: &'static str,
+    pub details: String,
+}
+
+impl IncidentBody {
+    pub fn new(details: String) -> Self {
+        IncidentBody {
+            object_type: "incident_body",
+            details,
+        }
+    }
+}
+
+/// A new incident. Creating one needs the From header (see `RestClient::with_from`).
+#[derive(Serialize, Debug, Clone)]
+pub struct NewIncident {
+    #[serde(rename = "type")]
+    object_type: &'static str,
+    pub title: String,
+    pub service: Reference,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub urgency: Option<Urgency>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incident_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<IncidentBody>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Reference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escalation_policy: Option<Reference>,
+}
+
+impl NewIncident {
+    pub fn new(title: String, service_id: &str) -> Self {
+        NewIncident {
+            object_type: "incident",
+            title,
+            service: Reference::new(service_id, "service_reference"),
+            urgency: None,
+            incident_key: None,
+            body: None,
+            priority: None,
+            escalation_policy: None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AssignmentUpdate {
+    pub assignee: Reference,
+}
+
+/// A change to one incident in a bulk update. Build one with a constructor such as
+/// `IncidentUpdate::acknowledge`, then set any other fields to change alongside.
+#[derive(Serialize, Debug, Clone)]
+pub struct IncidentUpdate {
+    pub id: String,
+    #[serde(rename = "type")]
+    object_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<IncidentStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub urgency: Option<Urgency>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Reference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escalation_level: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escalation_policy: Option<Reference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignments: Option<Vec<AssignmentUpdate>>,
+}
+
+impl IncidentUpdate {
+    pub fn new(id: String) -> Self {
+        IncidentUpdate {
+            id,
+            object_type: "incident_reference",
+            status: None,
+            resolution: None,
+            title: None,
+            urgency: None,
+            priority: None,
+            escalation_level: None,
+            escalation_policy: None,
+            assignments: None,
+        }
+    }
+
+    pub fn acknowledge(id: String) -> Self {
+        let mut update = Self::new(id);
+        update.status = Some(IncidentStatus::Acknowledged);
+        update
+    }
+
+    pub fn resolve(id: String, resolution: Option<String>) -> Self {
+        let mut update = Self::new(id);
+        update.status = Some(IncidentStatus::Resolved);
+        update.resolution = resolution;
+        update
+    }
+
+    /// Reassign to users (user_reference) or escalation policies (escalation_policy_reference).
+    pub fn reassign(id: String, assignees: Vec<Reference>) -> Self {
+        let mut update = Self::new(id);
+        update.assignments = Some(
+            assignees
+                .into_iter()
+                .map(|assignee| AssignmentUpdate { assignee })
+                .collect(),
+        );
+        update
+    }
+
+    pub fn escalate(id: String, escalation_level: u32) -> Self {
+        let mut update = Self::new(id);
+        update.escalation_level = Some(escalation_level);
+        update
+    }
+
+    pub fn change_priority(id: String, priority_id: &str) -> Self {
+        let mut update = Self::new(id);
+        update.priority = Some(Reference::new(priority_id, "priority_reference"));
+        update
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct IncidentList {
+    incidents: Vec<Incident>,
+    #[serde(flatten)]
+    pagination: Pagination,
+}
+
+impl Page for IncidentList {
+    type Item = Incident;
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn into_items(self) -> Vec<Incident> {
+        self.incidents
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct IncidentWrapper<T> {
+    incident: T,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct IncidentsWrapper<T> {
+    incidents: Vec<T>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct MergeRequest {
+    source_incidents: Vec<Reference>,
+}
+
+impl MergeRequest {
+    fn new(source_ids: &[&str]) -> Self {
+        MergeRequest {
+            source_incidents: source_ids
+                .iter()
+                .map(|id| Reference::new(id, "incident_reference"))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct SnoozeRequest {
+    /// Seconds
+    duration: u64,
+}
+
+#[cfg(feature = "sync")]
+impl crate::rest::restsync::RestClient {
+    /// List every incident matching the filter, fetching all pages.
+    pub fn list_incidents(&self, filter: &IncidentFilter) -> RestResult<Vec<Incident>> {
+        self.get_all::<IncidentList>("/incidents", &filter.to_query())
+    }
+
+    pub fn get_incident(&self, id: &str) -> RestResult<Incident> {
+        let wrapper: IncidentWrapper<Incident> =
+            self.get(&format!("/incidents/{}", Segment(id)), &Query::new())?;
+        Ok(wrapper.incident)
+    }
+
+    pub fn create_incident(&self, incident: &NewIncident) -> RestResult<Incident> {
+        let wrapper: IncidentWrapper<Incident> =
+            self.post("/incidents", &IncidentWrapper { incident })?;
+        Ok(wrapper.incident)
+    }
+
+    /// Update up to 250 incidents at once: acknowledge, resolve, reassign, escalate,
+    /// change priority and so on.
+    pub fn update_incidents(&self, updates: &[IncidentUpdate]) -> RestResult<Vec<Incident>> {
+        let wrapper: IncidentsWrapper<Incident> = self.put(
+            "/incidents",
+            &IncidentsWrapper {
+                incidents: updates.to_vec(),
+            },
+        )?;
+        Ok(wrapper.incidents)
+    }
+
+    /// Merge the source incidents into the target, resolving the sources.
+    pub fn merge_incidents(&self, target_id: &str, source_ids: &[&str]) -> RestResult<Incident> {
+        let wrapper: IncidentWrapper<Incident> = self.put(
+            &format!("/incidents/{}/merge", Segment(target_id)),
+            &MergeRequest::new(source_ids),
+        )?;
+        Ok(wrapper.incident)
+    }
+
+    /// Snooze an acknowledged incident, re-triggering it after the duration.
+    pub fn snooze_incident(&self, id: &str, duration: std::time::Duration) -> RestResult<Incident> {
+        let wrapper: IncidentWrapper<Incident> = self.post(
+            &format!("/incidents/{}/snooze", Segment(id)),
+            &SnoozeRequest {
+                duration: duration.as_secs(),
+            },
+        )?;
+        Ok(wrapper.incident)
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::rest::restasync::RestClient {
+    /// List every incident matching the filter, fetching all pages.
+    pub async fn list_incidents(&self, filter: &IncidentFilter) -> RestResult<Vec<Incident>> {
+        self.get_all::<IncidentList>("/incidents", &filter.to_query())
+            .await
+    }
+
+    pub async fn get_incident(&self, id: &str) -> RestResult<Incident> {
+        let wrapper: IncidentWrapper<Incident> = self
+            .get(&format!("/incidents/{}", Segment(id)), &Query::new())
+            .await?;
+        Ok(wrapper.incident)
+    }
+
+    pub async fn create_incident(&self, incident: &NewIncident) -> RestResult<Incident> {
+        let wrapper: IncidentWrapper<Incident> = self
+            .post("/incidents", &IncidentWrapper { incident })
+            .await?;
+        Ok(wrapper.incident)
+    }
+
+    /// Update up to 250 incidents at once: acknowledge, resolve, reassign, escalate,
+    /// change priority and so on.
+    pub async fn update_incidents(&self, updates: &[IncidentUpdate]) -> RestResult<Vec<Incident>> {
+        let wrapper: IncidentsWrapper<Incident> = self
+            .put(
+                "/incidents",
+                &IncidentsWrapper {
+                    incidents: updates.to_vec(),
+                },
+            )
+            .await?;
+        Ok(wrapper.incidents)
+    }
+
+    /// Merge the source incidents into the target, resolving the sources.
+    pub async fn merge_incidents(
+        &self,
+        target_id: &str,
+        source_ids: &[&str],
+    ) -> RestResult<Incident> {
+        let wrapper: IncidentWrapper<Incident> = self
+            .put(
+                &format!("/incidents/{}/merge", Segment(target_id)),
+                &MergeRequest::new(source_ids),
+            )
+            .await?;
+        Ok(wrapper.incident)
+    }
+
+    /// Snooze an acknowledged incident, re-triggering it after the duration.
+    pub async fn snooze_incident(
+        &self,
+        id: &str,
+        duration: std::time::Duration,
+    ) -> RestResult<Incident> {
+        let wrapper: IncidentWrapper<Incident> = self
+            .post(
+                &format!("/incidents/{}/snooze", Segment(id)),
+                &SnoozeRequest {
+                    duration: duration.as_secs(),
+                },
+            )
+            .await?;
+        Ok(wrapper.incident)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use time::macros::datetime;
+
+    #[test]
+    fn filter_query() {
+        let filter = IncidentFilter {
+            statuses: vec![IncidentStatus::Triggered, IncidentStatus::Acknowledged],
+            urgencies: vec![Urgency::High],
+            service_ids: vec!["PSERVICE".to_owned()],
+            since: Some(datetime!(2021-05-30 00:00:00 UTC)),
+            ..Default::default()
+        };
+        let query = filter.to_query();
+        let query: Vec<(&str, &str)> = query
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(
+            query,
+            vec![
+                ("statuses[]", "triggered"),
+                ("statuses[]", "acknowledged"),
+                ("urgencies[]", "high"),
+                ("service_ids[]", "PSERVICE"),
+                ("since", "2021-05-30T00:00:00Z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn deserializes_incident() {
+        let incident: Incident = serde_json::from_str(
+            r#"{
+                "id": "PT4KHLK",
+                "type": "incident",
+                "summary": "[#1234] The server is on fire.",
+                "self": "https://api.pagerduty.com/incidents/PT4KHLK",
+                "html_url": "https://subdomain.pagerduty.com/incidents/PT4KHLK",
+                "incident_number": 1234,
+                "title": "The server is on fire.",
+                "created_at": "2015-10-06T21:30:42Z",
+                "updated_at": "2015-10-06T21:40:23Z",
+                "status": "acknowledged",
+                "incident_key": "baf7cf21b1da41b4b0221008339ff357",
+                "service": {"id": "PIJ90N7", "type": "service_reference", "summary": "My Mail Service"},
+                "assignments": [{"at": "2015-11-10T00:31:52Z", "assignee": {"id": "PXPGF42", "type": "user_reference"}}],
+                "acknowledgements": [{"at": "2015-11-10T00:32:52Z", "acknowledger": {"id": "PXPGF42", "type": "user_reference"}}],
+                "last_status_change_at": "2015-10-06T21:38:23Z",
+                "escalation_policy": {"id": "PT20YPA", "type": "escalation_policy_reference"},
+                "teams": [{"id": "PQ9K7I8", "type": "team_reference"}],
+                "priority": null,
+                "urgency": "high",
+                "resolve_reason": null,
+                "alert_counts": {"all": 2, "triggered": 1, "resolved": 1}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(incident.incident_number, 1234);
+        assert_eq!(incident.status, IncidentStatus::Acknowledged);
+        assert_eq!(incident.created_at, datetime!(2015-10-06 21:30:42 UTC));
+        assert_eq!(incident.assignments[0].assignee.id, "PXPGF42");
+        assert_eq!(incident.alert_counts.unwrap().triggered, 1);
+    }
+
+    #[test]
+    fn serializes_updates() {
+        assert_eq!(
+            serde_json::to_value(IncidentUpdate::resolve(
+                "PT4KHLK".to_owned(),
+                Some("Fixed".to_owned())
+            ))
+            .unwrap(),
+            serde_json::json!({
+                "id": "PT4KHLK",
+                "type": "incident_reference",
+                "status": "resolved",
+                "resolution": "Fixed"
+            })
+        );
+    }
+}
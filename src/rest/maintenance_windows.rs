@@ -0,0 +1,604 @@
+//! Maintenance windows, during which services don't page.
+//!
+//! With feature `sync`, `MaintenanceGuard` holds a window open for as long as, say, a deploy
+//! runs, and ends it when finished or dropped. With feature `async`,
+//! `guardasync::MaintenanceGuard` does the same, except that it can't end the window when
+//! dropped, since that would mean awaiting in Drop.
+//!
+//! Either way the window has a fixed end, so the guard must be extended by hand while work
+//! runs past it; nothing extends it in the background.
+
+use crate::rest::types::*;
+use crate::types::{
+    datetime_to_iso8601, iso8601_to_datetime, optional_datetime_to_iso8601, truncate_summary,
+    Change, ChangePayload, Event, Link,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct MaintenanceWindow {
+    pub id: String,
+    #[serde(default)]
+    pub sequence_number: Option<u64>,
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub start_time: OffsetDateTime,
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub end_time: OffsetDateTime,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    #[serde(rename = "self", default)]
+    pub self_url: Option<String>,
+    #[serde(default)]
+    pub services: Vec<Reference>,
+    #[serde(default)]
+    pub teams: Vec<Reference>,
+    #[serde(default)]
+    pub created_by: Option<Reference>,
+}
+
+/// The fields of a maintenance window to create or update. Fields left None aren't sent, so an
+/// update changes only what's set.
+#[derive(Serialize, Debug, Clone)]
+pub struct MaintenanceWindowSpec {
+    #[serde(rename = "type")]
+    object_type: &'static str,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "optional_datetime_to_iso8601"
+    )]
+    pub start_time: Option<OffsetDateTime>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "optional_datetime_to_iso8601"
+    )]
+    pub end_time: Option<OffsetDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub services: Option<Vec<Reference>>,
+}
+
+impl Default for MaintenanceWindowSpec {
+    fn default() -> Self {
+        MaintenanceWindowSpec {
+            object_type: "maintenance_window",
+            start_time: None,
+            end_time: None,
+            description: None,
+            services: None,
+        }
+    }
+}
+
+impl MaintenanceWindowSpec {
+    /// The least a new maintenance window needs.
+    pub fn new(start_time: OffsetDateTime, end_time: OffsetDateTime, service_ids: &[&str]) -> Self {
+        MaintenanceWindowSpec {
+            start_time: Some(start_time),
+            end_time: Some(end_time),
+            services: Some(
+                service_ids
+                    .iter()
+                    .map(|id| Reference::new(id, "service_reference"))
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaintenanceWindowState {
+    Past,
+    Future,
+    Ongoing,
+
+    /// Future and ongoing.
+    Open,
+    All,
+}
+
+impl MaintenanceWindowState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Past => "past",
+            Self::Future => "future",
+            Self::Ongoing => "ongoing",
+            Self::Open => "open",
+            Self::All => "all",
+        }
+    }
+}
+
+/// Which maintenance windows to list.
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceWindowFilter {
+    /// Only windows whose description contains this.
+    pub query: Option<String>,
+    pub service_ids: Vec<String>,
+    pub team_ids: Vec<String>,
+
+    /// Expand references: "teams", "services" or "users".
+    pub include: Vec<String>,
+
+    /// None for all windows.
+    pub state: Option<MaintenanceWindowState>,
+}
+
+impl MaintenanceWindowFilter {
+    pub(crate) fn to_query(&self) -> Query {
+        let mut query = Query::new();
+        for (name, ids) in [
+            ("service_ids[]", &self.service_ids),
+            ("team_ids[]", &self.team_ids),
+        ] {
+            query.extend(ids.iter().map(|id| (name.to_owned(), id.clone())));
+        }
+        query.extend(include_query(&self.include));
+        if let Some(q) = &self.query {
+            query.push(("query".to_owned(), q.clone()));
+        }
+        if let Some(state) = self.state {
+            query.push(("filter".to_owned(), state.as_str().to_owned()));
+        }
+        query
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct MaintenanceWindowList {
+    maintenance_windows: Vec<MaintenanceWindow>,
+    #[serde(flatten)]
+    pagination: Pagination,
+}
+
+impl Page for MaintenanceWindowList {
+    type Item = MaintenanceWindow;
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn into_items(self) -> Vec<MaintenanceWindow> {
+        self.maintenance_windows
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct MaintenanceWindowWrapper<T> {
+    maintenance_window: T,
+}
+
+#[cfg(feature = "sync")]
+impl crate::rest::restsync::RestClient {
+    /// List every maintenance window matching the filter, fetching all pages.
+    pub fn list_maintenance_windows(
+        &self,
+        filter: &MaintenanceWindowFilter,
+    ) -> RestResult<Vec<MaintenanceWindow>> {
+        self.get_all::<MaintenanceWindowList>("/maintenance_windows", &filter.to_query())
+    }
+
+    pub fn get_maintenance_window(&self, id: &str) -> RestResult<MaintenanceWindow> {
+        let wrapper: MaintenanceWindowWrapper<MaintenanceWindow> = self.get(
+            &format!("/maintenance_windows/{}", Segment(id)),
+            &Query::new(),
+        )?;
+        Ok(wrapper.maintenance_window)
+    }
+
+    pub fn create_maintenance_window(
+        &self,
+        maintenance_window: &MaintenanceWindowSpec,
+    ) -> RestResult<MaintenanceWindow> {
+        let wrapper: MaintenanceWindowWrapper<MaintenanceWindow> = self.post(
+            "/maintenance_windows",
+            &MaintenanceWindowWrapper { maintenance_window },
+        )?;
+        Ok(wrapper.maintenance_window)
+    }
+
+    pub fn update_maintenance_window(
+        &self,
+        id: &str,
+        maintenance_window: &MaintenanceWindowSpec,
+    ) -> RestResult<MaintenanceWindow> {
+        let wrapper: MaintenanceWindowWrapper<MaintenanceWindow> = self.put(
+            &format!("/maintenance_windows/{}", Segment(id)),
+            &MaintenanceWindowWrapper { maintenance_window },
+        )?;
+        Ok(wrapper.maintenance_window)
+    }
+
+    /// Delete a future window, or end an ongoing one now. Past windows can't be deleted.
+    pub fn delete_maintenance_window(&self, id: &str) -> RestResult<()> {
+        self.delete(&format!("/maintenance_windows/{}", Segment(id)))
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::rest::restasync::RestClient {
+    /// List every maintenance window matching the filter, fetching all pages.
+    pub async fn list_maintenance_windows(
+        &self,
+        filter: &MaintenanceWindowFilter,
+    ) -> RestResult<Vec<MaintenanceWindow>> {
+        self.get_all::<MaintenanceWindowList>("/maintenance_windows", &filter.to_query())
+            .await
+    }
+
+    pub async fn get_maintenance_window(&self, id: &str) -> RestResult<MaintenanceWindow> {
+        let wrapper: MaintenanceWindowWrapper<MaintenanceWindow> = self
+            .get(
+                &format!("/maintenance_windows/{}", Segment(id)),
+                &Query::new(),
+            )
+            .await?;
+        Ok(wrapper.maintenance_window)
+    }
+
+    pub async fn create_maintenance_window(
+        &self,
+        maintenance_window: &MaintenanceWindowSpec,
+    ) -> RestResult<MaintenanceWindow> {
+        let wrapper: MaintenanceWindowWrapper<MaintenanceWindow> = self
+            .post(
+                "/maintenance_windows",
+                &MaintenanceWindowWrapper { maintenance_window },
+            )
+            .await?;
+        Ok(wrapper.maintenance_window)
+    }
+
+    pub async fn update_maintenance_window(
+        &self,
+        id: &str,
+        maintenance_window: &MaintenanceWindowSpec,
+    ) -> RestResult<MaintenanceWindow> {
+        let wrapper: MaintenanceWindowWrapper<MaintenanceWindow> = self
+            .put(
+                &format!("/maintenance_windows/{}", Segment(id)),
+                &MaintenanceWindowWrapper { maintenance_window },
+            )
+            .await?;
+        Ok(wrapper.maintenance_window)
+    }
+
+    /// Delete a future window, or end an ongoing one now. Past windows can't be deleted.
+    pub async fn delete_maintenance_window(&self, id: &str) -> RestResult<()> {
+        self.delete(&format!("/maintenance_windows/{}", Segment(id)))
+            .await
+    }
+}
+
+/// The Change event a guard sends when its window starts or ends.
+fn change_event(window: &MaintenanceWindow, what: &str) -> Event<Value> {
+    let summary = match &window.description {
+        Some(description) => format!("Maintenance {}: {}", what, description),
+        None => format!("Maintenance {}", what),
+    };
+    let services: Vec<&str> = window.services.iter().map(|s| s.id.as_str()).collect();
+    Event::Change(Change {
+        payload: ChangePayload {
+            summary: truncate_summary(&summary),
+            timestamp: OffsetDateTime::now_utc(),
+            source: None,
+            custom_details: Some(json!({
+                "maintenance_window": window.id,
+                "services": services,
+                "end_time": window.end_time.unix_timestamp(),
+            })),
+        },
+        links: window.html_url.as_ref().map(|href| {
+            vec![Link {
+                href: href.clone(),
+                text: Some("Maintenance window".to_owned()),
+            }]
+        }),
+    })
+}
+
+#[cfg(feature = "sync")]
+pub use guard::*;
+
+#[cfg(feature = "sync")]
+mod guard {
+    use super::*;
+    use crate::eventsv2sync::{EventsV2, EventsV2Error};
+    use crate::rest::restsync::RestClient;
+
+    use std::error::Error;
+    use std::fmt::{Display, Formatter, Result as FmtResult};
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    pub enum MaintenanceError {
+        RestError(RestError),
+        EventsV2Error(EventsV2Error),
+    }
+
+    impl Error for MaintenanceError {}
+    impl Display for MaintenanceError {
+        fn fmt(&self, f: &mut Formatter) -> FmtResult {
+            match self {
+                Self::RestError(e) => write!(f, "RestError: {}", e),
+                Self::EventsV2Error(e) => write!(f, "EventsV2Error: {}", e),
+            }
+        }
+    }
+    impl From<RestError> for MaintenanceError {
+        fn from(err: RestError) -> Self {
+            Self::RestError(err)
+        }
+    }
+    impl From<EventsV2Error> for MaintenanceError {
+        fn from(err: EventsV2Error) -> Self {
+            Self::EventsV2Error(err)
+        }
+    }
+
+    /// A maintenance window that lasts as long as the guard: it starts when the guard is made
+    /// and is deleted, ending it, when the guard is finished or dropped.
+    ///
+    /// The window is created with a fixed end, so a process that dies without dropping the
+    /// guard silences paging only until then. Nothing moves that end automatically: call
+    /// `extend` while work goes on, or paging resumes at the original end.
+    pub struct MaintenanceGuard<'a> {
+        client: &'a RestClient,
+        window: MaintenanceWindow,
+
+        /// Where to send a Change event at the start and end, if anywhere.
+        change_events: Option<&'a EventsV2>,
+        ended: bool,
+    }
+
+    impl<'a> MaintenanceGuard<'a> {
+        /// Put the services in maintenance from now until `duration` from now.
+        ///
+        /// The window is what matters, so failing to send the optional Change event doesn't
+        /// fail the start: its error is returned alongside the guard instead.
+        pub fn start(
+            client: &'a RestClient,
+            service_ids: &[&str],
+            duration: Duration,
+            description: String,
+            change_events: Option<&'a EventsV2>,
+        ) -> RestResult<(Self, Option<EventsV2Error>)> {
+            let now = OffsetDateTime::now_utc();
+            let mut spec = MaintenanceWindowSpec::new(now, now + duration, service_ids);
+            spec.description = Some(description);
+            let window = client.create_maintenance_window(&spec)?;
+
+            let guard = MaintenanceGuard {
+                client,
+                window,
+                change_events,
+                ended: false,
+            };
+            let change_error = guard.change("started").err();
+            Ok((guard, change_error))
+        }
+
+        pub fn window(&self) -> &MaintenanceWindow {
+            &self.window
+        }
+
+        /// Move the end of the window to `duration` from now.
+        pub fn extend(&mut self, duration: Duration) -> RestResult<()> {
+            let spec = MaintenanceWindowSpec {
+                end_time: Some(OffsetDateTime::now_utc() + duration),
+                ..Default::default()
+            };
+            self.window = self
+                .client
+                .update_maintenance_window(&self.window.id, &spec)?;
+            Ok(())
+        }
+
+        /// End the window now, reporting any error that dropping the guard would ignore.
+        /// If the window couldn't be deleted, dropping the guard tries once more.
+        pub fn finish(mut self) -> Result<(), MaintenanceError> {
+            self.end()
+        }
+
+        fn end(&mut self) -> Result<(), MaintenanceError> {
+            self.client.delete_maintenance_window(&self.window.id)?;
+            self.ended = true;
+            Ok(self.change("ended")?)
+        }
+
+        fn change(&self, what: &str) -> Result<(), EventsV2Error> {
+            match self.change_events {
+                Some(events_v2) => events_v2.event(change_event(&self.window, what)),
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl Drop for MaintenanceGuard<'_> {
+        fn drop(&mut self) {
+            if !self.ended {
+                let _ = self.end();
+            }
+        }
+    }
+}
+
+/// A `MaintenanceGuard` for the async client.
+///
+/// Drop can't await, so dropping the guard leaves the window in place until its end time.
+/// Call `finish` to end it early.
+#[cfg(feature = "async")]
+pub mod guardasync {
+    use super::*;
+    use crate::eventsv2async::{EventsV2, EventsV2Error};
+    use crate::rest::restasync::RestClient;
+
+    use std::error::Error;
+    use std::fmt::{Display, Formatter, Result as FmtResult};
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    pub enum MaintenanceError {
+        RestError(RestError),
+        EventsV2Error(EventsV2Error),
+    }
+
+    impl Error for MaintenanceError {}
+    impl Display for MaintenanceError {
+        fn fmt(&self, f: &mut Formatter) -> FmtResult {
+            match self {
+                Self::RestError(e) => write!(f, "RestError: {}", e),
+                Self::EventsV2Error(e) => write!(f, "EventsV2Error: {}", e),
+            }
+        }
+    }
+    impl From<RestError> for MaintenanceError {
+        fn from(err: RestError) -> Self {
+            Self::RestError(err)
+        }
+    }
+    impl From<EventsV2Error> for MaintenanceError {
+        fn from(err: EventsV2Error) -> Self {
+            Self::EventsV2Error(err)
+        }
+    }
+
+    /// A maintenance window that starts when the guard is made and is deleted, ending it,
+    /// when the guard is finished. Call `extend` while work runs past the window's end.
+    #[must_use = "dropping the guard leaves the window open until its end time; call finish"]
+    pub struct MaintenanceGuard<'a> {
+        client: &'a RestClient,
+        window: MaintenanceWindow,
+
+        /// Where to send a Change event at the start and end, if anywhere.
+        change_events: Option<&'a EventsV2>,
+        ended: bool,
+    }
+
+    impl<'a> MaintenanceGuard<'a> {
+        /// Put the services in maintenance from now until `duration` from now.
+        ///
+        /// The window is what matters, so failing to send the optional Change event doesn't
+        /// fail the start: its error is returned alongside the guard instead.
+        pub async fn start(
+            client: &'a RestClient,
+            service_ids: &[&str],
+            duration: Duration,
+            description: String,
+            change_events: Option<&'a EventsV2>,
+        ) -> RestResult<(Self, Option<EventsV2Error>)> {
+            let now = OffsetDateTime::now_utc();
+            let mut spec = MaintenanceWindowSpec::new(now, now + duration, service_ids);
+            spec.description = Some(description);
+            let window = client.create_maintenance_window(&spec).await?;
+
+            let guard = MaintenanceGuard {
+                client,
+                window,
+                change_events,
+                ended: false,
+            };
+            let change_error = guard.change("started").await.err();
+            Ok((guard, change_error))
+        }
+
+        pub fn window(&self) -> &MaintenanceWindow {
+            &self.window
+        }
+
+        /// Move the end of the window to `duration` from now.
+        pub async fn extend(&mut self, duration: Duration) -> RestResult<()> {
+            let spec = MaintenanceWindowSpec {
+                end_time: Some(OffsetDateTime::now_utc() + duration),
+                ..Default::default()
+            };
+            self.window = self
+                .client
+                .update_maintenance_window(&self.window.id, &spec)
+                .await?;
+            Ok(())
+        }
+
+        /// End the window now. If it couldn't be deleted, call this again to retry; once it
+        /// has been, later calls do nothing.
+        pub async fn finish(&mut self) -> Result<(), MaintenanceError> {
+            if self.ended {
+                return Ok(());
+            }
+            self.client
+                .delete_maintenance_window(&self.window.id)
+                .await?;
+            self.ended = true;
+            Ok(self.change("ended").await?)
+        }
+
+        async fn change(&self, what: &str) -> Result<(), EventsV2Error> {
+            match self.change_events {
+                Some(events_v2) => events_v2.event(change_event(&self.window, what)).await,
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use time::macros::datetime;
+
+    #[test]
+    fn serializes_spec_and_filter() {
+        let spec = MaintenanceWindowSpec::new(
+            datetime!(2021-06-01 09:00:00 UTC),
+            datetime!(2021-06-01 10:00:00 UTC),
+            &["PIJ90N7"],
+        );
+        assert_eq!(
+            serde_json::to_value(&spec).unwrap(),
+            json!({
+                "type": "maintenance_window",
+                "start_time": "2021-06-01T09:00:00Z",
+                "end_time": "2021-06-01T10:00:00Z",
+                "services": [{"id": "PIJ90N7", "type": "service_reference"}]
+            })
+        );
+
+        let update = MaintenanceWindowSpec {
+            description: Some("Longer".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            json!({"type": "maintenance_window", "description": "Longer"})
+        );
+
+        let filter = MaintenanceWindowFilter {
+            service_ids: vec!["PIJ90N7".to_owned()],
+            state: Some(MaintenanceWindowState::Ongoing),
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.to_query(),
+            vec![
+                ("service_ids[]".to_owned(), "PIJ90N7".to_owned()),
+                ("filter".to_owned(), "ongoing".to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,21 @@
+//! The PagerDuty REST API v2 (api.pagerduty.com).
+//!
+//! `RestClient` comes in the same two flavors as `EventsV2`: `restasync` with feature `async`
+//! and `restsync` with feature `sync`. Types shared by both are in `types`.
+
+pub mod escalation_policies;
+pub mod incidents;
+pub mod maintenance_windows;
+pub mod orchestrations;
+pub mod schedules;
+pub mod services;
+pub mod teams;
+pub mod timeline;
+pub mod types;
+pub mod users;
+
+#[cfg(feature = "sync")]
+pub mod restsync;
+
+#[cfg(feature = "async")]
+pub mod restasync;
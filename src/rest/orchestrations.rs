@@ -0,0 +1,733 @@
+//! Event Orchestrations: rules that route, enrich and act on events as they arrive.
+//!
+//! An orchestration's events go through its global path, then its router path, which sends
+//! them to a service's path, or to the unrouted path when no router rule matches.
+
+use crate::rest::types::*;
+use crate::types::{optional_datetime_to_iso8601, optional_iso8601_to_datetime, Severity};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct OrchestrationIntegrationParameters {
+    /// Send events here, e.g. with `EventsV2::new`, to go through the orchestration.
+    pub routing_key: String,
+    #[serde(rename = "type")]
+    pub integration_type: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct OrchestrationIntegration {
+    pub id: String,
+    pub parameters: OrchestrationIntegrationParameters,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Orchestration {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(rename = "self", default)]
+    pub self_url: Option<String>,
+    #[serde(default)]
+    pub team: Option<Reference>,
+    #[serde(default)]
+    pub integrations: Vec<OrchestrationIntegration>,
+
+    /// How many router rules there are.
+    #[serde(default)]
+    pub routes: u32,
+    #[serde(
+        default,
+        serialize_with = "optional_datetime_to_iso8601",
+        deserialize_with = "optional_iso8601_to_datetime"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(
+        default,
+        serialize_with = "optional_datetime_to_iso8601",
+        deserialize_with = "optional_iso8601_to_datetime"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+/// A PagerDuty Condition Language expression, e.g. `event.summary matches part 'timeout'`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub expression: String,
+}
+
+impl Condition {
+    pub fn new(expression: String) -> Self {
+        Condition { expression }
+    }
+}
+
+/// Capture part of the event into a variable for later actions, as `{{variables.name}}`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub name: String,
+
+    /// The event field to read, e.g. "event.summary".
+    pub path: String,
+
+    /// Always "regex".
+    #[serde(rename = "type")]
+    pub variable_type: String,
+
+    /// The regex; its first capture group, or the whole match, is the value.
+    pub value: String,
+}
+
+impl Variable {
+    pub fn regex(name: String, path: String, regex: String) -> Self {
+        Variable {
+            name,
+            path,
+            variable_type: "regex".to_owned(),
+            value: regex,
+        }
+    }
+}
+
+/// Overwrite an event field, with either a regex match of another field or a template.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Extraction {
+    /// The field to write, e.g. "event.summary".
+    pub target: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+
+    /// The field `regex` reads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+
+    /// e.g. "{{variables.host}} is down"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+impl Extraction {
+    pub fn regex(target: String, source: String, regex: String) -> Self {
+        Extraction {
+            target,
+            regex: Some(regex),
+            source: Some(source),
+            template: None,
+        }
+    }
+
+    pub fn template(target: String, template: String) -> Self {
+        Extraction {
+            target,
+            regex: None,
+            source: None,
+            template: Some(template),
+        }
+    }
+}
+
+/// What a rule does to the events it matches. Which actions apply depends on the path: router
+/// rules only `route_to`, for example. Actions left unset aren't sent.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct RuleActions {
+    /// Router rules: the service ID, or "unrouted". Other paths: the ID of the rule set to
+    /// continue in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+
+    /// The priority ID, which the REST API lists under /priorities.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+
+    /// A note added to the incident.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotate: Option<String>,
+
+    /// Make an alert but no incident.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suppress: Option<bool>,
+
+    /// Seconds to hold the alert before making an incident, in case a resolve follows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspend: Option<u64>,
+
+    /// "trigger" or "resolve".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_action: Option<String>,
+
+    /// Global and router paths only: drop the event entirely.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drop_event: Option<bool>,
+
+    /// Global path only: the escalation policy ID to page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escalation_policy: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<Variable>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extractions: Vec<Extraction>,
+
+    /// Actions without a typed field, such as automation actions.
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Rule {
+    /// None on rules yet to be created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub disabled: bool,
+
+    /// Any one matching is enough; none at all always matches.
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    pub actions: RuleActions,
+}
+
+impl Rule {
+    pub fn new(label: String, conditions: Vec<Condition>, actions: RuleActions) -> Self {
+        Rule {
+            id: None,
+            label: Some(label),
+            disabled: false,
+            conditions,
+            actions,
+        }
+    }
+}
+
+/// Rules evaluated in order until one matches. Every path has a set with the ID "start".
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RuleSet {
+    pub id: String,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+/// What happens to events no rule matches.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct CatchAll {
+    pub actions: RuleActions,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrchestrationPathType {
+    Global,
+    Router,
+    Unrouted,
+    Service,
+}
+
+/// The rules of one of an orchestration's paths, or of a service.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct OrchestrationPath {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub path_type: Option<OrchestrationPathType>,
+
+    /// The orchestration or service the path belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Reference>,
+    pub sets: Vec<RuleSet>,
+    #[serde(default)]
+    pub catch_all: CatchAll,
+    #[serde(rename = "self", default, skip_serializing_if = "Option::is_none")]
+    pub self_url: Option<String>,
+}
+
+/// Which path to get or update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrchestrationPathId<'a> {
+    /// Of the orchestration with this ID.
+    Global(&'a str),
+    Router(&'a str),
+    Unrouted(&'a str),
+
+    /// Of the service with this ID.
+    Service(&'a str),
+}
+
+impl OrchestrationPathId<'_> {
+    pub(crate) fn path(&self) -> String {
+        match self {
+            Self::Global(id) => format!("/event_orchestrations/{}/global", Segment(id)),
+            Self::Router(id) => format!("/event_orchestrations/{}/router", Segment(id)),
+            Self::Unrouted(id) => format!("/event_orchestrations/{}/unrouted", Segment(id)),
+            Self::Service(id) => format!("/event_orchestrations/services/{}", Segment(id)),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheVariableType {
+    /// The last value a regex matched in events matching the conditions.
+    RecentValue,
+
+    /// How many trigger events matching the conditions arrived within `ttl_seconds`.
+    TriggerEventCount,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CacheVariableConfiguration {
+    #[serde(rename = "type")]
+    pub variable_type: CacheVariableType,
+
+    /// Recent value only: the event field the regex reads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+
+    /// Trigger event count only: the counting window.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<u64>,
+}
+
+/// A value kept across events, usable in conditions as `cache_variables.name`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CacheVariable {
+    /// None on variables yet to be created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub disabled: bool,
+
+    /// Events that update the variable.
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    pub configuration: CacheVariableConfiguration,
+}
+
+impl CacheVariable {
+    pub fn recent_value(
+        name: String,
+        conditions: Vec<Condition>,
+        source: String,
+        regex: String,
+    ) -> Self {
+        CacheVariable {
+            id: None,
+            name,
+            disabled: false,
+            conditions,
+            configuration: CacheVariableConfiguration {
+                variable_type: CacheVariableType::RecentValue,
+                source: Some(source),
+                regex: Some(regex),
+                ttl_seconds: None,
+            },
+        }
+    }
+
+    pub fn trigger_event_count(name: String, conditions: Vec<Condition>, ttl_seconds: u64) -> Self {
+        CacheVariable {
+            id: None,
+            name,
+            disabled: false,
+            conditions,
+            configuration: CacheVariableConfiguration {
+                variable_type: CacheVariableType::TriggerEventCount,
+                source: None,
+                regex: None,
+                ttl_seconds: Some(ttl_seconds),
+            },
+        }
+    }
+}
+
+/// Whose cache variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheVariableScope<'a> {
+    /// Of the orchestration with this ID.
+    Global(&'a str),
+
+    /// Of the service with this ID.
+    Service(&'a str),
+}
+
+impl CacheVariableScope<'_> {
+    pub(crate) fn path(&self) -> String {
+        match self {
+            Self::Global(id) => format!("/event_orchestrations/{}/cache_variables", Segment(id)),
+            Self::Service(id) => {
+                format!(
+                    "/event_orchestrations/services/{}/cache_variables",
+                    Segment(id)
+                )
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct OrchestrationList {
+    orchestrations: Vec<Orchestration>,
+    #[serde(flatten)]
+    pagination: Pagination,
+}
+
+impl Page for OrchestrationList {
+    type Item = Orchestration;
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn into_items(self) -> Vec<Orchestration> {
+        self.orchestrations
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct OrchestrationWrapper<T> {
+    orchestration: T,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct OrchestrationPathWrapper<T> {
+    orchestration_path: T,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ActiveWrapper {
+    active: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct CacheVariableWrapper<T> {
+    cache_variable: T,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CacheVariablesWrapper {
+    cache_variables: Vec<CacheVariable>,
+}
+
+#[cfg(feature = "sync")]
+impl crate::rest::restsync::RestClient {
+    /// List every event orchestration, fetching all pages.
+    pub fn list_event_orchestrations(&self) -> RestResult<Vec<Orchestration>> {
+        self.get_all::<OrchestrationList>("/event_orchestrations", &Query::new())
+    }
+
+    pub fn get_event_orchestration(&self, id: &str) -> RestResult<Orchestration> {
+        let wrapper: OrchestrationWrapper<Orchestration> = self.get(
+            &format!("/event_orchestrations/{}", Segment(id)),
+            &Query::new(),
+        )?;
+        Ok(wrapper.orchestration)
+    }
+
+    pub fn get_orchestration_path(&self, id: OrchestrationPathId) -> RestResult<OrchestrationPath> {
+        let wrapper: OrchestrationPathWrapper<OrchestrationPath> =
+            self.get(&id.path(), &Query::new())?;
+        Ok(wrapper.orchestration_path)
+    }
+
+    /// Replace every rule set and the catch-all of the path.
+    pub fn update_orchestration_path(
+        &self,
+        id: OrchestrationPathId,
+        orchestration_path: &OrchestrationPath,
+    ) -> RestResult<OrchestrationPath> {
+        let wrapper: OrchestrationPathWrapper<OrchestrationPath> =
+            self.put(&id.path(), &OrchestrationPathWrapper { orchestration_path })?;
+        Ok(wrapper.orchestration_path)
+    }
+
+    /// Whether the service's events go through its orchestration path rather than its
+    /// event rules.
+    pub fn get_service_orchestration_active(&self, service_id: &str) -> RestResult<bool> {
+        let wrapper: ActiveWrapper = self.get(
+            &format!(
+                "/event_orchestrations/services/{}/active",
+                Segment(service_id)
+            ),
+            &Query::new(),
+        )?;
+        Ok(wrapper.active)
+    }
+
+    pub fn set_service_orchestration_active(
+        &self,
+        service_id: &str,
+        active: bool,
+    ) -> RestResult<bool> {
+        let wrapper: ActiveWrapper = self.put(
+            &format!(
+                "/event_orchestrations/services/{}/active",
+                Segment(service_id)
+            ),
+            &ActiveWrapper { active },
+        )?;
+        Ok(wrapper.active)
+    }
+
+    pub fn list_cache_variables(
+        &self,
+        scope: CacheVariableScope,
+    ) -> RestResult<Vec<CacheVariable>> {
+        let wrapper: CacheVariablesWrapper = self.get(&scope.path(), &Query::new())?;
+        Ok(wrapper.cache_variables)
+    }
+
+    pub fn get_cache_variable(
+        &self,
+        scope: CacheVariableScope,
+        id: &str,
+    ) -> RestResult<CacheVariable> {
+        let wrapper: CacheVariableWrapper<CacheVariable> =
+            self.get(&format!("{}/{}", scope.path(), Segment(id)), &Query::new())?;
+        Ok(wrapper.cache_variable)
+    }
+
+    pub fn create_cache_variable(
+        &self,
+        scope: CacheVariableScope,
+        cache_variable: &CacheVariable,
+    ) -> RestResult<CacheVariable> {
+        let wrapper: CacheVariableWrapper<CacheVariable> =
+            self.post(&scope.path(), &CacheVariableWrapper { cache_variable })?;
+        Ok(wrapper.cache_variable)
+    }
+
+    pub fn update_cache_variable(
+        &self,
+        scope: CacheVariableScope,
+        id: &str,
+        cache_variable: &CacheVariable,
+    ) -> RestResult<CacheVariable> {
+        let wrapper: CacheVariableWrapper<CacheVariable> = self.put(
+            &format!("{}/{}", scope.path(), Segment(id)),
+            &CacheVariableWrapper { cache_variable },
+        )?;
+        Ok(wrapper.cache_variable)
+    }
+
+    pub fn delete_cache_variable(&self, scope: CacheVariableScope, id: &str) -> RestResult<()> {
+        self.delete(&format!("{}/{}", scope.path(), Segment(id)))
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::rest::restasync::RestClient {
+    /// List every event orchestration, fetching all pages.
+    pub async fn list_event_orchestrations(&self) -> RestResult<Vec<Orchestration>> {
+        self.get_all::<OrchestrationList>("/event_orchestrations", &Query::new())
+            .await
+    }
+
+    pub async fn get_event_orchestration(&self, id: &str) -> RestResult<Orchestration> {
+        let wrapper: OrchestrationWrapper<Orchestration> = self
+            .get(
+                &format!("/event_orchestrations/{}", Segment(id)),
+                &Query::new(),
+            )
+            .await?;
+        Ok(wrapper.orchestration)
+    }
+
+    pub async fn get_orchestration_path(
+        &self,
+        id: OrchestrationPathId<'_>,
+    ) -> RestResult<OrchestrationPath> {
+        let wrapper: OrchestrationPathWrapper<OrchestrationPath> =
+            self.get(&id.path(), &Query::new()).await?;
+        Ok(wrapper.orchestration_path)
+    }
+
+    /// Replace every rule set and the catch-all of the path.
+    pub async fn update_orchestration_path(
+        &self,
+        id: OrchestrationPathId<'_>,
+        orchestration_path: &OrchestrationPath,
+    ) -> RestResult<OrchestrationPath> {
+        let wrapper: OrchestrationPathWrapper<OrchestrationPath> = self
+            .put(&id.path(), &OrchestrationPathWrapper { orchestration_path })
+            .await?;
+        Ok(wrapper.orchestration_path)
+    }
+
+    /// Whether the service's events go through its orchestration path rather than its
+    /// event rules.
+    pub async fn get_service_orchestration_active(&self, service_id: &str) -> RestResult<bool> {
+        let wrapper: ActiveWrapper = self
+            .get(
+                &format!(
+                    "/event_orchestrations/services/{}/active",
+                    Segment(service_id)
+                ),
+                &Query::new(),
+            )
+            .await?;
+        Ok(wrapper.active)
+    }
+
+    pub async fn set_service_orchestration_active(
+        &self,
+        service_id: &str,
+        active: bool,
+    ) -> RestResult<bool> {
+        let wrapper: ActiveWrapper = self
+            .put(
+                &format!(
+                    "/event_orchestrations/services/{}/active",
+                    Segment(service_id)
+                ),
+                &ActiveWrapper { active },
+            )
+            .await?;
+        Ok(wrapper.active)
+    }
+
+    pub async fn list_cache_variables(
+        &self,
+        scope: CacheVariableScope<'_>,
+    ) -> RestResult<Vec<CacheVariable>> {
+        let wrapper: CacheVariablesWrapper = self.get(&scope.path(), &Query::new()).await?;
+        Ok(wrapper.cache_variables)
+    }
+
+    pub async fn get_cache_variable(
+        &self,
+        scope: CacheVariableScope<'_>,
+        id: &str,
+    ) -> RestResult<CacheVariable> {
+        let wrapper: CacheVariableWrapper<CacheVariable> = self
+            .get(&format!("{}/{}", scope.path(), Segment(id)), &Query::new())
+            .await?;
+        Ok(wrapper.cache_variable)
+    }
+
+    pub async fn create_cache_variable(
+        &self,
+        scope: CacheVariableScope<'_>,
+        cache_variable: &CacheVariable,
+    ) -> RestResult<CacheVariable> {
+        let wrapper: CacheVariableWrapper<CacheVariable> = self
+            .post(&scope.path(), &CacheVariableWrapper { cache_variable })
+            .await?;
+        Ok(wrapper.cache_variable)
+    }
+
+    pub async fn update_cache_variable(
+        &self,
+        scope: CacheVariableScope<'_>,
+        id: &str,
+        cache_variable: &CacheVariable,
+    ) -> RestResult<CacheVariable> {
+        let wrapper: CacheVariableWrapper<CacheVariable> = self
+            .put(
+                &format!("{}/{}", scope.path(), Segment(id)),
+                &CacheVariableWrapper { cache_variable },
+            )
+            .await?;
+        Ok(wrapper.cache_variable)
+    }
+
+    pub async fn delete_cache_variable(
+        &self,
+        scope: CacheVariableScope<'_>,
+        id: &str,
+    ) -> RestResult<()> {
+        self.delete(&format!("{}/{}", scope.path(), Segment(id)))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn round_trips_service_path() {
+        let body = json!({
+            "type": "service",
+            "parent": {"id": "PIJ90N7", "type": "service_reference"},
+            "sets": [
+                {
+                    "id": "start",
+                    "rules": [{
+                        "id": "c91f72f3",
+                        "label": "Timeouts are warnings",
+                        "disabled": false,
+                        "conditions": [{"expression": "event.summary matches part 'timeout'"}],
+                        "actions": {
+                            "severity": "warning",
+                            "annotate": "See the timeout runbook",
+                            "variables": [{
+                                "name": "host",
+                                "path": "event.summary",
+                                "type": "regex",
+                                "value": "on (\\\\S+)"
+                            }],
+                            "extractions": [{
+                                "target": "event.summary",
+                                "template": "Timeout on {{variables.host}}"
+                            }],
+                            "route_to": "step-two",
+                            "automation_action": {"name": "Restart", "url": "https://example.com"}
+                        }
+                    }]
+                },
+                {"id": "step-two", "rules": []}
+            ],
+            "catch_all": {"actions": {"suppress": true}}
+        });
+
+        let path: OrchestrationPath = serde_json::from_value(body.clone()).unwrap();
+        let actions = &path.sets[0].rules[0].actions;
+        assert_eq!(actions.severity, Some(Severity::Warning));
+        assert_eq!(
+            actions.extractions,
+            vec![Extraction::template(
+                "event.summary".to_owned(),
+                "Timeout on {{variables.host}}".to_owned()
+            )]
+        );
+        assert!(actions.other.contains_key("automation_action"));
+        assert_eq!(path.catch_all.actions.suppress, Some(true));
+
+        assert_eq!(serde_json::to_value(&path).unwrap(), body);
+    }
+
+    #[test]
+    fn builds_paths_and_cache_variables() {
+        assert_eq!(
+            OrchestrationPathId::Router("b02e973d").path(),
+            "/event_orchestrations/b02e973d/router"
+        );
+        assert_eq!(
+            CacheVariableScope::Service("PIJ90N7").path(),
+            "/event_orchestrations/services/PIJ90N7/cache_variables"
+        );
+        assert_eq!(
+            serde_json::to_value(CacheVariable::trigger_event_count(
+                "flaps".to_owned(),
+                vec![Condition::new("event.source matches 'db1'".to_owned())],
+                300
+            ))
+            .unwrap(),
+            json!({
+                "name": "flaps",
+                "disabled": false,
+                "conditions": [{"expression": "event.source matches 'db1'"}],
+                "configuration": {"type": "trigger_event_count", "ttl_seconds": 300}
+            })
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use crate::rest::types::*;
+
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE, USER_AGENT};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+const CONTENT_TYPE_JSON: &str = "application/json";
+
+/// The main PagerDuty REST API v2 client
+pub struct RestClient {
+    client: Client,
+
+    /// Scheme and host of the REST API, e.g. https://api.pagerduty.com
+    base_url: String,
+
+    /// Email of the user that writes are made on behalf of
+    from: Option<HeaderValue>,
+}
+
+impl RestClient {
+    pub fn new(auth: Auth, user_agent: Option<String>) -> RestResult<RestClient> {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static(ACCEPT_V2));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE_JSON));
+        let mut authorization = HeaderValue::from_str(&auth.header_value())?;
+        authorization.set_sensitive(true);
+        headers.insert(AUTHORIZATION, authorization);
+        if let Some(ua) = user_agent {
+            headers.insert(USER_AGENT, HeaderValue::from_str(ua.as_str())?);
+        }
+
+        let client = Client::builder().default_headers(headers).build()?;
+
+        Ok(RestClient {
+            client,
+            base_url: US_BASE_URL.to_owned(),
+            from: None,
+        })
+    }
+
+    /// Use a different host, e.g. EU_BASE_URL or a proxy.
+    pub fn with_base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Send the From header with every write. Writes on behalf of a user, such as
+    /// acknowledging an incident, need it when authenticating with an account level token.
+    pub fn with_from(mut self, email: String) -> RestResult<Self> {
+        self.from = Some(HeaderValue::from_str(&email)?);
+        Ok(self)
+    }
+
+    /// GET any path, for endpoints without a typed method.
+    pub fn get<R: DeserializeOwned>(&self, path: &str, query: &Query) -> RestResult<R> {
+        self.send(self.request(Method::GET, path).query(query))
+    }
+
+    /// POST any path, for endpoints without a typed method.
+    pub fn post<B: Serialize, R: DeserializeOwned>(&self, path: &str, body: &B) -> RestResult<R> {
+        self.send(self.request(Method::POST, path).json(body))
+    }
+
+    /// PUT any path, for endpoints without a typed method.
+    pub fn put<B: Serialize, R: DeserializeOwned>(&self, path: &str, body: &B) -> RestResult<R> {
+        self.send(self.request(Method::PUT, path).json(body))
+    }
+
+    /// DELETE any path, for endpoints without a typed method.
+    pub fn delete(&self, path: &str) -> RestResult<()> {
+        self.send_empty(self.request(Method::DELETE, path))
+    }
+
+    /// Fetch every page of a list, for lists without a typed method.
+    pub fn get_all<P: Page>(&self, path: &str, query: &Query) -> RestResult<Vec<P::Item>> {
+        let mut items = vec![];
+        let mut offset = 0;
+        loop {
+            let mut page_query = query.clone();
+            page_query.push(("offset".to_owned(), offset.to_string()));
+            page_query.push(("limit".to_owned(), MAX_PAGE_SIZE.to_string()));
+
+            let page: P = self.get(path, &page_query)?;
+            let more = page.pagination().more;
+            let mut page_items = page.into_items();
+            offset += page_items.len() as u64;
+            let empty = page_items.is_empty();
+            items.append(&mut page_items);
+
+            if !more || empty {
+                return Ok(items);
+            }
+        }
+    }
+
+    pub(crate) fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let write = method != Method::GET;
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.base_url, path));
+        if let (true, Some(from)) = (write, &self.from) {
+            request = request.header("From", from.clone());
+        }
+        request
+    }
+
+    pub(crate) fn send<R: DeserializeOwned>(&self, request: RequestBuilder) -> RestResult<R> {
+        let res = request.send()?;
+        let status = res.status();
+        let body = res.bytes()?;
+        if status.is_client_error() || status.is_server_error() {
+            return Err(RestError::from_response(status.as_u16(), &body));
+        }
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Send a request whose response has no (useful) body.
+    pub(crate) fn send_empty(&self, request: RequestBuilder) -> RestResult<()> {
+        let res = request.send()?;
+        let status = res.status();
+        if status.is_client_error() || status.is_server_error() {
+            return Err(RestError::from_response(status.as_u16(), &res.bytes()?));
+        }
+        Ok(())
+    }
+}
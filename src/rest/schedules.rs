@@ -0,0 +1,671 @@
+//! On-call schedules, their layers and overrides, and who's on call.
+
+use crate::rest::types::*;
+use crate::types::{
+    datetime_to_iso8601, iso8601_to_datetime, optional_datetime_to_iso8601,
+    optional_iso8601_to_datetime,
+};
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RestrictionType {
+    DailyRestriction,
+    WeeklyRestriction,
+}
+
+/// Limits a layer to part of the day or week, e.g. weekdays 09:00 to 17:00.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Restriction {
+    #[serde(rename = "type")]
+    pub restriction_type: RestrictionType,
+
+    /// e.g. "09:00:00"
+    pub start_time_of_day: String,
+
+    /// ISO weekday the restriction starts on, 1 (Monday) to 7 (Sunday); weekly restrictions only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_day_of_week: Option<u8>,
+    pub duration_seconds: u64,
+}
+
+impl Restriction {
+    pub fn daily(start_time_of_day: String, duration_seconds: u64) -> Self {
+        Restriction {
+            restriction_type: RestrictionType::DailyRestriction,
+            start_time_of_day,
+            start_day_of_week: None,
+            duration_seconds,
+        }
+    }
+
+    pub fn weekly(start_day_of_week: u8, start_time_of_day: String, duration_seconds: u64) -> Self {
+        Restriction {
+            restriction_type: RestrictionType::WeeklyRestriction,
+            start_time_of_day,
+            start_day_of_week: Some(start_day_of_week),
+            duration_seconds,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ScheduleLayerUser {
+    pub user: Reference,
+}
+
+/// A stretch of time with a user on call.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ScheduleEntry {
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub start: OffsetDateTime,
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub end: OffsetDateTime,
+    pub user: Reference,
+}
+
+/// A rotation of users through the schedule.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ScheduleLayer {
+    /// None on layers yet to be created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub start: OffsetDateTime,
+
+    /// None for a layer that never ends.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "optional_datetime_to_iso8601",
+        deserialize_with = "optional_iso8601_to_datetime"
+    )]
+    pub end: Option<OffsetDateTime>,
+
+    /// When the first user's turn starts, which may be before `start`.
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub rotation_virtual_start: OffsetDateTime,
+    pub rotation_turn_length_seconds: u64,
+
+    /// The users in the order they take turns.
+    pub users: Vec<ScheduleLayerUser>,
+    #[serde(default)]
+    pub restrictions: Vec<Restriction>,
+
+    /// Rendered for the range of `get_schedule`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rendered_schedule_entries: Vec<ScheduleEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rendered_coverage_percentage: Option<f64>,
+}
+
+impl ScheduleLayer {
+    /// A layer taking turns through the users in order from `start`, with no restrictions.
+    pub fn new(
+        start: OffsetDateTime,
+        rotation_turn_length_seconds: u64,
+        user_ids: &[&str],
+    ) -> Self {
+        ScheduleLayer {
+            id: None,
+            name: None,
+            start,
+            end: None,
+            rotation_virtual_start: start,
+            rotation_turn_length_seconds,
+            users: user_ids
+                .iter()
+                .map(|id| ScheduleLayerUser {
+                    user: Reference::new(id, "user_reference"),
+                })
+                .collect(),
+            restrictions: vec![],
+            rendered_schedule_entries: vec![],
+            rendered_coverage_percentage: None,
+        }
+    }
+}
+
+/// A rendering of the schedule over a range: the final schedule or the overrides.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SubSchedule {
+    pub name: String,
+    #[serde(default)]
+    pub rendered_schedule_entries: Vec<ScheduleEntry>,
+    #[serde(default)]
+    pub rendered_coverage_percentage: Option<f64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Schedule {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    #[serde(rename = "self", default)]
+    pub self_url: Option<String>,
+
+    /// e.g. "America/New_York"
+    pub time_zone: String,
+    #[serde(default)]
+    pub schedule_layers: Vec<ScheduleLayer>,
+
+    /// The layers and overrides combined, rendered for the range of `get_schedule`.
+    #[serde(default)]
+    pub final_schedule: Option<SubSchedule>,
+    #[serde(default)]
+    pub overrides_subschedule: Option<SubSchedule>,
+    #[serde(default)]
+    pub escalation_policies: Vec<Reference>,
+    #[serde(default)]
+    pub users: Vec<Reference>,
+    #[serde(default)]
+    pub teams: Vec<Reference>,
+}
+
+/// A schedule to create, update or preview. Updates replace the whole schedule.
+#[derive(Serialize, Debug, Clone)]
+pub struct ScheduleSpec {
+    #[serde(rename = "type")]
+    object_type: &'static str,
+    pub name: String,
+    pub time_zone: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub schedule_layers: Vec<ScheduleLayer>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub teams: Vec<Reference>,
+}
+
+impl ScheduleSpec {
+    pub fn new(name: String, time_zone: String, schedule_layers: Vec<ScheduleLayer>) -> Self {
+        ScheduleSpec {
+            object_type: "schedule",
+            name,
+            time_zone,
+            description: None,
+            schedule_layers,
+            teams: vec![],
+        }
+    }
+}
+
+/// The range to render a schedule or list overrides for.
+#[derive(Debug, Clone, Default)]
+pub struct ScheduleRange {
+    pub since: Option<OffsetDateTime>,
+    pub until: Option<OffsetDateTime>,
+
+    /// Render in this time zone rather than the schedule's.
+    pub time_zone: Option<String>,
+}
+
+impl ScheduleRange {
+    pub(crate) fn to_query(&self) -> Query {
+        let mut query = time_range_query(self.since, self.until);
+        if let Some(time_zone) = &self.time_zone {
+            query.push(("time_zone".to_owned(), time_zone.clone()));
+        }
+        query
+    }
+}
+
+/// Someone else on call for a while, over the top of the layers.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Override {
+    /// None on overrides yet to be created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub start: OffsetDateTime,
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub end: OffsetDateTime,
+    pub user: Reference,
+}
+
+impl Override {
+    pub fn new(start: OffsetDateTime, end: OffsetDateTime, user_id: &str) -> Self {
+        Override {
+            id: None,
+            start,
+            end,
+            user: Reference::new(user_id, "user_reference"),
+        }
+    }
+}
+
+/// The outcome of creating one of several overrides.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct OverrideResult {
+    /// The HTTP status for this override, e.g. 201 or 400.
+    pub status: u16,
+    #[serde(rename = "override", default)]
+    pub schedule_override: Option<Override>,
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+/// Who's on call for an escalation policy's level, and until when.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct OnCall {
+    pub user: Reference,
+    pub escalation_policy: Reference,
+    pub escalation_level: u32,
+
+    /// None when the user is a target of the escalation rule rather than on a schedule.
+    #[serde(default)]
+    pub schedule: Option<Reference>,
+
+    /// None when on call indefinitely.
+    #[serde(
+        default,
+        serialize_with = "optional_datetime_to_iso8601",
+        deserialize_with = "optional_iso8601_to_datetime"
+    )]
+    pub start: Option<OffsetDateTime>,
+    #[serde(
+        default,
+        serialize_with = "optional_datetime_to_iso8601",
+        deserialize_with = "optional_iso8601_to_datetime"
+    )]
+    pub end: Option<OffsetDateTime>,
+}
+
+/// Which on-calls to list.
+#[derive(Debug, Clone, Default)]
+pub struct OnCallFilter {
+    pub user_ids: Vec<String>,
+    pub escalation_policy_ids: Vec<String>,
+    pub schedule_ids: Vec<String>,
+
+    /// Expand references: "users", "schedules" or "escalation_policies".
+    pub include: Vec<String>,
+    pub since: Option<OffsetDateTime>,
+    pub until: Option<OffsetDateTime>,
+
+    /// Only the on-call that's soonest for each user, escalation policy and level.
+    pub earliest: bool,
+    pub time_zone: Option<String>,
+}
+
+impl OnCallFilter {
+    pub(crate) fn to_query(&self) -> Query {
+        let mut query = Query::new();
+        for (name, ids) in [
+            ("user_ids[]", &self.user_ids),
+            ("escalation_policy_ids[]", &self.escalation_policy_ids),
+            ("schedule_ids[]", &self.schedule_ids),
+        ] {
+            query.extend(ids.iter().map(|id| (name.to_owned(), id.clone())));
+        }
+        query.extend(include_query(&self.include));
+        query.extend(time_range_query(self.since, self.until));
+        if self.earliest {
+            query.push(("earliest".to_owned(), "true".to_owned()));
+        }
+        if let Some(time_zone) = &self.time_zone {
+            query.push(("time_zone".to_owned(), time_zone.clone()));
+        }
+        query
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ScheduleList {
+    schedules: Vec<Schedule>,
+    #[serde(flatten)]
+    pagination: Pagination,
+}
+
+impl Page for ScheduleList {
+    type Item = Schedule;
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn into_items(self) -> Vec<Schedule> {
+        self.schedules
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct OnCallList {
+    oncalls: Vec<OnCall>,
+    #[serde(flatten)]
+    pagination: Pagination,
+}
+
+impl Page for OnCallList {
+    type Item = OnCall;
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn into_items(self) -> Vec<OnCall> {
+        self.oncalls
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ScheduleWrapper<T> {
+    schedule: T,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct OverridesWrapper<T> {
+    overrides: T,
+}
+
+#[cfg(feature = "sync")]
+impl crate::rest::restsync::RestClient {
+    /// List every schedule whose name contains `query`, fetching all pages.
+    pub fn list_schedules(&self, query: Option<&str>) -> RestResult<Vec<Schedule>> {
+        let query = query
+            .map(|q| vec![("query".to_owned(), q.to_owned())])
+            .unwrap_or_default();
+        self.get_all::<ScheduleList>("/schedules", &query)
+    }
+
+    /// Get a schedule with its layers, final schedule and overrides rendered for the range.
+    pub fn get_schedule(&self, id: &str, range: &ScheduleRange) -> RestResult<Schedule> {
+        let wrapper: ScheduleWrapper<Schedule> =
+            self.get(&format!("/schedules/{}", Segment(id)), &range.to_query())?;
+        Ok(wrapper.schedule)
+    }
+
+    pub fn create_schedule(&self, schedule: &ScheduleSpec) -> RestResult<Schedule> {
+        let wrapper: ScheduleWrapper<Schedule> =
+            self.post("/schedules", &ScheduleWrapper { schedule })?;
+        Ok(wrapper.schedule)
+    }
+
+    pub fn update_schedule(&self, id: &str, schedule: &ScheduleSpec) -> RestResult<Schedule> {
+        let wrapper: ScheduleWrapper<Schedule> = self.put(
+            &format!("/schedules/{}", Segment(id)),
+            &ScheduleWrapper { schedule },
+        )?;
+        Ok(wrapper.schedule)
+    }
+
+    pub fn delete_schedule(&self, id: &str) -> RestResult<()> {
+        self.delete(&format!("/schedules/{}", Segment(id)))
+    }
+
+    /// Render a schedule for the range without saving it.
+    pub fn preview_schedule(
+        &self,
+        schedule: &ScheduleSpec,
+        range: &ScheduleRange,
+    ) -> RestResult<Schedule> {
+        let request = self
+            .request(reqwest::Method::POST, "/schedules/preview")
+            .query(&range.to_query())
+            .json(&ScheduleWrapper { schedule });
+        let wrapper: ScheduleWrapper<Schedule> = self.send(request)?;
+        Ok(wrapper.schedule)
+    }
+
+    /// List the overrides of a schedule within the range.
+    pub fn list_schedule_overrides(
+        &self,
+        id: &str,
+        range: &ScheduleRange,
+    ) -> RestResult<Vec<Override>> {
+        let wrapper: OverridesWrapper<Vec<Override>> = self.get(
+            &format!("/schedules/{}/overrides", Segment(id)),
+            &range.to_query(),
+        )?;
+        Ok(wrapper.overrides)
+    }
+
+    /// Create overrides, each of which may fail on its own.
+    pub fn create_schedule_overrides(
+        &self,
+        id: &str,
+        overrides: &[Override],
+    ) -> RestResult<Vec<OverrideResult>> {
+        self.post(
+            &format!("/schedules/{}/overrides", Segment(id)),
+            &OverridesWrapper { overrides },
+        )
+    }
+
+    pub fn delete_schedule_override(&self, id: &str, override_id: &str) -> RestResult<()> {
+        self.delete(&format!(
+            "/schedules/{}/overrides/{}",
+            Segment(id),
+            Segment(override_id)
+        ))
+    }
+
+    /// List everyone on call matching the filter, fetching all pages.
+    pub fn list_oncalls(&self, filter: &OnCallFilter) -> RestResult<Vec<OnCall>> {
+        self.get_all::<OnCallList>("/oncalls", &filter.to_query())
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::rest::restasync::RestClient {
+    /// List every schedule whose name contains `query`, fetching all pages.
+    pub async fn list_schedules(&self, query: Option<&str>) -> RestResult<Vec<Schedule>> {
+        let query = query
+            .map(|q| vec![("query".to_owned(), q.to_owned())])
+            .unwrap_or_default();
+        self.get_all::<ScheduleList>("/schedules", &query).await
+    }
+
+    /// Get a schedule with its layers, final schedule and overrides rendered for the range.
+    pub async fn get_schedule(&self, id: &str, range: &ScheduleRange) -> RestResult<Schedule> {
+        let wrapper: ScheduleWrapper<Schedule> = self
+            .get(&format!("/schedules/{}", Segment(id)), &range.to_query())
+            .await?;
+        Ok(wrapper.schedule)
+    }
+
+    pub async fn create_schedule(&self, schedule: &ScheduleSpec) -> RestResult<Schedule> {
+        let wrapper: ScheduleWrapper<Schedule> = self
+            .post("/schedules", &ScheduleWrapper { schedule })
+            .await?;
+        Ok(wrapper.schedule)
+    }
+
+    pub async fn update_schedule(&self, id: &str, schedule: &ScheduleSpec) -> RestResult<Schedule> {
+        let wrapper: ScheduleWrapper<Schedule> = self
+            .put(
+                &format!("/schedules/{}", Segment(id)),
+                &ScheduleWrapper { schedule },
+            )
+            .await?;
+        Ok(wrapper.schedule)
+    }
+
+    pub async fn delete_schedule(&self, id: &str) -> RestResult<()> {
+        self.delete(&format!("/schedules/{}", Segment(id))).await
+    }
+
+    /// Render a schedule for the range without saving it.
+    pub async fn preview_schedule(
+        &self,
+        schedule: &ScheduleSpec,
+        range: &ScheduleRange,
+    ) -> RestResult<Schedule> {
+        let request = self
+            .request(reqwest::Method::POST, "/schedules/preview")
+            .query(&range.to_query())
+            .json(&ScheduleWrapper { schedule });
+        let wrapper: ScheduleWrapper<Schedule> = self.send(request).await?;
+        Ok(wrapper.schedule)
+    }
+
+    /// List the overrides of a schedule within the range.
+    pub async fn list_schedule_overrides(
+        &self,
+        id: &str,
+        range: &ScheduleRange,
+    ) -> RestResult<Vec<Override>> {
+        let wrapper: OverridesWrapper<Vec<Override>> = self
+            .get(
+                &format!("/schedules/{}/overrides", Segment(id)),
+                &range.to_query(),
+            )
+            .await?;
+        Ok(wrapper.overrides)
+    }
+
+    /// Create overrides, each of which may fail on its own.
+    pub async fn create_schedule_overrides(
+        &self,
+        id: &str,
+        overrides: &[Override],
+    ) -> RestResult<Vec<OverrideResult>> {
+        self.post(
+            &format!("/schedules/{}/overrides", Segment(id)),
+            &OverridesWrapper { overrides },
+        )
+        .await
+    }
+
+    pub async fn delete_schedule_override(&self, id: &str, override_id: &str) -> RestResult<()> {
+        self.delete(&format!(
+            "/schedules/{}/overrides/{}",
+            Segment(id),
+            Segment(override_id)
+        ))
+        .await
+    }
+
+    /// List everyone on call matching the filter, fetching all pages.
+    pub async fn list_oncalls(&self, filter: &OnCallFilter) -> RestResult<Vec<OnCall>> {
+        self.get_all::<OnCallList>("/oncalls", &filter.to_query())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use time::macros::datetime;
+
+    #[test]
+    fn deserializes_rendered_schedule() {
+        let schedule: Schedule = serde_json::from_value(json!({
+            "id": "PI7DH85",
+            "type": "schedule",
+            "name": "Daily Engineering Rotation",
+            "time_zone": "America/New_York",
+            "schedule_layers": [{
+                "id": "PG68P1M",
+                "name": "Night Shift",
+                "start": "2015-11-06T20:00:00-05:00",
+                "end": null,
+                "rotation_virtual_start": "2015-11-06T20:00:00-05:00",
+                "rotation_turn_length_seconds": 86400,
+                "users": [{"user": {"id": "PXPGF42", "type": "user_reference"}}],
+                "restrictions": [{
+                    "type": "daily_restriction",
+                    "start_time_of_day": "08:00:00",
+                    "duration_seconds": 32400
+                }],
+                "rendered_coverage_percentage": 37.5
+            }],
+            "final_schedule": {
+                "name": "Final Schedule",
+                "rendered_schedule_entries": [{
+                    "start": "2015-11-09T08:00:00-05:00",
+                    "end": "2015-11-09T17:00:00-05:00",
+                    "user": {"id": "PXPGF42", "type": "user_reference", "summary": "Earline Greenholt"}
+                }],
+                "rendered_coverage_percentage": 37.5
+            },
+            "escalation_policies": [{"id": "PT20YPA", "type": "escalation_policy_reference"}]
+        }))
+        .unwrap();
+
+        let layer = &schedule.schedule_layers[0];
+        assert_eq!(layer.end, None);
+        assert_eq!(
+            layer.restrictions,
+            vec![Restriction::daily("08:00:00".to_owned(), 32400)]
+        );
+        let entry = &schedule.final_schedule.unwrap().rendered_schedule_entries[0];
+        assert_eq!(entry.start, datetime!(2015-11-09 13:00:00 UTC));
+        assert_eq!(entry.user.id, "PXPGF42");
+    }
+
+    #[test]
+    fn serializes_spec_and_oncall_filter() {
+        let start = datetime!(2021-06-01 09:00:00 UTC);
+        let mut layer = ScheduleLayer::new(start, 7 * 86400, &["PXPGF42", "PAM4FGS"]);
+        layer.restrictions = vec![Restriction::weekly(1, "09:00:00".to_owned(), 5 * 86400)];
+        let spec = ScheduleSpec::new("Primary".to_owned(), "UTC".to_owned(), vec![layer]);
+
+        assert_eq!(
+            serde_json::to_value(&spec).unwrap(),
+            json!({
+                "type": "schedule",
+                "name": "Primary",
+                "time_zone": "UTC",
+                "schedule_layers": [{
+                    "start": "2021-06-01T09:00:00Z",
+                    "rotation_virtual_start": "2021-06-01T09:00:00Z",
+                    "rotation_turn_length_seconds": 604800,
+                    "users": [
+                        {"user": {"id": "PXPGF42", "type": "user_reference"}},
+                        {"user": {"id": "PAM4FGS", "type": "user_reference"}}
+                    ],
+                    "restrictions": [{
+                        "type": "weekly_restriction",
+                        "start_time_of_day": "09:00:00",
+                        "start_day_of_week": 1,
+                        "duration_seconds": 432000
+                    }]
+                }]
+            })
+        );
+
+        let filter = OnCallFilter {
+            schedule_ids: vec!["PI7DH85".to_owned()],
+            since: Some(start),
+            earliest: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.to_query(),
+            vec![
+                ("schedule_ids[]".to_owned(), "PI7DH85".to_owned()),
+                ("since".to_owned(), "2021-06-01T09:00:00Z".to_owned()),
+                ("earliest".to_owned(), "true".to_owned()),
+            ]
+        );
+    }
+}
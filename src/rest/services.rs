@@ -0,0 +1,625 @@
+//! Services and their integrations.
+//!
+//! Creating an Events API v2 integration on a service gives the integration key
+//! `EventsV2::new` needs.
+
+use crate::rest::types::*;
+use crate::types::{optional_datetime_to_iso8601, optional_iso8601_to_datetime};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
+
+/// The integration type to send events to with `EventsV2`.
+pub const EVENTS_API_V2_INTEGRATION: &str = "events_api_v2_inbound_integration";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceUrgency {
+    High,
+    Low,
+
+    /// High for critical and error alerts, low for warnings and info.
+    SeverityBased,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct UrgencyType {
+    /// Always "constant".
+    #[serde(rename = "type")]
+    pub urgency_type: String,
+    pub urgency: ServiceUrgency,
+}
+
+impl UrgencyType {
+    pub fn constant(urgency: ServiceUrgency) -> Self {
+        UrgencyType {
+            urgency_type: "constant".to_owned(),
+            urgency,
+        }
+    }
+}
+
+/// The urgency of the service's new incidents.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IncidentUrgencyRule {
+    Constant {
+        urgency: ServiceUrgency,
+    },
+
+    /// One urgency during support hours and another outside them.
+    UseSupportHours {
+        during_support_hours: UrgencyType,
+        outside_support_hours: UrgencyType,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SupportHours {
+    /// Always "fixed_time_per_day".
+    #[serde(rename = "type")]
+    pub support_hours_type: String,
+
+    /// e.g. "America/Lima"
+    pub time_zone: String,
+
+    /// ISO weekdays, 1 (Monday) to 7 (Sunday).
+    pub days_of_week: Vec<u8>,
+
+    /// e.g. "09:00:00"
+    pub start_time: String,
+    pub end_time: String,
+}
+
+impl SupportHours {
+    pub fn new(
+        time_zone: String,
+        days_of_week: Vec<u8>,
+        start_time: String,
+        end_time: String,
+    ) -> Self {
+        SupportHours {
+            support_hours_type: "fixed_time_per_day".to_owned(),
+            time_zone,
+            days_of_week,
+            start_time,
+            end_time,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ScheduledActionTime {
+    /// Always "named_time".
+    #[serde(rename = "type")]
+    pub time_type: String,
+
+    /// "support_hours_start" or "support_hours_end".
+    pub name: String,
+}
+
+/// Raise the urgency of open incidents when support hours start.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ScheduledAction {
+    /// Always "urgency_change".
+    #[serde(rename = "type")]
+    pub action_type: String,
+    pub at: ScheduledActionTime,
+    pub to_urgency: ServiceUrgency,
+}
+
+impl ScheduledAction {
+    pub fn urgency_change_at_support_hours_start() -> Self {
+        ScheduledAction {
+            action_type: "urgency_change".to_owned(),
+            at: ScheduledActionTime {
+                time_type: "named_time".to_owned(),
+                name: "support_hours_start".to_owned(),
+            },
+            to_urgency: ServiceUrgency::High,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct AlertGroupingConfig {
+    /// Time grouping: minutes to group alerts for, 0 for as long as the incident is open.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+
+    /// Content based grouping: "all" or "any" of `fields` must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregate: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<String>>,
+
+    /// Content based and intelligent grouping: minutes to group alerts for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_window: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AlertGroupingParameters {
+    /// "time", "intelligent" or "content_based"; None to not group.
+    #[serde(rename = "type")]
+    pub grouping_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<AlertGroupingConfig>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Service {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    #[serde(rename = "self", default)]
+    pub self_url: Option<String>,
+
+    /// "active", "warning", "critical", "maintenance" or "disabled".
+    #[serde(default)]
+    pub status: Option<String>,
+
+    /// Seconds until an incident resolves itself; None to never.
+    #[serde(default)]
+    pub auto_resolve_timeout: Option<u64>,
+
+    /// Seconds until an acknowledged incident re-triggers; None to never.
+    #[serde(default)]
+    pub acknowledgement_timeout: Option<u64>,
+    pub escalation_policy: Reference,
+    #[serde(default)]
+    pub teams: Vec<Reference>,
+    #[serde(default)]
+    pub integrations: Vec<Reference>,
+    #[serde(default)]
+    pub incident_urgency_rule: Option<IncidentUrgencyRule>,
+    #[serde(default)]
+    pub support_hours: Option<SupportHours>,
+    #[serde(default)]
+    pub scheduled_actions: Vec<ScheduledAction>,
+
+    /// "create_alerts_and_incidents" or "create_incidents".
+    #[serde(default)]
+    pub alert_creation: Option<String>,
+    #[serde(default)]
+    pub alert_grouping_parameters: Option<AlertGroupingParameters>,
+    #[serde(
+        default,
+        serialize_with = "optional_datetime_to_iso8601",
+        deserialize_with = "optional_iso8601_to_datetime"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(
+        default,
+        serialize_with = "optional_datetime_to_iso8601",
+        deserialize_with = "optional_iso8601_to_datetime"
+    )]
+    pub last_incident_timestamp: Option<OffsetDateTime>,
+}
+
+/// The fields of a service to create or update. Fields left None aren't sent, so an update
+/// changes only what's set.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ServiceSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escalation_policy: Option<Reference>,
+
+    /// Some(None) turns auto-resolution off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_resolve_timeout: Option<Option<u64>>,
+
+    /// Some(None) turns acknowledgement timeouts off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acknowledgement_timeout: Option<Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub teams: Option<Vec<Reference>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incident_urgency_rule: Option<IncidentUrgencyRule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub support_hours: Option<SupportHours>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduled_actions: Option<Vec<ScheduledAction>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert_creation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert_grouping_parameters: Option<AlertGroupingParameters>,
+}
+
+impl ServiceSpec {
+    /// The least a new service needs.
+    pub fn new(name: String, escalation_policy_id: &str) -> Self {
+        ServiceSpec {
+            name: Some(name),
+            escalation_policy: Some(Reference::new(
+                escalation_policy_id,
+                "escalation_policy_reference",
+            )),
+            ..Default::default()
+        }
+    }
+}
+
+/// Which services to list.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceFilter {
+    /// Only services whose name contains this.
+    pub query: Option<String>,
+    pub team_ids: Vec<String>,
+
+    /// Expand references, e.g. "escalation_policies", "teams" or "integrations".
+    pub include: Vec<String>,
+}
+
+impl ServiceFilter {
+    pub(crate) fn to_query(&self) -> Query {
+        let mut query: Query = self
+            .team_ids
+            .iter()
+            .map(|t| ("team_ids[]".to_owned(), t.clone()))
+            .collect();
+        query.extend(include_query(&self.include));
+        if let Some(q) = &self.query {
+            query.push(("query".to_owned(), q.clone()));
+        }
+        query
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Integration {
+    pub id: String,
+
+    /// e.g. EVENTS_API_V2_INTEGRATION
+    #[serde(rename = "type")]
+    pub integration_type: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    #[serde(rename = "self", default)]
+    pub self_url: Option<String>,
+    #[serde(default)]
+    pub service: Option<Reference>,
+    #[serde(default)]
+    pub vendor: Option<Reference>,
+
+    /// The routing key for `EventsV2::new`, on events integrations.
+    #[serde(default)]
+    pub integration_key: Option<String>,
+
+    /// The address to email, on email integrations.
+    #[serde(default)]
+    pub integration_email: Option<String>,
+    #[serde(
+        default,
+        serialize_with = "optional_datetime_to_iso8601",
+        deserialize_with = "optional_iso8601_to_datetime"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct IntegrationSpec {
+    #[serde(rename = "type")]
+    pub integration_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<Reference>,
+
+    /// Settings specific to the integration type.
+    #[serde(flatten)]
+    pub extra: Option<serde_json::Map<String, Value>>,
+}
+
+impl IntegrationSpec {
+    pub fn events_api_v2(name: String) -> Self {
+        IntegrationSpec {
+            integration_type: EVENTS_API_V2_INTEGRATION.to_owned(),
+            name: Some(name),
+            vendor: None,
+            extra: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ServiceList {
+    services: Vec<Service>,
+    #[serde(flatten)]
+    pagination: Pagination,
+}
+
+impl Page for ServiceList {
+    type Item = Service;
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn into_items(self) -> Vec<Service> {
+        self.services
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ServiceWrapper<T> {
+    service: T,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct IntegrationWrapper<T> {
+    integration: T,
+}
+
+#[cfg(feature = "sync")]
+impl crate::rest::restsync::RestClient {
+    /// List every service matching the filter, fetching all pages.
+    pub fn list_services(&self, filter: &ServiceFilter) -> RestResult<Vec<Service>> {
+        self.get_all::<ServiceList>("/services", &filter.to_query())
+    }
+
+    pub fn get_service(&self, id: &str) -> RestResult<Service> {
+        let wrapper: ServiceWrapper<Service> =
+            self.get(&format!("/services/{}", Segment(id)), &Query::new())?;
+        Ok(wrapper.service)
+    }
+
+    pub fn create_service(&self, service: &ServiceSpec) -> RestResult<Service> {
+        let wrapper: ServiceWrapper<Service> =
+            self.post("/services", &ServiceWrapper { service })?;
+        Ok(wrapper.service)
+    }
+
+    pub fn update_service(&self, id: &str, service: &ServiceSpec) -> RestResult<Service> {
+        let wrapper: ServiceWrapper<Service> = self.put(
+            &format!("/services/{}", Segment(id)),
+            &ServiceWrapper { service },
+        )?;
+        Ok(wrapper.service)
+    }
+
+    pub fn delete_service(&self, id: &str) -> RestResult<()> {
+        self.delete(&format!("/services/{}", Segment(id)))
+    }
+
+    pub fn create_integration(
+        &self,
+        service_id: &str,
+        integration: &IntegrationSpec,
+    ) -> RestResult<Integration> {
+        let wrapper: IntegrationWrapper<Integration> = self.post(
+            &format!("/services/{}/integrations", Segment(service_id)),
+            &IntegrationWrapper { integration },
+        )?;
+        Ok(wrapper.integration)
+    }
+
+    pub fn get_integration(&self, service_id: &str, id: &str) -> RestResult<Integration> {
+        let wrapper: IntegrationWrapper<Integration> = self.get(
+            &format!(
+                "/services/{}/integrations/{}",
+                Segment(service_id),
+                Segment(id)
+            ),
+            &Query::new(),
+        )?;
+        Ok(wrapper.integration)
+    }
+
+    pub fn update_integration(
+        &self,
+        service_id: &str,
+        id: &str,
+        integration: &IntegrationSpec,
+    ) -> RestResult<Integration> {
+        let wrapper: IntegrationWrapper<Integration> = self.put(
+            &format!(
+                "/services/{}/integrations/{}",
+                Segment(service_id),
+                Segment(id)
+            ),
+            &IntegrationWrapper { integration },
+        )?;
+        Ok(wrapper.integration)
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::rest::restasync::RestClient {
+    /// List every service matching the filter, fetching all pages.
+    pub async fn list_services(&self, filter: &ServiceFilter) -> RestResult<Vec<Service>> {
+        self.get_all::<ServiceList>("/services", &filter.to_query())
+            .await
+    }
+
+    pub async fn get_service(&self, id: &str) -> RestResult<Service> {
+        let wrapper: ServiceWrapper<Service> = self
+            .get(&format!("/services/{}", Segment(id)), &Query::new())
+            .await?;
+        Ok(wrapper.service)
+    }
+
+    pub async fn create_service(&self, service: &ServiceSpec) -> RestResult<Service> {
+        let wrapper: ServiceWrapper<Service> =
+            self.post("/services", &ServiceWrapper { service }).await?;
+        Ok(wrapper.service)
+    }
+
+    pub async fn update_service(&self, id: &str, service: &ServiceSpec) -> RestResult<Service> {
+        let wrapper: ServiceWrapper<Service> = self
+            .put(
+                &format!("/services/{}", Segment(id)),
+                &ServiceWrapper { service },
+            )
+            .await?;
+        Ok(wrapper.service)
+    }
+
+    pub async fn delete_service(&self, id: &str) -> RestResult<()> {
+        self.delete(&format!("/services/{}", Segment(id))).await
+    }
+
+    pub async fn create_integration(
+        &self,
+        service_id: &str,
+        integration: &IntegrationSpec,
+    ) -> RestResult<Integration> {
+        let wrapper: IntegrationWrapper<Integration> = self
+            .post(
+                &format!("/services/{}/integrations", Segment(service_id)),
+                &IntegrationWrapper { integration },
+            )
+            .await?;
+        Ok(wrapper.integration)
+    }
+
+    pub async fn get_integration(&self, service_id: &str, id: &str) -> RestResult<Integration> {
+        let wrapper: IntegrationWrapper<Integration> = self
+            .get(
+                &format!(
+                    "/services/{}/integrations/{}",
+                    Segment(service_id),
+                    Segment(id)
+                ),
+                &Query::new(),
+            )
+            .await?;
+        Ok(wrapper.integration)
+    }
+
+    pub async fn update_integration(
+        &self,
+        service_id: &str,
+        id: &str,
+        integration: &IntegrationSpec,
+    ) -> RestResult<Integration> {
+        let wrapper: IntegrationWrapper<Integration> = self
+            .put(
+                &format!(
+                    "/services/{}/integrations/{}",
+                    Segment(service_id),
+                    Segment(id)
+                ),
+                &IntegrationWrapper { integration },
+            )
+            .await?;
+        Ok(wrapper.integration)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn deserializes_service() {
+        let service: Service = serde_json::from_value(json!({
+            "id": "PIJ90N7",
+            "type": "service",
+            "name": "My Application Service",
+            "auto_resolve_timeout": 14400,
+            "acknowledgement_timeout": null,
+            "created_at": "2015-11-06T11:12:51-05:00",
+            "status": "active",
+            "alert_creation": "create_alerts_and_incidents",
+            "alert_grouping_parameters": {"type": "time", "config": {"timeout": 2}},
+            "integrations": [{"id": "PQ12345", "type": "generic_email_inbound_integration_reference"}],
+            "escalation_policy": {"id": "PT20YPA", "type": "escalation_policy_reference"},
+            "teams": [],
+            "incident_urgency_rule": {
+                "type": "use_support_hours",
+                "during_support_hours": {"type": "constant", "urgency": "high"},
+                "outside_support_hours": {"type": "constant", "urgency": "low"}
+            },
+            "support_hours": {
+                "type": "fixed_time_per_day",
+                "time_zone": "America/Lima",
+                "start_time": "09:00:00",
+                "end_time": "17:00:00",
+                "days_of_week": [1, 2, 3, 4, 5]
+            },
+            "scheduled_actions": [{
+                "type": "urgency_change",
+                "at": {"type": "named_time", "name": "support_hours_start"},
+                "to_urgency": "high"
+            }]
+        }))
+        .unwrap();
+
+        assert_eq!(service.auto_resolve_timeout, Some(14400));
+        assert_eq!(service.acknowledgement_timeout, None);
+        assert_eq!(
+            service.incident_urgency_rule,
+            Some(IncidentUrgencyRule::UseSupportHours {
+                during_support_hours: UrgencyType::constant(ServiceUrgency::High),
+                outside_support_hours: UrgencyType::constant(ServiceUrgency::Low),
+            })
+        );
+        assert_eq!(
+            service.scheduled_actions,
+            vec![ScheduledAction::urgency_change_at_support_hours_start()]
+        );
+        assert_eq!(
+            service
+                .alert_grouping_parameters
+                .unwrap()
+                .config
+                .unwrap()
+                .timeout,
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn serializes_spec() {
+        let mut spec = ServiceSpec::new("Checkout".to_owned(), "PT20YPA");
+        spec.auto_resolve_timeout = Some(None);
+        spec.incident_urgency_rule = Some(IncidentUrgencyRule::Constant {
+            urgency: ServiceUrgency::SeverityBased,
+        });
+
+        assert_eq!(
+            serde_json::to_value(&spec).unwrap(),
+            json!({
+                "name": "Checkout",
+                "escalation_policy": {"id": "PT20YPA", "type": "escalation_policy_reference"},
+                "auto_resolve_timeout": null,
+                "incident_urgency_rule": {"type": "constant", "urgency": "severity_based"}
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(IntegrationSpec::events_api_v2("Rust".to_owned())).unwrap(),
+            json!({"type": "events_api_v2_inbound_integration", "name": "Rust"})
+        );
+
+        let mut email = IntegrationSpec::events_api_v2("Inbox".to_owned());
+        email.integration_type = "generic_email_inbound_integration".to_owned();
+        let mut extra = serde_json::Map::new();
+        extra.insert(
+            "integration_email".to_owned(),
+            json!("checkout@example.pagerduty.com"),
+        );
+        email.extra = Some(extra);
+        assert_eq!(
+            serde_json::to_value(email).unwrap(),
+            json!({
+                "type": "generic_email_inbound_integration",
+                "name": "Inbox",
+                "integration_email": "checkout@example.pagerduty.com"
+            })
+        );
+    }
+}
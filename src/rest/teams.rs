@@ -0,0 +1,328 @@
+//! Teams, their members and their escalation policies.
+
+use crate::rest::types::*;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TeamRole {
+    Observer,
+    Responder,
+    Manager,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Team {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    #[serde(rename = "self", default)]
+    pub self_url: Option<String>,
+
+    /// The team this one is a subteam of.
+    #[serde(default)]
+    pub parent: Option<Reference>,
+
+    /// The role new members get, e.g. "manager" or "none".
+    #[serde(default)]
+    pub default_role: Option<String>,
+}
+
+/// The fields of a team to create or update. Fields left None aren't sent, so an update
+/// changes only what's set.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct TeamSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Reference>,
+}
+
+impl TeamSpec {
+    /// The least a new team needs.
+    pub fn new(name: String) -> Self {
+        TeamSpec {
+            name: Some(name),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TeamMember {
+    pub user: Reference,
+    pub role: TeamRole,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TeamList {
+    teams: Vec<Team>,
+    #[serde(flatten)]
+    pagination: Pagination,
+}
+
+impl Page for TeamList {
+    type Item = Team;
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn into_items(self) -> Vec<Team> {
+        self.teams
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TeamMemberList {
+    members: Vec<TeamMember>,
+    #[serde(flatten)]
+    pagination: Pagination,
+}
+
+impl Page for TeamMemberList {
+    type Item = TeamMember;
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn into_items(self) -> Vec<TeamMember> {
+        self.members
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct TeamWrapper<T> {
+    team: T,
+}
+
+#[derive(Serialize)]
+pub(crate) struct RoleBody {
+    role: TeamRole,
+}
+
+fn query_of(query: Option<&str>) -> Query {
+    query
+        .map(|q| vec![("query".to_owned(), q.to_owned())])
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "sync")]
+impl crate::rest::restsync::RestClient {
+    /// List every team whose name contains `query`, fetching all pages.
+    pub fn list_teams(&self, query: Option<&str>) -> RestResult<Vec<Team>> {
+        self.get_all::<TeamList>("/teams", &query_of(query))
+    }
+
+    pub fn get_team(&self, id: &str) -> RestResult<Team> {
+        let wrapper: TeamWrapper<Team> =
+            self.get(&format!("/teams/{}", Segment(id)), &Query::new())?;
+        Ok(wrapper.team)
+    }
+
+    pub fn create_team(&self, team: &TeamSpec) -> RestResult<Team> {
+        let wrapper: TeamWrapper<Team> = self.post("/teams", &TeamWrapper { team })?;
+        Ok(wrapper.team)
+    }
+
+    pub fn update_team(&self, id: &str, team: &TeamSpec) -> RestResult<Team> {
+        let wrapper: TeamWrapper<Team> =
+            self.put(&format!("/teams/{}", Segment(id)), &TeamWrapper { team })?;
+        Ok(wrapper.team)
+    }
+
+    pub fn delete_team(&self, id: &str) -> RestResult<()> {
+        self.delete(&format!("/teams/{}", Segment(id)))
+    }
+
+    /// List every member of the team with their role, fetching all pages.
+    pub fn list_team_members(&self, id: &str) -> RestResult<Vec<TeamMember>> {
+        self.get_all::<TeamMemberList>(&format!("/teams/{}/members", Segment(id)), &Query::new())
+    }
+
+    /// Add a user to the team, or change their role if they're already a member.
+    pub fn add_team_user(&self, id: &str, user_id: &str, role: TeamRole) -> RestResult<()> {
+        self.send_empty(
+            self.request(
+                reqwest::Method::PUT,
+                &format!("/teams/{}/users/{}", Segment(id), Segment(user_id)),
+            )
+            .json(&RoleBody { role }),
+        )
+    }
+
+    pub fn remove_team_user(&self, id: &str, user_id: &str) -> RestResult<()> {
+        self.delete(&format!(
+            "/teams/{}/users/{}",
+            Segment(id),
+            Segment(user_id)
+        ))
+    }
+
+    pub fn add_team_escalation_policy(
+        &self,
+        id: &str,
+        escalation_policy_id: &str,
+    ) -> RestResult<()> {
+        self.send_empty(self.request(
+            reqwest::Method::PUT,
+            &format!(
+                "/teams/{}/escalation_policies/{}",
+                Segment(id),
+                Segment(escalation_policy_id)
+            ),
+        ))
+    }
+
+    pub fn remove_team_escalation_policy(
+        &self,
+        id: &str,
+        escalation_policy_id: &str,
+    ) -> RestResult<()> {
+        self.delete(&format!(
+            "/teams/{}/escalation_policies/{}",
+            Segment(id),
+            Segment(escalation_policy_id)
+        ))
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::rest::restasync::RestClient {
+    /// List every team whose name contains `query`, fetching all pages.
+    pub async fn list_teams(&self, query: Option<&str>) -> RestResult<Vec<Team>> {
+        self.get_all::<TeamList>("/teams", &query_of(query)).await
+    }
+
+    pub async fn get_team(&self, id: &str) -> RestResult<Team> {
+        let wrapper: TeamWrapper<Team> = self
+            .get(&format!("/teams/{}", Segment(id)), &Query::new())
+            .await?;
+        Ok(wrapper.team)
+    }
+
+    pub async fn create_team(&self, team: &TeamSpec) -> RestResult<Team> {
+        let wrapper: TeamWrapper<Team> = self.post("/teams", &TeamWrapper { team }).await?;
+        Ok(wrapper.team)
+    }
+
+    pub async fn update_team(&self, id: &str, team: &TeamSpec) -> RestResult<Team> {
+        let wrapper: TeamWrapper<Team> = self
+            .put(&format!("/teams/{}", Segment(id)), &TeamWrapper { team })
+            .await?;
+        Ok(wrapper.team)
+    }
+
+    pub async fn delete_team(&self, id: &str) -> RestResult<()> {
+        self.delete(&format!("/teams/{}", Segment(id))).await
+    }
+
+    /// List every member of the team with their role, fetching all pages.
+    pub async fn list_team_members(&self, id: &str) -> RestResult<Vec<TeamMember>> {
+        self.get_all::<TeamMemberList>(&format!("/teams/{}/members", Segment(id)), &Query::new())
+            .await
+    }
+
+    /// Add a user to the team, or change their role if they're already a member.
+    pub async fn add_team_user(&self, id: &str, user_id: &str, role: TeamRole) -> RestResult<()> {
+        self.send_empty(
+            self.request(
+                reqwest::Method::PUT,
+                &format!("/teams/{}/users/{}", Segment(id), Segment(user_id)),
+            )
+            .json(&RoleBody { role }),
+        )
+        .await
+    }
+
+    pub async fn remove_team_user(&self, id: &str, user_id: &str) -> RestResult<()> {
+        self.delete(&format!(
+            "/teams/{}/users/{}",
+            Segment(id),
+            Segment(user_id)
+        ))
+        .await
+    }
+
+    pub async fn add_team_escalation_policy(
+        &self,
+        id: &str,
+        escalation_policy_id: &str,
+    ) -> RestResult<()> {
+        self.send_empty(self.request(
+            reqwest::Method::PUT,
+            &format!(
+                "/teams/{}/escalation_policies/{}",
+                Segment(id),
+                Segment(escalation_policy_id)
+            ),
+        ))
+        .await
+    }
+
+    pub async fn remove_team_escalation_policy(
+        &self,
+        id: &str,
+        escalation_policy_id: &str,
+    ) -> RestResult<()> {
+        self.delete(&format!(
+            "/teams/{}/escalation_policies/{}",
+            Segment(id),
+            Segment(escalation_policy_id)
+        ))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn deserializes_members() {
+        let members: TeamMemberList = serde_json::from_value(json!({
+            "members": [
+                {"user": {"id": "PXPGF42", "type": "user_reference"}, "role": "manager"},
+                {"user": {"id": "PAM4FGS", "type": "user_reference"}, "role": "responder"}
+            ],
+            "offset": 0,
+            "limit": 25,
+            "more": false
+        }))
+        .unwrap();
+
+        assert_eq!(
+            members
+                .into_items()
+                .iter()
+                .map(|m| (m.user.id.as_str(), m.role))
+                .collect::<Vec<_>>(),
+            vec![
+                ("PXPGF42", TeamRole::Manager),
+                ("PAM4FGS", TeamRole::Responder)
+            ]
+        );
+    }
+
+    #[test]
+    fn serializes_role_body() {
+        assert_eq!(
+            serde_json::to_value(RoleBody {
+                role: TeamRole::Observer
+            })
+            .unwrap(),
+            json!({"role": "observer"})
+        );
+    }
+}
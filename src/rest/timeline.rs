@@ -0,0 +1,620 @@
+//! What's under an incident: notes, log entries, alerts, related change events, and the
+//! outlier and past incident analyses.
+
+use crate::rest::types::*;
+use crate::types::{
+    datetime_to_iso8601, iso8601_to_datetime, optional_datetime_to_iso8601,
+    optional_iso8601_to_datetime, Link, Severity,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct NoteChannel {
+    pub summary: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Note {
+    pub id: String,
+    #[serde(default)]
+    pub user: Option<Reference>,
+    #[serde(default)]
+    pub channel: Option<NoteChannel>,
+    pub content: String,
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub id: String,
+
+    /// e.g. "trigger_log_entry", "acknowledge_log_entry", "annotate_log_entry"
+    #[serde(rename = "type")]
+    pub log_entry_type: String,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub created_at: OffsetDateTime,
+
+    /// Who or what caused the entry: a user, service, integration and so on.
+    #[serde(default)]
+    pub agent: Option<Reference>,
+
+    /// How the entry came about, such as the API or a phone call; expanded by include[]=channels.
+    #[serde(default)]
+    pub channel: Option<Value>,
+    #[serde(default)]
+    pub service: Option<Value>,
+    #[serde(default)]
+    pub incident: Option<Value>,
+    #[serde(default)]
+    pub teams: Vec<Value>,
+    #[serde(default)]
+    pub contexts: Vec<Value>,
+    #[serde(default)]
+    pub event_details: Option<Value>,
+
+    /// For notify_log_entry and assign_log_entry, who was notified or assigned.
+    #[serde(default)]
+    pub user: Option<Reference>,
+    #[serde(default)]
+    pub assignees: Vec<Reference>,
+}
+
+/// Which log entries to list.
+#[derive(Debug, Clone, Default)]
+pub struct LogEntryFilter {
+    /// Only the most important changes: triggers, acknowledgements, escalations and so on.
+    pub is_overview: bool,
+
+    /// Expand references: "incidents", "services", "channels" or "teams".
+    pub include: Vec<String>,
+    pub since: Option<OffsetDateTime>,
+    pub until: Option<OffsetDateTime>,
+
+    /// e.g. "Europe/Berlin"
+    pub time_zone: Option<String>,
+}
+
+impl LogEntryFilter {
+    pub(crate) fn to_query(&self) -> Query {
+        let mut query = include_query(&self.include);
+        if self.is_overview {
+            query.push(("is_overview".to_owned(), "true".to_owned()));
+        }
+        query.extend(time_range_query(self.since, self.until));
+        if let Some(time_zone) = &self.time_zone {
+            query.push(("time_zone".to_owned(), time_zone.clone()));
+        }
+        query
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Triggered,
+    Resolved,
+}
+
+/// An alert under an incident, as made by an event or email.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub id: String,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub created_at: OffsetDateTime,
+    pub status: AlertStatus,
+
+    /// The dedup key of the events that made the alert.
+    #[serde(default)]
+    pub alert_key: Option<String>,
+    #[serde(default)]
+    pub service: Option<Reference>,
+    #[serde(default)]
+    pub incident: Option<Reference>,
+    #[serde(default)]
+    pub severity: Option<Severity>,
+    #[serde(default)]
+    pub suppressed: bool,
+
+    /// The event's payload, with its custom_details, as PagerDuty stored it.
+    #[serde(default)]
+    pub body: Option<Value>,
+}
+
+/// A change event PagerDuty thinks may be related to an incident.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RelatedChangeEvent {
+    pub id: String,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(
+        default,
+        serialize_with = "optional_datetime_to_iso8601",
+        deserialize_with = "optional_iso8601_to_datetime"
+    )]
+    pub timestamp: Option<OffsetDateTime>,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub integration: Option<Reference>,
+    #[serde(default)]
+    pub services: Vec<Reference>,
+    #[serde(default)]
+    pub links: Vec<Link>,
+    #[serde(default)]
+    pub custom_details: Option<Value>,
+
+    /// Why it's related, e.g. "same_service" or "related_service".
+    #[serde(default)]
+    pub reasons: Vec<String>,
+}
+
+/// A short description of an incident, as analyses refer to them.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct IncidentSummary {
+    pub id: String,
+    #[serde(
+        serialize_with = "datetime_to_iso8601",
+        deserialize_with = "iso8601_to_datetime"
+    )]
+    pub created_at: OffsetDateTime,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(rename = "self", default)]
+    pub self_url: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct IncidentTemplate {
+    pub id: String,
+    #[serde(default)]
+    pub cluster_id: Option<String>,
+    #[serde(default)]
+    pub mined_text: Option<String>,
+}
+
+/// How unusual an incident is for its service.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct OutlierIncident {
+    pub incident: IncidentSummary,
+    #[serde(default)]
+    pub incident_template: Option<IncidentTemplate>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct PastIncident {
+    pub incident: IncidentSummary,
+
+    /// How similar it is to the incident asked about.
+    pub score: f64,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NotesWrapper {
+    notes: Vec<Note>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct NoteWrapper<T> {
+    note: T,
+}
+
+#[derive(Serialize)]
+pub(crate) struct NewNote<'a> {
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LogEntryList {
+    log_entries: Vec<LogEntry>,
+    #[serde(flatten)]
+    pagination: Pagination,
+}
+
+impl Page for LogEntryList {
+    type Item = LogEntry;
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn into_items(self) -> Vec<LogEntry> {
+        self.log_entries
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AlertList {
+    alerts: Vec<Alert>,
+    #[serde(flatten)]
+    pagination: Pagination,
+}
+
+impl Page for AlertList {
+    type Item = Alert;
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn into_items(self) -> Vec<Alert> {
+        self.alerts
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct AlertWrapper<T> {
+    alert: T,
+}
+
+#[derive(Serialize)]
+pub(crate) struct AlertStatusUpdate {
+    #[serde(rename = "type")]
+    object_type: &'static str,
+    status: AlertStatus,
+}
+
+impl AlertStatusUpdate {
+    fn new(status: AlertStatus) -> AlertWrapper<Self> {
+        AlertWrapper {
+            alert: AlertStatusUpdate {
+                object_type: "alert",
+                status,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChangeEventsWrapper {
+    change_events: Vec<RelatedChangeEvent>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct OutlierIncidentWrapper {
+    outlier_incident: OutlierIncident,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct PastIncidentsWrapper {
+    past_incidents: Vec<PastIncident>,
+}
+
+fn alert_query(statuses: &[AlertStatus]) -> Query {
+    statuses
+        .iter()
+        .map(|s| {
+            (
+                "statuses[]".to_owned(),
+                match s {
+                    AlertStatus::Triggered => "triggered".to_owned(),
+                    AlertStatus::Resolved => "resolved".to_owned(),
+                },
+            )
+        })
+        .collect()
+}
+
+fn limit_query(limit: u32) -> Query {
+    vec![("limit".to_owned(), limit.to_string())]
+}
+
+#[cfg(feature = "sync")]
+impl crate::rest::restsync::RestClient {
+    pub fn list_incident_notes(&self, incident_id: &str) -> RestResult<Vec<Note>> {
+        let wrapper: NotesWrapper = self.get(
+            &format!("/incidents/{}/notes", Segment(incident_id)),
+            &Query::new(),
+        )?;
+        Ok(wrapper.notes)
+    }
+
+    /// Add a note to an incident. Needs the From header (see `RestClient::with_from`).
+    pub fn create_incident_note(&self, incident_id: &str, content: &str) -> RestResult<Note> {
+        let wrapper: NoteWrapper<Note> = self.post(
+            &format!("/incidents/{}/notes", Segment(incident_id)),
+            &NoteWrapper {
+                note: NewNote { content },
+            },
+        )?;
+        Ok(wrapper.note)
+    }
+
+    /// The incident's log entries, oldest first, fetching all pages.
+    pub fn list_incident_log_entries(
+        &self,
+        incident_id: &str,
+        filter: &LogEntryFilter,
+    ) -> RestResult<Vec<LogEntry>> {
+        self.get_all::<LogEntryList>(
+            &format!("/incidents/{}/log_entries", Segment(incident_id)),
+            &filter.to_query(),
+        )
+    }
+
+    /// The incident's alerts with any of the statuses (all alerts if none are given).
+    pub fn list_incident_alerts(
+        &self,
+        incident_id: &str,
+        statuses: &[AlertStatus],
+    ) -> RestResult<Vec<Alert>> {
+        self.get_all::<AlertList>(
+            &format!("/incidents/{}/alerts", Segment(incident_id)),
+            &alert_query(statuses),
+        )
+    }
+
+    pub fn get_incident_alert(&self, incident_id: &str, alert_id: &str) -> RestResult<Alert> {
+        let wrapper: AlertWrapper<Alert> = self.get(
+            &format!(
+                "/incidents/{}/alerts/{}",
+                Segment(incident_id),
+                Segment(alert_id)
+            ),
+            &Query::new(),
+        )?;
+        Ok(wrapper.alert)
+    }
+
+    /// Resolve (or re-trigger) one alert. Needs the From header.
+    pub fn update_incident_alert_status(
+        &self,
+        incident_id: &str,
+        alert_id: &str,
+        status: AlertStatus,
+    ) -> RestResult<Alert> {
+        let wrapper: AlertWrapper<Alert> = self.put(
+            &format!(
+                "/incidents/{}/alerts/{}",
+                Segment(incident_id),
+                Segment(alert_id)
+            ),
+            &AlertStatusUpdate::new(status),
+        )?;
+        Ok(wrapper.alert)
+    }
+
+    pub fn list_related_change_events(
+        &self,
+        incident_id: &str,
+    ) -> RestResult<Vec<RelatedChangeEvent>> {
+        let wrapper: ChangeEventsWrapper = self.get(
+            &format!("/incidents/{}/related_change_events", Segment(incident_id)),
+            &Query::new(),
+        )?;
+        Ok(wrapper.change_events)
+    }
+
+    pub fn get_outlier_incident(&self, incident_id: &str) -> RestResult<OutlierIncident> {
+        let wrapper: OutlierIncidentWrapper = self.get(
+            &format!("/incidents/{}/outlier_incident", Segment(incident_id)),
+            &Query::new(),
+        )?;
+        Ok(wrapper.outlier_incident)
+    }
+
+    /// Up to `limit` (at most 999) incidents on the same service that resemble this one.
+    pub fn list_past_incidents(
+        &self,
+        incident_id: &str,
+        limit: u32,
+    ) -> RestResult<Vec<PastIncident>> {
+        let wrapper: PastIncidentsWrapper = self.get(
+            &format!("/incidents/{}/past_incidents", Segment(incident_id)),
+            &limit_query(limit),
+        )?;
+        Ok(wrapper.past_incidents)
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::rest::restasync::RestClient {
+    pub async fn list_incident_notes(&self, incident_id: &str) -> RestResult<Vec<Note>> {
+        let wrapper: NotesWrapper = self
+            .get(
+                &format!("/incidents/{}/notes", Segment(incident_id)),
+                &Query::new(),
+            )
+            .await?;
+        Ok(wrapper.notes)
+    }
+
+    /// Add a note to an incident. Needs the From header (see `RestClient::with_from`).
+    pub async fn create_incident_note(&self, incident_id: &str, content: &str) -> RestResult<Note> {
+        let wrapper: NoteWrapper<Note> = self
+            .post(
+                &format!("/incidents/{}/notes", Segment(incident_id)),
+                &NoteWrapper {
+                    note: NewNote { content },
+                },
+            )
+            .await?;
+        Ok(wrapper.note)
+    }
+
+    /// The incident's log entries, oldest first, fetching all pages.
+    pub async fn list_incident_log_entries(
+        &self,
+        incident_id: &str,
+        filter: &LogEntryFilter,
+    ) -> RestResult<Vec<LogEntry>> {
+        self.get_all::<LogEntryList>(
+            &format!("/incidents/{}/log_entries", Segment(incident_id)),
+            &filter.to_query(),
+        )
+        .await
+    }
+
+    /// The incident's alerts with any of the statuses (all alerts if none are given).
+    pub async fn list_incident_alerts(
+        &self,
+        incident_id: &str,
+        statuses: &[AlertStatus],
+    ) -> RestResult<Vec<Alert>> {
+        self.get_all::<AlertList>(
+            &format!("/incidents/{}/alerts", Segment(incident_id)),
+            &alert_query(statuses),
+        )
+        .await
+    }
+
+    pub async fn get_incident_alert(&self, incident_id: &str, alert_id: &str) -> RestResult<Alert> {
+        let wrapper: AlertWrapper<Alert> = self
+            .get(
+                &format!(
+                    "/incidents/{}/alerts/{}",
+                    Segment(incident_id),
+                    Segment(alert_id)
+                ),
+                &Query::new(),
+            )
+            .await?;
+        Ok(wrapper.alert)
+    }
+
+    /// Resolve (or re-trigger) one alert. Needs the From header.
+    pub async fn update_incident_alert_status(
+        &self,
+        incident_id: &str,
+        alert_id: &str,
+        status: AlertStatus,
+    ) -> RestResult<Alert> {
+        let wrapper: AlertWrapper<Alert> = self
+            .put(
+                &format!(
+                    "/incidents/{}/alerts/{}",
+                    Segment(incident_id),
+                    Segment(alert_id)
+                ),
+                &AlertStatusUpdate::new(status),
+            )
+            .await?;
+        Ok(wrapper.alert)
+    }
+
+    pub async fn list_related_change_events(
+        &self,
+        incident_id: &str,
+    ) -> RestResult<Vec<RelatedChangeEvent>> {
+        let wrapper: ChangeEventsWrapper = self
+            .get(
+                &format!("/incidents/{}/related_change_events", Segment(incident_id)),
+                &Query::new(),
+            )
+            .await?;
+        Ok(wrapper.change_events)
+    }
+
+    pub async fn get_outlier_incident(&self, incident_id: &str) -> RestResult<OutlierIncident> {
+        let wrapper: OutlierIncidentWrapper = self
+            .get(
+                &format!("/incidents/{}/outlier_incident", Segment(incident_id)),
+                &Query::new(),
+            )
+            .await?;
+        Ok(wrapper.outlier_incident)
+    }
+
+    /// Up to `limit` (at most 999) incidents on the same service that resemble this one.
+    pub async fn list_past_incidents(
+        &self,
+        incident_id: &str,
+        limit: u32,
+    ) -> RestResult<Vec<PastIncident>> {
+        let wrapper: PastIncidentsWrapper = self
+            .get(
+                &format!("/incidents/{}/past_incidents", Segment(incident_id)),
+                &limit_query(limit),
+            )
+            .await?;
+        Ok(wrapper.past_incidents)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn log_entry_query() {
+        let filter = LogEntryFilter {
+            is_overview: true,
+            include: vec!["channels".to_owned(), "teams".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.to_query(),
+            vec![
+                ("include[]".to_owned(), "channels".to_owned()),
+                ("include[]".to_owned(), "teams".to_owned()),
+                ("is_overview".to_owned(), "true".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn deserializes_timeline() {
+        let entry: LogEntry = serde_json::from_str(
+            r#"{
+                "id": "Q02JTSNZWHSEKV",
+                "type": "trigger_log_entry",
+                "summary": "Triggered through the API",
+                "created_at": "2015-11-07T00:14:20Z",
+                "agent": {"id": "PLBZ3SD", "type": "generic_events_api_inbound_integration_reference"},
+                "channel": {"type": "api", "details": {"cpu": "95%"}},
+                "service": {"id": "PIJ90N7", "type": "service_reference"},
+                "incident": {"id": "PT4KHLK", "type": "incident_reference"},
+                "teams": [],
+                "contexts": [{"type": "link", "href": "https://example.com"}],
+                "event_details": {"description": "CPU high"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(entry.log_entry_type, "trigger_log_entry");
+        assert_eq!(entry.contexts.len(), 1);
+
+        let alert: Alert = serde_json::from_str(
+            r#"{
+                "id": "PT4KHLK",
+                "type": "alert",
+                "created_at": "2015-10-06T21:30:42Z",
+                "status": "resolved",
+                "alert_key": "baf7cf21b1da41b4b0221008339ff357",
+                "service": {"id": "PIJ90N7", "type": "service_reference"},
+                "severity": "critical",
+                "suppressed": false,
+                "body": {"type": "alert_body", "details": {"customKey": "Server is on fire!"}}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(alert.status, AlertStatus::Resolved);
+        assert_eq!(alert.severity, Some(Severity::Critical));
+
+        let past: PastIncidentsWrapper = serde_json::from_str(
+            r#"{"past_incidents": [{"incident": {"id": "Q1", "created_at": "2020-11-04T16:08:15Z", "self": "https://api.pagerduty.com/incidents/Q1", "title": "Same again"}, "score": 46.8}], "total": 1, "limit": 5}"#,
+        )
+        .unwrap();
+        assert_eq!(past.past_incidents[0].score, 46.8);
+    }
+}
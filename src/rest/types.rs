@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use std::convert::From;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use reqwest::header::InvalidHeaderValue;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// The REST API in the US service region.
+pub const US_BASE_URL: &str = "https://api.pagerduty.com";
+
+/// The REST API in the EU service region.
+pub const EU_BASE_URL: &str = "https://api.eu.pagerduty.com";
+
+pub(crate) const ACCEPT_V2: &str = "application/vnd.pagerduty+json;version=2";
+
+/// How a RestClient authenticates.
+pub enum Auth {
+    /// A REST API key, account or user level.
+    Token(String),
+
+    /// An OAuth access token.
+    Bearer(String),
+}
+
+impl Auth {
+    pub(crate) fn header_value(&self) -> String {
+        match self {
+            Self::Token(token) => format!("Token token={}", token),
+            Self::Bearer(token) => format!("Bearer {}", token),
+        }
+    }
+}
+
+/// The error PagerDuty returns with a failed request.
+///
+/// https://developer.pagerduty.com/docs/rest-api-v2/errors/
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ApiErrorBody {
+    pub message: String,
+
+    /// PagerDuty's error code, e.g. 2001 for invalid input.
+    #[serde(default)]
+    pub code: Option<u32>,
+
+    /// Details, such as which fields were invalid.
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ApiErrorEnvelope {
+    pub error: ApiErrorBody,
+}
+
+#[derive(Debug)]
+pub enum RestError {
+    ReqwestError(reqwest::Error),
+    InvalidHeaderValue(InvalidHeaderValue),
+    SerdeJsonError(serde_json::Error),
+
+    /// A 4xx or 5xx, with PagerDuty's explanation when it sent one.
+    ApiError(u16, Option<ApiErrorBody>),
+}
+
+impl Error for RestError {}
+impl Display for RestError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::ReqwestError(e) => write!(f, "RequestError: {}", e),
+            Self::InvalidHeaderValue(e) => write!(f, "InvalidHeaderValue: {}", e),
+            Self::SerdeJsonError(e) => write!(f, "SerdeJsonError: {}", e),
+            Self::ApiError(status, Some(body)) => {
+                write!(f, "ApiError: {} {}", status, body.message)?;
+                if !body.errors.is_empty() {
+                    write!(f, " ({})", body.errors.join("; "))?;
+                }
+                Ok(())
+            }
+            Self::ApiError(status, None) => write!(f, "ApiError: {}", status),
+        }
+    }
+}
+impl From<reqwest::Error> for RestError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::ReqwestError(err)
+    }
+}
+impl From<InvalidHeaderValue> for RestError {
+    fn from(err: InvalidHeaderValue) -> Self {
+        Self::InvalidHeaderValue(err)
+    }
+}
+impl From<serde_json::Error> for RestError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::SerdeJsonError(err)
+    }
+}
+
+impl RestError {
+    /// Build the error for a failed response from its status and body.
+    pub(crate) fn from_response(status: u16, body: &[u8]) -> Self {
+        Self::ApiError(
+            status,
+            serde_json::from_slice::<ApiErrorEnvelope>(body)
+                .ok()
+                .map(|e| e.error),
+        )
+    }
+}
+
+pub type RestResult<T> = Result<T, RestError>;
+
+/// Query parameters. A list, not a map, since array filters such as `statuses[]` repeat.
+pub type Query = Vec<(String, String)>;
+
+/// The query expanding the references in `include`, e.g. include[]=teams.
+pub(crate) fn include_query(include: &[String]) -> Query {
+    include
+        .iter()
+        .map(|i| ("include[]".to_owned(), i.clone()))
+        .collect()
+}
+
+/// `since` and `until` query parameters, in RFC 3339, for whichever bounds are set.
+pub(crate) fn time_range_query(
+    since: Option<OffsetDateTime>,
+    until: Option<OffsetDateTime>,
+) -> Query {
+    let mut query = Query::new();
+    for (name, date) in [("since", since), ("until", until)] {
+        if let Some(formatted) = date.and_then(|d| d.format(&Rfc3339).ok()) {
+            query.push((name.to_owned(), formatted));
+        }
+    }
+    query
+}
+
+/// An ID placed in a request path. Displays percent-encoded, so characters like `/`, `?`
+/// and `#` stay part of the segment instead of changing the URL around it.
+pub(crate) struct Segment<'a>(pub(crate) &'a str);
+
+impl Display for Segment<'_> {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        for byte in self.0.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    write!(f, "{}", byte as char)?
+                }
+                _ => write!(f, "%{:02X}", byte)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Classic offset pagination, as returned with every list.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Pagination {
+    #[serde(default)]
+    pub offset: u64,
+    #[serde(default)]
+    pub limit: u64,
+    #[serde(default)]
+    pub more: bool,
+    #[serde(default)]
+    pub total: Option<u64>,
+}
+
+/// A page of a list response, so `get_all` can fetch every page of any list.
+pub trait Page: serde::de::DeserializeOwned {
+    type Item;
+
+    fn pagination(&self) -> &Pagination;
+    fn into_items(self) -> Vec<Self::Item>;
+}
+
+/// The largest page PagerDuty serves.
+pub(crate) const MAX_PAGE_SIZE: u64 = 100;
+
+/// A reference to another object, as PagerDuty embeds them: `{"id": "P123", "type": "service_reference"}`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub object_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html_url: Option<String>,
+    #[serde(rename = "self", default, skip_serializing_if = "Option::is_none")]
+    pub self_url: Option<String>,
+}
+
+impl Reference {
+    /// A reference to send, e.g. `Reference::new("P123", "service_reference")`.
+    pub fn new(id: &str, object_type: &str) -> Self {
+        Reference {
+            id: id.to_owned(),
+            object_type: object_type.to_owned(),
+            summary: None,
+            html_url: None,
+            self_url: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn time_range_query_skips_unset_bounds() {
+        let since = time::macros::datetime!(2021-06-01 09:00 UTC);
+        assert_eq!(
+            time_range_query(Some(since), None),
+            vec![("since".to_owned(), "2021-06-01T09:00:00Z".to_owned())]
+        );
+        assert_eq!(time_range_query(None, None), Query::new());
+    }
+
+    #[test]
+    fn encodes_path_segments() {
+        assert_eq!(Segment("PXPGF42").to_string(), "PXPGF42");
+        assert_eq!(Segment("a/b?c#d e%").to_string(), "a%2Fb%3Fc%23d%20e%25");
+    }
+
+    #[test]
+    fn parses_error_bodies() {
+        let err = RestError::from_response(
+            400,
+            br#"{"error": {"message": "Invalid Input Provided", "code": 2001, "errors": ["Title cannot be empty."]}}"#,
+        );
+        assert_eq!(
+            err.to_string(),
+            "ApiError: 400 Invalid Input Provided (Title cannot be empty.)"
+        );
+        match err {
+            RestError::ApiError(400, Some(body)) => assert_eq!(body.code, Some(2001)),
+            _ => panic!("expected an API error"),
+        }
+
+        assert!(matches!(
+            RestError::from_response(502, b"<html>Bad Gateway</html>"),
+            RestError::ApiError(502, None)
+        ));
+    }
+}
@@ -0,0 +1,807 @@
+//! Users, how to reach them, and when.
+
+use crate::rest::incidents::Urgency;
+use crate::rest::schedules::OnCallFilter;
+use crate::rest::types::*;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserRole {
+    Owner,
+    Admin,
+    User,
+    LimitedUser,
+    Observer,
+    RestrictedAccess,
+    ReadOnlyUser,
+    ReadOnlyLimitedUser,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactMethodType {
+    /// References, as in a user without include[]=contact_methods, are read as the full type.
+    #[serde(
+        rename = "email_contact_method",
+        alias = "email_contact_method_reference"
+    )]
+    Email,
+    #[serde(
+        rename = "phone_contact_method",
+        alias = "phone_contact_method_reference"
+    )]
+    Phone,
+    #[serde(rename = "sms_contact_method", alias = "sms_contact_method_reference")]
+    Sms,
+    #[serde(
+        rename = "push_notification_contact_method",
+        alias = "push_notification_contact_method_reference"
+    )]
+    PushNotification,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ContactMethod {
+    /// None on contact methods yet to be created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub contact_method_type: ContactMethodType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+
+    /// e.g. "Work"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// The email address or phone number without country code; None on references.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+
+    /// Phone and SMS only, e.g. 1 for the US.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country_code: Option<u32>,
+
+    /// Email only: send an abbreviated email.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_short_email: Option<bool>,
+
+    /// Phone and SMS only: the number refused messages from PagerDuty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blacklisted: Option<bool>,
+
+    /// Push notifications only: "android" or "ios".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html_url: Option<String>,
+    #[serde(rename = "self", default, skip_serializing_if = "Option::is_none")]
+    pub self_url: Option<String>,
+}
+
+impl ContactMethod {
+    fn new(contact_method_type: ContactMethodType, label: String, address: String) -> Self {
+        ContactMethod {
+            id: None,
+            contact_method_type,
+            summary: None,
+            label: Some(label),
+            address: Some(address),
+            country_code: None,
+            send_short_email: None,
+            blacklisted: None,
+            device_type: None,
+            html_url: None,
+            self_url: None,
+        }
+    }
+
+    pub fn email(label: String, address: String) -> Self {
+        ContactMethod::new(ContactMethodType::Email, label, address)
+    }
+
+    pub fn phone(label: String, country_code: u32, number: String) -> Self {
+        ContactMethod {
+            country_code: Some(country_code),
+            ..ContactMethod::new(ContactMethodType::Phone, label, number)
+        }
+    }
+
+    pub fn sms(label: String, country_code: u32, number: String) -> Self {
+        ContactMethod {
+            country_code: Some(country_code),
+            ..ContactMethod::new(ContactMethodType::Sms, label, number)
+        }
+    }
+}
+
+/// Notify a contact method some minutes after the user is assigned an incident.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct NotificationRule {
+    /// None on rules yet to be created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// "assignment_notification_rule", or its reference.
+    #[serde(rename = "type")]
+    pub rule_type: String,
+
+    /// None on references, as in a user without include[]=notification_rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_delay_in_minutes: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact_method: Option<ContactMethod>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub urgency: Option<Urgency>,
+}
+
+impl NotificationRule {
+    pub fn new(
+        start_delay_in_minutes: u32,
+        contact_method_id: String,
+        contact_method_type: ContactMethodType,
+        urgency: Urgency,
+    ) -> Self {
+        NotificationRule {
+            id: None,
+            rule_type: "assignment_notification_rule".to_owned(),
+            start_delay_in_minutes: Some(start_delay_in_minutes),
+            contact_method: Some(ContactMethod {
+                id: Some(contact_method_id),
+                contact_method_type,
+                summary: None,
+                label: None,
+                address: None,
+                country_code: None,
+                send_short_email: None,
+                blacklisted: None,
+                device_type: None,
+                html_url: None,
+                self_url: None,
+            }),
+            urgency: Some(urgency),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct User {
+    pub id: String,
+    pub name: String,
+    pub email: String,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    #[serde(rename = "self", default)]
+    pub self_url: Option<String>,
+
+    /// e.g. "America/Lima"
+    #[serde(default)]
+    pub time_zone: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    pub role: UserRole,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub job_title: Option<String>,
+    #[serde(default)]
+    pub invitation_sent: bool,
+
+    /// Only on accounts with licenses.
+    #[serde(default)]
+    pub license: Option<Reference>,
+
+    /// Expanded by include[]=teams.
+    #[serde(default)]
+    pub teams: Vec<Reference>,
+
+    /// Expanded by include[]=contact_methods.
+    #[serde(default)]
+    pub contact_methods: Vec<ContactMethod>,
+
+    /// Expanded by include[]=notification_rules.
+    #[serde(default)]
+    pub notification_rules: Vec<NotificationRule>,
+}
+
+/// The fields of a user to create or update. Fields left None aren't sent, so an update
+/// changes only what's set.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct UserSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<UserRole>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_title: Option<String>,
+
+    /// The license to allocate, on accounts with licenses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<Reference>,
+}
+
+impl UserSpec {
+    /// The least a new user needs.
+    pub fn new(name: String, email: String) -> Self {
+        UserSpec {
+            name: Some(name),
+            email: Some(email),
+            ..Default::default()
+        }
+    }
+}
+
+/// Which users to list.
+#[derive(Debug, Clone, Default)]
+pub struct UserFilter {
+    /// Only users whose name or email contains this.
+    pub query: Option<String>,
+    pub team_ids: Vec<String>,
+
+    /// Expand references: "contact_methods", "notification_rules", "teams" or "subdomains".
+    pub include: Vec<String>,
+}
+
+impl UserFilter {
+    pub(crate) fn to_query(&self) -> Query {
+        let mut query: Query = self
+            .team_ids
+            .iter()
+            .map(|t| ("team_ids[]".to_owned(), t.clone()))
+            .collect();
+        query.extend(include_query(&self.include));
+        if let Some(q) = &self.query {
+            query.push(("query".to_owned(), q.clone()));
+        }
+        query
+    }
+}
+
+/// A license and the roles it allows.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct License {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub valid_roles: Vec<UserRole>,
+
+    /// e.g. "FullUser" or "Stakeholder"
+    #[serde(default)]
+    pub role_group: Option<String>,
+
+    /// Licenses bought, and those not yet allocated to users.
+    #[serde(default)]
+    pub current_value: Option<u32>,
+    #[serde(default)]
+    pub allocations_available: Option<u32>,
+}
+
+/// Match users to the emails, ignoring case. Emails without a user are left out.
+pub(crate) fn match_emails(users: Vec<User>, emails: &[String]) -> HashMap<String, User> {
+    let mut by_email: HashMap<String, User> = users
+        .into_iter()
+        .map(|u| (u.email.to_lowercase(), u))
+        .collect();
+    emails
+        .iter()
+        .filter_map(|e| by_email.remove(&e.to_lowercase()).map(|u| (e.clone(), u)))
+        .collect()
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UserList {
+    users: Vec<User>,
+    #[serde(flatten)]
+    pagination: Pagination,
+}
+
+impl Page for UserList {
+    type Item = User;
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn into_items(self) -> Vec<User> {
+        self.users
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct UserWrapper<T> {
+    user: T,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ContactMethodWrapper<T> {
+    contact_method: T,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ContactMethodsWrapper {
+    contact_methods: Vec<ContactMethod>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct NotificationRuleWrapper<T> {
+    notification_rule: T,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NotificationRulesWrapper {
+    notification_rules: Vec<NotificationRule>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LicenseWrapper {
+    license: License,
+}
+
+#[cfg(feature = "sync")]
+impl crate::rest::restsync::RestClient {
+    /// List every user matching the filter, fetching all pages.
+    pub fn list_users(&self, filter: &UserFilter) -> RestResult<Vec<User>> {
+        self.get_all::<UserList>("/users", &filter.to_query())
+    }
+
+    /// Look up many users by email with one listing of all users, keyed by the emails given.
+    pub fn find_users_by_email(&self, emails: &[String]) -> RestResult<HashMap<String, User>> {
+        Ok(match_emails(
+            self.list_users(&UserFilter::default())?,
+            emails,
+        ))
+    }
+
+    /// Get a user, expanding the references in `include`.
+    pub fn get_user(&self, id: &str, include: &[String]) -> RestResult<User> {
+        let wrapper: UserWrapper<User> =
+            self.get(&format!("/users/{}", Segment(id)), &include_query(include))?;
+        Ok(wrapper.user)
+    }
+
+    /// The user whose OAuth token the client uses; API tokens have no user.
+    pub fn get_current_user(&self, include: &[String]) -> RestResult<User> {
+        let wrapper: UserWrapper<User> = self.get("/users/me", &include_query(include))?;
+        Ok(wrapper.user)
+    }
+
+    pub fn create_user(&self, user: &UserSpec) -> RestResult<User> {
+        let wrapper: UserWrapper<User> = self.post("/users", &UserWrapper { user })?;
+        Ok(wrapper.user)
+    }
+
+    pub fn update_user(&self, id: &str, user: &UserSpec) -> RestResult<User> {
+        let wrapper: UserWrapper<User> =
+            self.put(&format!("/users/{}", Segment(id)), &UserWrapper { user })?;
+        Ok(wrapper.user)
+    }
+
+    /// Fails while the user is the only target of an escalation rule, or has open incidents.
+    pub fn delete_user(&self, id: &str) -> RestResult<()> {
+        self.delete(&format!("/users/{}", Segment(id)))
+    }
+
+    pub fn get_user_license(&self, id: &str) -> RestResult<License> {
+        let wrapper: LicenseWrapper =
+            self.get(&format!("/users/{}/license", Segment(id)), &Query::new())?;
+        Ok(wrapper.license)
+    }
+
+    pub fn list_contact_methods(&self, user_id: &str) -> RestResult<Vec<ContactMethod>> {
+        let wrapper: ContactMethodsWrapper = self.get(
+            &format!("/users/{}/contact_methods", Segment(user_id)),
+            &Query::new(),
+        )?;
+        Ok(wrapper.contact_methods)
+    }
+
+    pub fn create_contact_method(
+        &self,
+        user_id: &str,
+        contact_method: &ContactMethod,
+    ) -> RestResult<ContactMethod> {
+        let wrapper: ContactMethodWrapper<ContactMethod> = self.post(
+            &format!("/users/{}/contact_methods", Segment(user_id)),
+            &ContactMethodWrapper { contact_method },
+        )?;
+        Ok(wrapper.contact_method)
+    }
+
+    pub fn update_contact_method(
+        &self,
+        user_id: &str,
+        id: &str,
+        contact_method: &ContactMethod,
+    ) -> RestResult<ContactMethod> {
+        let wrapper: ContactMethodWrapper<ContactMethod> = self.put(
+            &format!(
+                "/users/{}/contact_methods/{}",
+                Segment(user_id),
+                Segment(id)
+            ),
+            &ContactMethodWrapper { contact_method },
+        )?;
+        Ok(wrapper.contact_method)
+    }
+
+    pub fn delete_contact_method(&self, user_id: &str, id: &str) -> RestResult<()> {
+        self.delete(&format!(
+            "/users/{}/contact_methods/{}",
+            Segment(user_id),
+            Segment(id)
+        ))
+    }
+
+    /// List the user's notification rules, for one urgency or (None) all.
+    pub fn list_notification_rules(
+        &self,
+        user_id: &str,
+        urgency: Option<Urgency>,
+    ) -> RestResult<Vec<NotificationRule>> {
+        let mut query = vec![("include[]".to_owned(), "contact_methods".to_owned())];
+        query.push((
+            "urgency".to_owned(),
+            urgency.map_or("any", |u| u.as_str()).to_owned(),
+        ));
+        let wrapper: NotificationRulesWrapper = self.get(
+            &format!("/users/{}/notification_rules", Segment(user_id)),
+            &query,
+        )?;
+        Ok(wrapper.notification_rules)
+    }
+
+    pub fn create_notification_rule(
+        &self,
+        user_id: &str,
+        notification_rule: &NotificationRule,
+    ) -> RestResult<NotificationRule> {
+        let wrapper: NotificationRuleWrapper<NotificationRule> = self.post(
+            &format!("/users/{}/notification_rules", Segment(user_id)),
+            &NotificationRuleWrapper { notification_rule },
+        )?;
+        Ok(wrapper.notification_rule)
+    }
+
+    pub fn update_notification_rule(
+        &self,
+        user_id: &str,
+        id: &str,
+        notification_rule: &NotificationRule,
+    ) -> RestResult<NotificationRule> {
+        let wrapper: NotificationRuleWrapper<NotificationRule> = self.put(
+            &format!(
+                "/users/{}/notification_rules/{}",
+                Segment(user_id),
+                Segment(id)
+            ),
+            &NotificationRuleWrapper { notification_rule },
+        )?;
+        Ok(wrapper.notification_rule)
+    }
+
+    pub fn delete_notification_rule(&self, user_id: &str, id: &str) -> RestResult<()> {
+        self.delete(&format!(
+            "/users/{}/notification_rules/{}",
+            Segment(user_id),
+            Segment(id)
+        ))
+    }
+
+    /// The user's on-call shifts matching the filter, whose user_ids are replaced by the user's.
+    pub fn list_user_oncalls(
+        &self,
+        user_id: &str,
+        filter: &OnCallFilter,
+    ) -> RestResult<Vec<crate::rest::schedules::OnCall>> {
+        let filter = OnCallFilter {
+            user_ids: vec![user_id.to_owned()],
+            ..filter.clone()
+        };
+        self.list_oncalls(&filter)
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::rest::restasync::RestClient {
+    /// List every user matching the filter, fetching all pages.
+    pub async fn list_users(&self, filter: &UserFilter) -> RestResult<Vec<User>> {
+        self.get_all::<UserList>("/users", &filter.to_query()).await
+    }
+
+    /// Look up many users by email with one listing of all users, keyed by the emails given.
+    pub async fn find_users_by_email(
+        &self,
+        emails: &[String],
+    ) -> RestResult<HashMap<String, User>> {
+        Ok(match_emails(
+            self.list_users(&UserFilter::default()).await?,
+            emails,
+        ))
+    }
+
+    /// Get a user, expanding the references in `include`.
+    pub async fn get_user(&self, id: &str, include: &[String]) -> RestResult<User> {
+        let wrapper: UserWrapper<User> = self
+            .get(&format!("/users/{}", Segment(id)), &include_query(include))
+            .await?;
+        Ok(wrapper.user)
+    }
+
+    /// The user whose OAuth token the client uses; API tokens have no user.
+    pub async fn get_current_user(&self, include: &[String]) -> RestResult<User> {
+        let wrapper: UserWrapper<User> = self.get("/users/me", &include_query(include)).await?;
+        Ok(wrapper.user)
+    }
+
+    pub async fn create_user(&self, user: &UserSpec) -> RestResult<User> {
+        let wrapper: UserWrapper<User> = self.post("/users", &UserWrapper { user }).await?;
+        Ok(wrapper.user)
+    }
+
+    pub async fn update_user(&self, id: &str, user: &UserSpec) -> RestResult<User> {
+        let wrapper: UserWrapper<User> = self
+            .put(&format!("/users/{}", Segment(id)), &UserWrapper { user })
+            .await?;
+        Ok(wrapper.user)
+    }
+
+    /// Fails while the user is the only target of an escalation rule, or has open incidents.
+    pub async fn delete_user(&self, id: &str) -> RestResult<()> {
+        self.delete(&format!("/users/{}", Segment(id))).await
+    }
+
+    pub async fn get_user_license(&self, id: &str) -> RestResult<License> {
+        let wrapper: LicenseWrapper = self
+            .get(&format!("/users/{}/license", Segment(id)), &Query::new())
+            .await?;
+        Ok(wrapper.license)
+    }
+
+    pub async fn list_contact_methods(&self, user_id: &str) -> RestResult<Vec<ContactMethod>> {
+        let wrapper: ContactMethodsWrapper = self
+            .get(
+                &format!("/users/{}/contact_methods", Segment(user_id)),
+                &Query::new(),
+            )
+            .await?;
+        Ok(wrapper.contact_methods)
+    }
+
+    pub async fn create_contact_method(
+        &self,
+        user_id: &str,
+        contact_method: &ContactMethod,
+    ) -> RestResult<ContactMethod> {
+        let wrapper: ContactMethodWrapper<ContactMethod> = self
+            .post(
+                &format!("/users/{}/contact_methods", Segment(user_id)),
+                &ContactMethodWrapper { contact_method },
+            )
+            .await?;
+        Ok(wrapper.contact_method)
+    }
+
+    pub async fn update_contact_method(
+        &self,
+        user_id: &str,
+        id: &str,
+        contact_method: &ContactMethod,
+    ) -> RestResult<ContactMethod> {
+        let wrapper: ContactMethodWrapper<ContactMethod> = self
+            .put(
+                &format!(
+                    "/users/{}/contact_methods/{}",
+                    Segment(user_id),
+                    Segment(id)
+                ),
+                &ContactMethodWrapper { contact_method },
+            )
+            .await?;
+        Ok(wrapper.contact_method)
+    }
+
+    pub async fn delete_contact_method(&self, user_id: &str, id: &str) -> RestResult<()> {
+        self.delete(&format!(
+            "/users/{}/contact_methods/{}",
+            Segment(user_id),
+            Segment(id)
+        ))
+        .await
+    }
+
+    /// List the user's notification rules, for one urgency or (None) all.
+    pub async fn list_notification_rules(
+        &self,
+        user_id: &str,
+        urgency: Option<Urgency>,
+    ) -> RestResult<Vec<NotificationRule>> {
+        let mut query = vec![("include[]".to_owned(), "contact_methods".to_owned())];
+        query.push((
+            "urgency".to_owned(),
+            urgency.map_or("any", |u| u.as_str()).to_owned(),
+        ));
+        let wrapper: NotificationRulesWrapper = self
+            .get(
+                &format!("/users/{}/notification_rules", Segment(user_id)),
+                &query,
+            )
+            .await?;
+        Ok(wrapper.notification_rules)
+    }
+
+    pub async fn create_notification_rule(
+        &self,
+        user_id: &str,
+        notification_rule: &NotificationRule,
+    ) -> RestResult<NotificationRule> {
+        let wrapper: NotificationRuleWrapper<NotificationRule> = self
+            .post(
+                &format!("/users/{}/notification_rules", Segment(user_id)),
+                &NotificationRuleWrapper { notification_rule },
+            )
+            .await?;
+        Ok(wrapper.notification_rule)
+    }
+
+    pub async fn update_notification_rule(
+        &self,
+        user_id: &str,
+        id: &str,
+        notification_rule: &NotificationRule,
+    ) -> RestResult<NotificationRule> {
+        let wrapper: NotificationRuleWrapper<NotificationRule> = self
+            .put(
+                &format!(
+                    "/users/{}/notification_rules/{}",
+                    Segment(user_id),
+                    Segment(id)
+                ),
+                &NotificationRuleWrapper { notification_rule },
+            )
+            .await?;
+        Ok(wrapper.notification_rule)
+    }
+
+    pub async fn delete_notification_rule(&self, user_id: &str, id: &str) -> RestResult<()> {
+        self.delete(&format!(
+            "/users/{}/notification_rules/{}",
+            Segment(user_id),
+            Segment(id)
+        ))
+        .await
+    }
+
+    /// The user's on-call shifts matching the filter, whose user_ids are replaced by the user's.
+    pub async fn list_user_oncalls(
+        &self,
+        user_id: &str,
+        filter: &OnCallFilter,
+    ) -> RestResult<Vec<crate::rest::schedules::OnCall>> {
+        let filter = OnCallFilter {
+            user_ids: vec![user_id.to_owned()],
+            ..filter.clone()
+        };
+        self.list_oncalls(&filter).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn user(id: &str, email: &str) -> User {
+        serde_json::from_value(json!({
+            "id": id,
+            "type": "user",
+            "name": "Earline Greenholt",
+            "email": email,
+            "role": "admin"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn deserializes_user_with_references_and_expansions() {
+        let user: User = serde_json::from_value(json!({
+            "id": "PXPGF42",
+            "type": "user",
+            "name": "Earline Greenholt",
+            "email": "125.greenholt.earline@graham.name",
+            "time_zone": "America/Lima",
+            "role": "read_only_limited_user",
+            "invitation_sent": true,
+            "license": {"id": "PTNVJG3", "type": "license_reference"},
+            "contact_methods": [
+                {"id": "PTDVERC", "type": "email_contact_method_reference", "summary": "Default"},
+                {
+                    "id": "PDZ8UWA",
+                    "type": "sms_contact_method",
+                    "label": "Mobile",
+                    "address": "5555555555",
+                    "country_code": 1,
+                    "blacklisted": false
+                }
+            ],
+            "notification_rules": [{"id": "P8GRWKK", "type": "assignment_notification_rule_reference"}]
+        }))
+        .unwrap();
+
+        assert_eq!(user.role, UserRole::ReadOnlyLimitedUser);
+        assert_eq!(
+            user.contact_methods[0].contact_method_type,
+            ContactMethodType::Email
+        );
+        assert_eq!(user.contact_methods[0].address, None);
+        assert_eq!(user.contact_methods[1].country_code, Some(1));
+        assert_eq!(user.notification_rules[0].start_delay_in_minutes, None);
+    }
+
+    #[test]
+    fn serializes_contact_methods_and_rules() {
+        assert_eq!(
+            serde_json::to_value(ContactMethod::phone(
+                "Mobile".to_owned(),
+                1,
+                "5555555555".to_owned()
+            ))
+            .unwrap(),
+            json!({
+                "type": "phone_contact_method",
+                "label": "Mobile",
+                "address": "5555555555",
+                "country_code": 1
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(NotificationRule::new(
+                5,
+                "PDZ8UWA".to_owned(),
+                ContactMethodType::Sms,
+                Urgency::High
+            ))
+            .unwrap(),
+            json!({
+                "type": "assignment_notification_rule",
+                "start_delay_in_minutes": 5,
+                "contact_method": {"id": "PDZ8UWA", "type": "sms_contact_method"},
+                "urgency": "high"
+            })
+        );
+    }
+
+    #[test]
+    fn matches_emails_ignoring_case() {
+        let matched = match_emails(
+            vec![
+                user("P1", "Jane@Example.com"),
+                user("P2", "joe@example.com"),
+            ],
+            &[
+                "jane@example.com".to_owned(),
+                "nobody@example.com".to_owned(),
+            ],
+        );
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched["jane@example.com"].id, "P1");
+    }
+}
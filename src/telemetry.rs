@@ -0,0 +1,345 @@
+//! Hooks for the optional tracing and metrics instrumentation.
+//! Without either feature these compile down to nothing, and the arguments only
+//! a disabled feature records go unused.
+
+use crate::types::*;
+
+use serde::Serialize;
+use std::fmt::Display;
+use std::time::Duration;
+
+/// The Events API action an event is sent as.
+pub fn event_action<T: Serialize>(event: &Event<T>) -> &'static str {
+    match event {
+        Event::Change(_) => "change",
+        Event::AlertTrigger(_) => "trigger",
+        Event::AlertAcknowledge(_) => "acknowledge",
+        Event::AlertResolve(_) => "resolve",
+    }
+}
+
+/// The severity of a trigger, or "none" for every other kind of event.
+pub fn event_severity<T: Serialize>(event: &Event<T>) -> &'static str {
+    match event {
+        Event::AlertTrigger(at) => match at.payload.severity {
+            Severity::Critical => "critical",
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        },
+        _ => "none",
+    }
+}
+
+/// Only the first four characters of a routing key are safe to record.
+#[cfg(feature = "tracing")]
+pub fn redact(routing_key: &str) -> String {
+    let prefix: String = routing_key.chars().take(4).collect();
+    format!("{}****", prefix)
+}
+
+/// The kind and severity of an event, which label its metrics.
+#[derive(Clone, Copy)]
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+pub struct EventLabels {
+    kind: &'static str,
+    severity: &'static str,
+}
+
+impl EventLabels {
+    pub fn of<T: Serialize>(event: &Event<T>) -> Self {
+        EventLabels {
+            kind: event_action(event),
+            severity: event_severity(event),
+        }
+    }
+}
+
+/// An event is about to be posted to PagerDuty.
+pub fn event_sent(
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))] labels: EventLabels,
+) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("pagerduty_events_sent_total", "kind" => labels.kind, "severity" => labels.severity)
+        .increment(1);
+}
+
+/// PagerDuty accepted (or didn't accept) an event.
+pub fn event_outcome(
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))] labels: EventLabels,
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))] accepted: bool,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        let name = if accepted {
+            "pagerduty_events_accepted_total"
+        } else {
+            "pagerduty_events_rejected_total"
+        };
+        metrics::counter!(name, "kind" => labels.kind, "severity" => labels.severity).increment(1);
+    }
+}
+
+/// A queue overflowed, and an event was dropped.
+pub fn event_dropped(
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))] labels: EventLabels,
+) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("pagerduty_events_dropped_total", "kind" => labels.kind, "severity" => labels.severity)
+        .increment(1);
+}
+
+/// Record the response to a post.
+pub fn record_response(
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))] status: u16,
+    #[cfg_attr(
+        not(any(feature = "tracing", feature = "metrics")),
+        allow(unused_variables)
+    )]
+    latency: Duration,
+) {
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::Span::current();
+        span.record("http.status", status);
+        span.record("latency_ms", latency.as_millis() as u64);
+    }
+
+    #[cfg(feature = "metrics")]
+    metrics::histogram!("pagerduty_post_duration_seconds").record(latency.as_secs_f64());
+}
+
+/// A post failed, either with an error response or without reaching PagerDuty at all.
+pub fn post_failed(
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))] err: &dyn Display,
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))] latency: Duration,
+) {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::Span::current().record("latency_ms", latency.as_millis() as u64);
+        tracing::error!(error = %err, "failed to post event to PagerDuty");
+    }
+}
+
+/// The circuit breaker is open, and an event was handed to its fallback.
+pub fn circuit_open(
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))] url: &str,
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))] labels: EventLabels,
+) {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::Span::current().record("fallback", true);
+        tracing::warn!(url, "circuit breaker open, handing event to fallback");
+    }
+
+    #[cfg(feature = "metrics")]
+    metrics::counter!("pagerduty_events_fallback_total", "kind" => labels.kind, "severity" => labels.severity)
+        .increment(1);
+}
+
+#[cfg(all(test, feature = "sync", any(feature = "tracing", feature = "metrics")))]
+mod test {
+    use crate::eventsv2sync::*;
+    use crate::standin;
+    use crate::types::*;
+    use pretty_assertions::assert_eq;
+
+    fn trigger(severity: Severity) -> Event<()> {
+        Event::AlertTrigger(AlertTrigger {
+            payload: AlertTriggerPayload {
+                severity,
+                summary: "disk full".to_owned(),
+                source: "db-1".to_owned(),
+                timestamp: None,
+                component: None,
+                group: None,
+                class: None,
+                custom_details: None,
+            },
+            dedup_key: Some("disk:db-1".to_owned()),
+            images: None,
+            links: None,
+            client: None,
+            client_url: None,
+        })
+    }
+
+    #[cfg(feature = "tracing")]
+    mod spans {
+        use std::collections::HashMap;
+        use std::fmt::Debug;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::Subscriber;
+        use tracing_subscriber::layer::Context;
+        use tracing_subscriber::registry::LookupSpan;
+        use tracing_subscriber::Layer;
+
+        /// The fields recorded on each span, by span name.
+        #[derive(Clone, Default)]
+        pub struct Spans(pub Arc<Mutex<HashMap<&'static str, HashMap<String, String>>>>);
+
+        struct Fields<'a>(&'a mut HashMap<String, String>);
+
+        impl Visit for Fields<'_> {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                self.0.insert(field.name().to_owned(), value.to_owned());
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.0
+                    .insert(field.name().to_owned(), format!("{:?}", value));
+            }
+        }
+
+        impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Spans {
+            fn on_new_span(&self, attrs: &Attributes, _: &Id, _: Context<S>) {
+                let mut spans = self.0.lock().unwrap();
+                attrs.record(&mut Fields(
+                    spans.entry(attrs.metadata().name()).or_default(),
+                ));
+            }
+
+            fn on_record(&self, id: &Id, values: &Record, ctx: Context<S>) {
+                let name = ctx.span(id).unwrap().name();
+                let mut spans = self.0.lock().unwrap();
+                values.record(&mut Fields(spans.entry(name).or_default()));
+            }
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn records_spans() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let (url, _) = standin::events_api(202);
+        let ev2 = EventsV2::new("R0UTINGKEY".to_owned(), None)
+            .unwrap()
+            .with_base_url(url.clone());
+        let spans = spans::Spans::default();
+        let subscriber = tracing_subscriber::registry().with(spans.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            ev2.event(trigger(Severity::Critical)).unwrap();
+        });
+
+        let spans = spans.0.lock().unwrap();
+        let event = &spans["pagerduty.event"];
+        assert_eq!(event["routing_key"], "R0UT****");
+        assert_eq!(event["event_action"], "trigger");
+        assert_eq!(event["dedup_key"], "disk:db-1");
+
+        let post = &spans["pagerduty.do_post"];
+        assert_eq!(post["url"], format!("{}/v2/enqueue", url));
+        assert_eq!(post["http.status"], "202");
+        assert!(post.contains_key("latency_ms"));
+        assert!(!post.contains_key("fallback"));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn records_metrics() {
+        use crate::circuitbreaker::*;
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+        use std::sync::Mutex;
+
+        let (accepting, _) = standin::events_api(202);
+        let accepting = EventsV2::new("key".to_owned(), None)
+            .unwrap()
+            .with_base_url(accepting);
+        let (failing, _) = standin::events_api(500);
+        let failing = EventsV2::new("key".to_owned(), None)
+            .unwrap()
+            .with_base_url(failing)
+            .with_circuit_breaker(CircuitBreaker::new(
+                CircuitBreakerConfig {
+                    failure_threshold: 1,
+                    ..CircuitBreakerConfig::default()
+                },
+                Fallback::LogSink(Mutex::new(Box::new(std::io::sink()))),
+            ));
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            accepting.event(trigger(Severity::Critical)).unwrap();
+            assert!(failing.event(trigger(Severity::Warning)).is_err());
+            // The failure opened the circuit, so this one goes to the fallback.
+            failing
+                .event::<()>(Event::AlertResolve(AlertResolve {
+                    dedup_key: "disk:db-1".to_owned(),
+                }))
+                .unwrap();
+        });
+
+        let mut metrics: Vec<(String, Vec<String>, DebugValue)> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels = key
+                    .labels()
+                    .map(|l| format!("{}={}", l.key(), l.value()))
+                    .collect();
+                (key.name().to_owned(), labels, value)
+            })
+            .collect();
+        metrics.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+
+        let counters: Vec<(&str, Vec<&str>, u64)> = metrics
+            .iter()
+            .filter_map(|(name, labels, value)| match value {
+                DebugValue::Counter(n) => Some((
+                    name.as_str(),
+                    labels.iter().map(|l| l.as_str()).collect(),
+                    *n,
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            counters,
+            vec![
+                (
+                    "pagerduty_events_accepted_total",
+                    vec!["kind=trigger", "severity=critical"],
+                    1
+                ),
+                (
+                    "pagerduty_events_fallback_total",
+                    vec!["kind=resolve", "severity=none"],
+                    1
+                ),
+                (
+                    "pagerduty_events_rejected_total",
+                    vec!["kind=trigger", "severity=warning"],
+                    1
+                ),
+                (
+                    "pagerduty_events_sent_total",
+                    vec!["kind=trigger", "severity=critical"],
+                    1
+                ),
+                (
+                    "pagerduty_events_sent_total",
+                    vec!["kind=trigger", "severity=warning"],
+                    1
+                ),
+            ]
+        );
+
+        // One latency for each response, fallbacks excluded.
+        match metrics
+            .iter()
+            .find(|(name, _, _)| name == "pagerduty_post_duration_seconds")
+        {
+            Some((_, labels, DebugValue::Histogram(latencies))) => {
+                assert!(labels.is_empty());
+                assert_eq!(latencies.len(), 2);
+            }
+            _ => panic!("expected a latency histogram"),
+        }
+    }
+}
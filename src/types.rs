@@ -1,8 +1,11 @@
-use serde::{ser::Error as SerializeError, Serialize, Serializer};
+use serde::{
+    de::Error as DeserializeError, ser::Error as SerializeError, Deserialize, Deserializer,
+    Serialize, Serializer,
+};
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 /// Indicates the severity of the impact to the affected system.
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum Severity {
     Info,
@@ -11,7 +14,23 @@ pub enum Severity {
     Critical,
 }
 
-#[derive(Serialize)]
+impl Severity {
+    /// Parse the severity names monitoring tools commonly put in labels and tags,
+    /// including Prometheus' page/ticket convention.
+    pub fn from_label(label: &str) -> Option<Severity> {
+        match label.to_lowercase().as_str() {
+            "critical" | "crit" | "page" | "emergency" | "alert" | "fatal" => {
+                Some(Severity::Critical)
+            }
+            "error" | "err" | "high" => Some(Severity::Error),
+            "warning" | "warn" | "ticket" | "medium" => Some(Severity::Warning),
+            "info" | "informational" | "notice" | "low" | "none" => Some(Severity::Info),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Link {
     /// URL of the link to be attached.
     pub href: String,
@@ -177,7 +196,65 @@ pub enum Event<T: Serialize> {
     AlertResolve(AlertResolve),
 }
 
-fn optional_datetime_to_iso8601<S>(
+impl<T: Serialize> Event<T> {
+    /// The deduplication key this event correlates on, if any.
+    pub fn dedup_key(&self) -> Option<&str> {
+        match self {
+            Event::Change(_) => None,
+            Event::AlertTrigger(at) => at.dedup_key.as_deref(),
+            Event::AlertAcknowledge(aa) => Some(aa.dedup_key.as_str()),
+            Event::AlertResolve(ar) => Some(ar.dedup_key.as_str()),
+        }
+    }
+}
+
+/// The maximum permitted length of a summary, in characters.
+const MAX_SUMMARY_LEN: usize = 1024;
+
+/// The maximum permitted length of a dedup key, in characters.
+const MAX_DEDUP_KEY_LEN: usize = 255;
+
+/// Cut a summary to the length PagerDuty accepts.
+pub(crate) fn truncate_summary(summary: &str) -> String {
+    summary.chars().take(MAX_SUMMARY_LEN).collect()
+}
+
+/// Cut a dedup key to the length PagerDuty accepts.
+pub(crate) fn truncate_dedup_key(dedup_key: &str) -> String {
+    dedup_key.chars().take(MAX_DEDUP_KEY_LEN).collect()
+}
+
+/// Fit a dedup key that must stay unique to the length PagerDuty accepts. Over the limit, the
+/// tail is replaced with a hash of the whole key, so long keys sharing a prefix don't collide.
+#[cfg(any(feature = "alertmanager", feature = "cloudwatch"))]
+pub(crate) fn hash_dedup_key(dedup_key: &str) -> String {
+    if dedup_key.chars().count() <= MAX_DEDUP_KEY_LEN {
+        return dedup_key.to_owned();
+    }
+
+    let hash = format!("~{:016x}", fingerprint([dedup_key]));
+    let mut key: String = dedup_key
+        .chars()
+        .take(MAX_DEDUP_KEY_LEN - hash.len())
+        .collect();
+    key.push_str(&hash);
+    key
+}
+
+/// A stable 64-bit FNV-1a hash of some strings, for keys that must be the same on every
+/// run and every platform (std's hashers are neither).
+pub(crate) fn fingerprint<'a>(parts: impl IntoIterator<Item = &'a str>) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in part.bytes().chain(std::iter::once(0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+pub(crate) fn optional_datetime_to_iso8601<S>(
     od: &Option<OffsetDateTime>,
     serializer: S,
 ) -> Result<S::Ok, S::Error>
@@ -190,8 +267,7 @@ where
     }
 }
 
-// This suggestion
-fn datetime_to_iso8601<S>(d: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error>
+pub(crate) fn datetime_to_iso8601<S>(d: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
@@ -201,6 +277,28 @@ where
     }
 }
 
+pub(crate) fn optional_iso8601_to_datetime<'de, D>(
+    deserializer: D,
+) -> Result<Option<OffsetDateTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(s) => OffsetDateTime::parse(&s, &Rfc3339)
+            .map(Some)
+            .map_err(DeserializeError::custom),
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn iso8601_to_datetime<'de, D>(deserializer: D) -> Result<OffsetDateTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    OffsetDateTime::parse(&s, &Rfc3339).map_err(DeserializeError::custom)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
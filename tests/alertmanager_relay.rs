@@ -0,0 +1,144 @@
+#[cfg(all(feature = "alertmanager", feature = "sync"))]
+mod relaytest {
+    use serde_json::Value;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::process::{Child, Command};
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    const FIRING: &str = r#"{
+        "version": "4",
+        "groupKey": "{}:{alertname=\"DiskFull\"}",
+        "status": "firing",
+        "receiver": "pagerduty",
+        "groupLabels": {"alertname": "DiskFull"},
+        "commonLabels": {"alertname": "DiskFull", "severity": "warning"},
+        "commonAnnotations": {"summary": "Disk almost full"},
+        "externalURL": "http://alertmanager:9093",
+        "alerts": [{
+            "status": "firing",
+            "labels": {"alertname": "DiskFull", "severity": "warning"},
+            "annotations": {},
+            "startsAt": "2021-05-30T00:00:00Z",
+            "generatorURL": "http://prometheus:9090/graph"
+        }]
+    }"#;
+
+    /// Read one HTTP request, returning its first line and body.
+    fn read_request(stream: &mut TcpStream) -> (String, Vec<u8>) {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        (request_line, body)
+    }
+
+    /// A stand-in for events.pagerduty.com that accepts everything and hands back what it got.
+    fn events_api_standin() -> (String, Receiver<(String, Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let (request_line, body) = read_request(&mut stream);
+                stream
+                    .write_all(
+                        b"HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    )
+                    .unwrap();
+                let _ = tx.send((request_line, serde_json::from_slice(&body).unwrap()));
+            }
+        });
+        (url, rx)
+    }
+
+    fn post(addr: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST /webhook HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            addr,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    struct Relay(Child);
+
+    impl Drop for Relay {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn start_relay(events_url: &str) -> (Relay, String) {
+        let addr = {
+            let l = TcpListener::bind("127.0.0.1:0").unwrap();
+            l.local_addr().unwrap().to_string()
+        };
+        let child = Command::new(env!("CARGO_BIN_EXE_alertmanager-relay"))
+            .env("PAGERDUTY_ROUTING_KEY", "routingkey")
+            .env("PAGERDUTY_EVENTS_URL", events_url)
+            .env("RELAY_LISTEN", &addr)
+            .env("NO_PROXY", "127.0.0.1")
+            .spawn()
+            .unwrap();
+        let relay = Relay(child);
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while TcpStream::connect(&addr).is_err() {
+            assert!(Instant::now() < deadline, "relay didn't start");
+            thread::sleep(Duration::from_millis(50));
+        }
+        (relay, addr)
+    }
+
+    #[test]
+    fn forwards_firing_and_resolved_groups() {
+        let (events_url, received) = events_api_standin();
+        let (_relay, addr) = start_relay(&events_url);
+
+        let response = post(&addr, FIRING);
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        let (request_line, trigger) = received.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(request_line.trim(), "POST /v2/enqueue HTTP/1.1");
+        assert_eq!(trigger["routing_key"], "routingkey");
+        assert_eq!(trigger["event_action"], "trigger");
+        assert_eq!(trigger["dedup_key"], "{}:{alertname=\"DiskFull\"}");
+        assert_eq!(trigger["payload"]["severity"], "warning");
+        assert_eq!(trigger["payload"]["summary"], "Disk almost full");
+        assert_eq!(trigger["links"][0]["href"], "http://prometheus:9090/graph");
+
+        let response = post(&addr, &FIRING.replace("\"firing\"", "\"resolved\""));
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        let (_, resolve) = received.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(resolve["event_action"], "resolve");
+        assert_eq!(resolve["dedup_key"], "{}:{alertname=\"DiskFull\"}");
+
+        let response = post(&addr, "not json");
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+    }
+}
@@ -0,0 +1,555 @@
+#![cfg(any(feature = "sync", feature = "async"))]
+
+mod resttest {
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread;
+    use std::time::Duration;
+
+    /// A request as the REST API stand-in received it.
+    pub struct Request {
+        /// e.g. "GET /incidents?statuses%5B%5D=triggered"
+        pub target: String,
+        pub headers: HashMap<String, String>,
+        pub body: Value,
+    }
+
+    impl Request {
+        pub fn header(&self, name: &str) -> Option<&str> {
+            self.headers.get(&name.to_lowercase()).map(|v| v.as_str())
+        }
+    }
+
+    fn read_request(stream: &mut TcpStream) -> Request {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut parts = request_line.split_whitespace();
+        let target = format!("{} {}", parts.next().unwrap(), parts.next().unwrap());
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.to_lowercase(), value.trim().to_owned());
+            }
+        }
+
+        let content_length = headers
+            .get("content-length")
+            .map(|l| l.parse().unwrap())
+            .unwrap_or(0);
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        Request {
+            target,
+            headers,
+            body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+        }
+    }
+
+    /// A stand-in for api.pagerduty.com that answers requests with the given responses,
+    /// in order, and hands back the requests it got.
+    pub fn standin(responses: Vec<(u16, Value)>) -> (String, Receiver<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let request = read_request(&mut stream);
+                let body = body.to_string();
+                write!(
+                    stream,
+                    "HTTP/1.1 {} Whatever\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                let _ = tx.send(request);
+            }
+        });
+        (url, rx)
+    }
+
+    pub fn received(rx: &Receiver<Request>) -> Request {
+        rx.recv_timeout(Duration::from_secs(10)).unwrap()
+    }
+
+    #[cfg(feature = "sync")]
+    mod sync {
+        use super::*;
+        use pagerduty_rs::rest::restsync::*;
+        use pagerduty_rs::rest::types::*;
+
+        fn client(url: &str) -> RestClient {
+            RestClient::new(Auth::Token("secret".to_owned()), None)
+                .unwrap()
+                .with_base_url(url.to_owned())
+                .with_from("jane@example.com".to_owned())
+                .unwrap()
+        }
+
+        #[test]
+        fn sends_headers() {
+            let (url, requests) = standin(vec![
+                (200, json!({"abilities": ["sso"]})),
+                (201, json!({"ok": true})),
+            ]);
+            let client = client(&url);
+
+            let _: Value = client.get("/abilities", &vec![]).unwrap();
+            let request = received(&requests);
+            assert_eq!(request.target, "GET /abilities");
+            assert_eq!(
+                request.header("Accept"),
+                Some("application/vnd.pagerduty+json;version=2")
+            );
+            assert_eq!(request.header("Authorization"), Some("Token token=secret"));
+            assert_eq!(request.header("From"), None);
+
+            let _: Value = client.post("/things", &json!({"a": 1})).unwrap();
+            let request = received(&requests);
+            assert_eq!(request.target, "POST /things");
+            assert_eq!(request.header("From"), Some("jane@example.com"));
+            assert_eq!(request.body, json!({"a": 1}));
+        }
+
+        fn incident(id: &str, status: &str) -> Value {
+            json!({
+                "id": id,
+                "type": "incident",
+                "incident_number": 1,
+                "title": "The server is on fire.",
+                "created_at": "2015-10-06T21:30:42Z",
+                "status": status,
+                "service": {"id": "PIJ90N7", "type": "service_reference"},
+                "urgency": "high"
+            })
+        }
+
+        #[test]
+        fn lists_every_page_of_incidents() {
+            use pagerduty_rs::rest::incidents::*;
+
+            let (url, requests) = standin(vec![
+                (
+                    200,
+                    json!({"incidents": [incident("P1", "triggered")], "offset": 0, "limit": 1, "more": true}),
+                ),
+                (
+                    200,
+                    json!({"incidents": [incident("P2", "triggered")], "offset": 1, "limit": 1, "more": false}),
+                ),
+                (200, json!({"incidents": [incident("P1", "acknowledged")]})),
+            ]);
+            let client = client(&url);
+
+            let filter = IncidentFilter {
+                statuses: vec![IncidentStatus::Triggered],
+                ..Default::default()
+            };
+            let incidents = client.list_incidents(&filter).unwrap();
+            assert_eq!(
+                incidents
+                    .iter()
+                    .map(|i| i.id.as_str())
+                    .collect::<Vec<&str>>(),
+                vec!["P1", "P2"]
+            );
+            assert_eq!(
+                received(&requests).target,
+                "GET /incidents?statuses%5B%5D=triggered&offset=0&limit=100"
+            );
+            assert_eq!(
+                received(&requests).target,
+                "GET /incidents?statuses%5B%5D=triggered&offset=1&limit=100"
+            );
+
+            let updated = client
+                .update_incidents(&[IncidentUpdate::acknowledge("P1".to_owned())])
+                .unwrap();
+            assert_eq!(updated[0].status, IncidentStatus::Acknowledged);
+            let request = received(&requests);
+            assert_eq!(request.target, "PUT /incidents");
+            assert_eq!(request.header("From"), Some("jane@example.com"));
+            assert_eq!(
+                request.body,
+                json!({"incidents": [{"id": "P1", "type": "incident_reference", "status": "acknowledged"}]})
+            );
+        }
+
+        #[test]
+        fn creates_events_integration() {
+            use pagerduty_rs::rest::services::*;
+
+            let (url, requests) = standin(vec![(
+                201,
+                json!({"integration": {
+                    "id": "PE1U9CH",
+                    "type": "events_api_v2_inbound_integration",
+                    "name": "pagerduty-rs",
+                    "integration_key": "f7b3a0e5d1c24b6e9a8d2c4f6e8b0a1c",
+                    "service": {"id": "PIJ90N7", "type": "service_reference"}
+                }}),
+            )]);
+
+            let integration = client(&url)
+                .create_integration(
+                    "PIJ90N7",
+                    &IntegrationSpec::events_api_v2("pagerduty-rs".to_owned()),
+                )
+                .unwrap();
+            assert_eq!(
+                integration.integration_key.as_deref(),
+                Some("f7b3a0e5d1c24b6e9a8d2c4f6e8b0a1c")
+            );
+            let request = received(&requests);
+            assert_eq!(request.target, "POST /services/PIJ90N7/integrations");
+            assert_eq!(
+                request.body,
+                json!({"integration": {"type": "events_api_v2_inbound_integration", "name": "pagerduty-rs"}})
+            );
+        }
+
+        #[test]
+        fn previews_schedule_and_creates_overrides() {
+            use pagerduty_rs::rest::schedules::*;
+            use time::macros::datetime;
+
+            let (url, requests) = standin(vec![
+                (
+                    200,
+                    json!({"schedule": {"id": "", "name": "Primary", "time_zone": "UTC"}}),
+                ),
+                (
+                    201,
+                    json!([{"status": 201, "override": {
+                        "id": "PQ47DCP",
+                        "start": "2021-06-05T00:00:00Z",
+                        "end": "2021-06-06T00:00:00Z",
+                        "user": {"id": "PAM4FGS", "type": "user_reference"}
+                    }}]),
+                ),
+            ]);
+            let client = client(&url);
+            let start = datetime!(2021-06-01 00:00:00 UTC);
+
+            let layer = ScheduleLayer::new(start, 86400, &["PXPGF42"]);
+            let spec = ScheduleSpec::new("Primary".to_owned(), "UTC".to_owned(), vec![layer]);
+            let range = ScheduleRange {
+                since: Some(start),
+                until: Some(datetime!(2021-06-08 00:00:00 UTC)),
+                ..Default::default()
+            };
+            client.preview_schedule(&spec, &range).unwrap();
+            let request = received(&requests);
+            assert_eq!(
+                request.target,
+                "POST /schedules/preview?since=2021-06-01T00%3A00%3A00Z&until=2021-06-08T00%3A00%3A00Z"
+            );
+            assert_eq!(request.body["schedule"]["name"], "Primary");
+
+            let results = client
+                .create_schedule_overrides(
+                    "PI7DH85",
+                    &[Override::new(
+                        datetime!(2021-06-05 00:00:00 UTC),
+                        datetime!(2021-06-06 00:00:00 UTC),
+                        "PAM4FGS",
+                    )],
+                )
+                .unwrap();
+            assert_eq!(results[0].status, 201);
+            assert_eq!(
+                results[0].schedule_override.as_ref().unwrap().id.as_deref(),
+                Some("PQ47DCP")
+            );
+            let request = received(&requests);
+            assert_eq!(request.target, "POST /schedules/PI7DH85/overrides");
+            assert_eq!(
+                request.body,
+                json!({"overrides": [{
+                    "start": "2021-06-05T00:00:00Z",
+                    "end": "2021-06-06T00:00:00Z",
+                    "user": {"id": "PAM4FGS", "type": "user_reference"}
+                }]})
+            );
+        }
+
+        #[test]
+        fn manages_team_membership() {
+            use pagerduty_rs::rest::teams::*;
+
+            let (url, requests) = standin(vec![(204, json!(null)), (204, json!(null))]);
+            let client = client(&url);
+
+            client
+                .add_team_user("PQ9K7I8", "PXPGF42", TeamRole::Manager)
+                .unwrap();
+            let request = received(&requests);
+            assert_eq!(request.target, "PUT /teams/PQ9K7I8/users/PXPGF42");
+            assert_eq!(request.body, json!({"role": "manager"}));
+
+            client
+                .add_team_escalation_policy("PQ9K7I8", "PT20YPA")
+                .unwrap();
+            assert_eq!(
+                received(&requests).target,
+                "PUT /teams/PQ9K7I8/escalation_policies/PT20YPA"
+            );
+        }
+
+        #[test]
+        fn encodes_ids_in_paths() {
+            let (url, requests) = standin(vec![(204, json!(null))]);
+            client(&url).delete_team("../users?x=1#y").unwrap();
+            assert_eq!(
+                received(&requests).target,
+                "DELETE /teams/..%2Fusers%3Fx%3D1%23y"
+            );
+        }
+
+        #[test]
+        fn maintenance_guard_ends_window_on_drop() {
+            use pagerduty_rs::eventsv2sync::EventsV2;
+            use pagerduty_rs::rest::maintenance_windows::*;
+            use std::time::Duration;
+
+            let window = json!({"maintenance_window": {
+                "id": "PW98YIO",
+                "start_time": "2021-06-01T09:00:00Z",
+                "end_time": "2021-06-01T10:00:00Z",
+                "description": "Deploy 42",
+                "services": [{"id": "PIJ90N7", "type": "service_reference"}]
+            }});
+            let (url, requests) = standin(vec![
+                (201, window.clone()),
+                (202, json!({"status": "success"})),
+                (200, window),
+                (204, json!(null)),
+                (202, json!({"status": "success"})),
+            ]);
+            let client = client(&url);
+            let events_v2 = EventsV2::new("routing".to_owned(), None)
+                .unwrap()
+                .with_base_url(url.clone());
+
+            {
+                let (mut guard, change_error) = MaintenanceGuard::start(
+                    &client,
+                    &["PIJ90N7"],
+                    Duration::from_secs(3600),
+                    "Deploy 42".to_owned(),
+                    Some(&events_v2),
+                )
+                .unwrap();
+                assert!(change_error.is_none());
+                assert_eq!(guard.window().id, "PW98YIO");
+                guard.extend(Duration::from_secs(1800)).unwrap();
+            }
+
+            let request = received(&requests);
+            assert_eq!(request.target, "POST /maintenance_windows");
+            assert_eq!(
+                request.body["maintenance_window"]["services"],
+                json!([{"id": "PIJ90N7", "type": "service_reference"}])
+            );
+            let request = received(&requests);
+            assert_eq!(
+                request.body["payload"]["summary"],
+                "Maintenance started: Deploy 42"
+            );
+            let request = received(&requests);
+            assert_eq!(request.target, "PUT /maintenance_windows/PW98YIO");
+            assert!(request.body["maintenance_window"]["end_time"].is_string());
+            assert_eq!(
+                received(&requests).target,
+                "DELETE /maintenance_windows/PW98YIO"
+            );
+            assert_eq!(
+                received(&requests).body["payload"]["summary"],
+                "Maintenance ended: Deploy 42"
+            );
+        }
+
+        #[test]
+        fn maintenance_guard_survives_failures() {
+            use pagerduty_rs::eventsv2sync::{EventsV2, EventsV2Error};
+            use pagerduty_rs::rest::maintenance_windows::*;
+            use std::time::Duration;
+
+            let window = json!({"maintenance_window": {
+                "id": "PW98YIO",
+                "start_time": "2021-06-01T09:00:00Z",
+                "end_time": "2021-06-01T10:00:00Z",
+                "services": [{"id": "PIJ90N7", "type": "service_reference"}]
+            }});
+            let (url, requests) = standin(vec![
+                (201, window),
+                (500, json!({"status": "error"})),
+                (
+                    500,
+                    json!({"error": {"message": "Internal Server Error", "code": 2001}}),
+                ),
+                (204, json!(null)),
+                (202, json!({"status": "success"})),
+            ]);
+            let client = client(&url);
+            let events_v2 = EventsV2::new("routing".to_owned(), None)
+                .unwrap()
+                .with_base_url(url.clone());
+
+            // A failed Change event is handed back, and the window stays open.
+            let (guard, change_error) = MaintenanceGuard::start(
+                &client,
+                &["PIJ90N7"],
+                Duration::from_secs(3600),
+                "Deploy 43".to_owned(),
+                Some(&events_v2),
+            )
+            .unwrap();
+            assert!(matches!(change_error, Some(EventsV2Error::HttpError(500))));
+            assert_eq!(received(&requests).target, "POST /maintenance_windows");
+            assert_eq!(received(&requests).target, "POST /v2/change/enqueue");
+
+            // A failed delete is reported, then retried when the guard drops.
+            assert!(matches!(
+                guard.finish(),
+                Err(MaintenanceError::RestError(_))
+            ));
+            for _ in 0..2 {
+                assert_eq!(
+                    received(&requests).target,
+                    "DELETE /maintenance_windows/PW98YIO"
+                );
+            }
+            assert_eq!(
+                received(&requests).body["payload"]["summary"],
+                "Maintenance ended"
+            );
+        }
+
+        #[test]
+        fn typed_errors() {
+            let (url, _requests) = standin(vec![(
+                404,
+                json!({"error": {"message": "Not Found", "code": 2100}}),
+            )]);
+            match client(&url).get::<Value>("/incidents/NOPE", &vec![]) {
+                Err(RestError::ApiError(404, Some(body))) => {
+                    assert_eq!(body.message, "Not Found");
+                    assert_eq!(body.code, Some(2100));
+                }
+                other => panic!("expected a 404, got {:?}", other),
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    mod asynchronous {
+        use super::*;
+        use pagerduty_rs::rest::restasync::*;
+        use pagerduty_rs::rest::types::*;
+
+        #[tokio::test]
+        async fn bearer_auth() {
+            let (url, requests) = standin(vec![(200, json!({})), (200, json!({}))]);
+            let client = RestClient::new(Auth::Bearer("oauth".to_owned()), None)
+                .unwrap()
+                .with_base_url(url);
+
+            let _: Value = client.get("/abilities", &vec![]).await.unwrap();
+            let request = received(&requests);
+            assert_eq!(request.target, "GET /abilities");
+            assert_eq!(request.header("Authorization"), Some("Bearer oauth"));
+
+            let _: Value = client.put("/things/1", &json!({"a": 1})).await.unwrap();
+            let request = received(&requests);
+            assert_eq!(request.target, "PUT /things/1");
+            assert_eq!(request.body, json!({"a": 1}));
+        }
+
+        #[tokio::test]
+        async fn maintenance_guard_retries_finish() {
+            use pagerduty_rs::eventsv2async::EventsV2;
+            use pagerduty_rs::rest::maintenance_windows::guardasync::*;
+            use std::time::Duration;
+
+            let window = json!({"maintenance_window": {
+                "id": "PW98YIO",
+                "start_time": "2021-06-01T09:00:00Z",
+                "end_time": "2021-06-01T10:00:00Z",
+                "description": "Deploy 44",
+                "services": [{"id": "PIJ90N7", "type": "service_reference"}]
+            }});
+            let (url, requests) = standin(vec![
+                (201, window.clone()),
+                (202, json!({"status": "success"})),
+                (200, window),
+                (
+                    500,
+                    json!({"error": {"message": "Internal Server Error", "code": 2001}}),
+                ),
+                (204, json!(null)),
+                (202, json!({"status": "success"})),
+            ]);
+            let client = RestClient::new(Auth::Token("secret".to_owned()), None)
+                .unwrap()
+                .with_base_url(url.clone());
+            let events_v2 = EventsV2::new("routing".to_owned(), None)
+                .unwrap()
+                .with_base_url(url);
+
+            let (mut guard, change_error) = MaintenanceGuard::start(
+                &client,
+                &["PIJ90N7"],
+                Duration::from_secs(3600),
+                "Deploy 44".to_owned(),
+                Some(&events_v2),
+            )
+            .await
+            .unwrap();
+            assert!(change_error.is_none());
+            assert_eq!(received(&requests).target, "POST /maintenance_windows");
+            assert_eq!(
+                received(&requests).body["payload"]["summary"],
+                "Maintenance started: Deploy 44"
+            );
+
+            guard.extend(Duration::from_secs(1800)).await.unwrap();
+            assert_eq!(
+                received(&requests).target,
+                "PUT /maintenance_windows/PW98YIO"
+            );
+
+            // A failed delete leaves the guard able to try again.
+            assert!(matches!(
+                guard.finish().await,
+                Err(MaintenanceError::RestError(_))
+            ));
+            guard.finish().await.unwrap();
+            for _ in 0..2 {
+                assert_eq!(
+                    received(&requests).target,
+                    "DELETE /maintenance_windows/PW98YIO"
+                );
+            }
+            assert_eq!(
+                received(&requests).body["payload"]["summary"],
+                "Maintenance ended: Deploy 44"
+            );
+
+            // Once ended, finishing again sends nothing.
+            guard.finish().await.unwrap();
+        }
+    }
+}